@@ -0,0 +1,78 @@
+//! Shared example setup. Run an example with `--headless` to render a few
+//! frames into an offscreen context with GL errors turned into panics, so the
+//! examples double as smoke tests on machines without a display.
+#![allow(dead_code)]
+
+use cgmath::{Matrix4, Vector3};
+use std;
+
+use gl_fun::app::AppConfig;
+use gl_fun::debug::DebugConfig;
+use gl_fun::mesh::MeshData;
+use gl_fun::mesh_builder::MeshBuilder;
+use gl_fun::vertex_array::DrawTy;
+
+pub const WIDTH: u32 = 1024;
+pub const HEIGHT: u32 = 768;
+
+const HEADLESS_FRAMES: u32 = 3;
+
+/// Unit cube as interleaved position and color.
+const CUBE_VERTICES: [[f32; 6]; 8] = [
+    [-0.5, -0.5, -0.5, 0., 0., 0.],
+    [ 0.5, -0.5, -0.5, 1., 0., 0.],
+    [ 0.5,  0.5, -0.5, 1., 1., 0.],
+    [-0.5,  0.5, -0.5, 0., 1., 0.],
+    [-0.5, -0.5,  0.5, 0., 0., 1.],
+    [ 0.5, -0.5,  0.5, 1., 0., 1.],
+    [ 0.5,  0.5,  0.5, 1., 1., 1.],
+    [-0.5,  0.5,  0.5, 0., 1., 1.],
+];
+
+/// Counter-clockwise triangles facing out of the cube.
+const CUBE_INDICES: [u32; 36] = [
+    0, 3, 2, 2, 1, 0,
+    4, 5, 6, 6, 7, 4,
+    0, 4, 7, 7, 3, 0,
+    1, 2, 6, 6, 5, 1,
+    0, 1, 5, 5, 4, 0,
+    3, 7, 6, 6, 2, 3,
+];
+
+/// Unit cube with a color per corner.
+pub fn cube() -> MeshData {
+    MeshData {
+        positions: CUBE_VERTICES.iter().map(|vertex| [vertex[0], vertex[1], vertex[2]]).collect(),
+        colors: CUBE_VERTICES.iter().map(|vertex| [vertex[3], vertex[4], vertex[5], 1.0]).collect(),
+        indices: Some(CUBE_INDICES.to_vec()),
+        ..MeshData::new(DrawTy::Triangles)
+    }
+}
+
+/// Textured quad facing +Z, spanning `min` to `max` in XY.
+pub fn quad(min: [f32; 2], max: [f32; 2]) -> MeshData {
+    let center = Vector3::new((min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0, 0.0);
+    MeshBuilder::new()
+        .set_transform(Matrix4::from_translation(center))
+        .add_plane([max[0] - min[0], max[1] - min[1]], [1, 1])
+        .build()
+}
+
+pub fn config(title: &str) -> AppConfig {
+    let headless = std::env::args().any(|arg| arg == "--headless");
+    AppConfig {
+        title: title.into(),
+        width: WIDTH,
+        height: HEIGHT,
+        debug: if headless || cfg!(debug_assertions) {
+            Some(DebugConfig {
+                panic_on_error: headless,
+                ..DebugConfig::default()
+            })
+        } else {
+            None
+        },
+        headless_frames: if headless { Some(HEADLESS_FRAMES) } else { None },
+        ..AppConfig::default()
+    }
+}
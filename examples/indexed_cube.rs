@@ -0,0 +1,85 @@
+extern crate cgmath;
+extern crate gl;
+extern crate gl_fun;
+
+mod common;
+
+use std::path::Path;
+
+use gl_fun::app::{self, App, FramebufferSize};
+use gl_fun::camera::{Camera, OrbitController, Projection};
+use gl_fun::context::Context;
+use gl_fun::input::Input;
+use gl_fun::mesh::Mesh;
+use gl_fun::render_state::{CompareFunc, CullFace, RenderState};
+use gl_fun::shader_program::{ShaderProgram, UniformLocation};
+
+struct IndexedCube {
+    program: ShaderProgram,
+    wvp_loc: UniformLocation,
+    cube: Mesh,
+    camera: Camera,
+    orbit: OrbitController,
+    render_state: RenderState,
+    previous_time: f32,
+    time: f32,
+}
+
+impl App for IndexedCube {
+    fn init(context: &Context) -> Self {
+        let program = ShaderProgram::from_file(context, Path::new("res/shaders/color.glsl")).unwrap();
+        let wvp_loc = program.get_uniform_location("wvp");
+        let cube = Mesh::new(context, &common::cube()).unwrap();
+        let mut camera = Camera::new(Projection::Perspective {
+            fov_y: cgmath::Rad(1.0),
+            near: 0.1,
+            far: 100.0,
+        });
+        camera.position = cgmath::Point3::new(0.0, 1.5, 3.0);
+        let orbit = OrbitController::from_camera(&camera, cgmath::Point3::new(0.0, 0.0, 0.0));
+        IndexedCube {
+            program,
+            wvp_loc,
+            cube,
+            camera,
+            orbit,
+            render_state: RenderState {
+                depth_test: Some(CompareFunc::Less),
+                cull_face: Some(CullFace::Back),
+                ..RenderState::default()
+            },
+            previous_time: 0.0,
+            time: 0.0,
+        }
+    }
+
+    fn update(&mut self, _context: &Context, input: &mut Input, dt: f32) {
+        self.orbit.update(&mut self.camera, input);
+        self.previous_time = self.time;
+        self.time += dt;
+    }
+
+    fn on_resize(&mut self, _context: &Context, size: FramebufferSize) {
+        self.camera.resize(size);
+    }
+
+    fn render(&mut self, context: &Context, alpha: f32) {
+        let time = self.previous_time + (self.time - self.previous_time) * alpha;
+        let world = cgmath::Matrix4::from_angle_y(cgmath::Rad(time))
+            * cgmath::Matrix4::from_angle_x(cgmath::Rad(0.5 * time));
+        let wvp = self.camera.view_projection() * world;
+        context.apply_render_state(&self.render_state);
+        unsafe {
+            gl::ClearColor(0.1, 0.1, 0.15, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        self.program.activate().uniform_float_mat4(self.wvp_loc, wvp.as_ref());
+        self.cube.draw();
+    }
+}
+
+fn main() {
+    let mut config = common::config("Indexed cube");
+    OrbitController::bind_default_actions(&mut config.actions);
+    app::run::<IndexedCube>(config).unwrap();
+}
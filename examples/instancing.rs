@@ -0,0 +1,97 @@
+extern crate cgmath;
+extern crate gl;
+extern crate gl_fun;
+
+mod common;
+
+use std::path::Path;
+
+use gl_fun::app::{self, App, FramebufferSize};
+use gl_fun::camera::{Camera, FlyController, Projection};
+use gl_fun::context::Context;
+use gl_fun::input::Input;
+use gl_fun::mesh::Mesh;
+use gl_fun::render_state::{CompareFunc, CullFace, RenderState};
+use gl_fun::shader_program::{ShaderProgram, UniformLocation};
+
+const GRID_SIZE: usize = 16;
+
+struct Instancing {
+    program: ShaderProgram,
+    view_projection_loc: UniformLocation,
+    time_loc: UniformLocation,
+    grid_size_loc: UniformLocation,
+    cube: Mesh,
+    camera: Camera,
+    fly: FlyController,
+    render_state: RenderState,
+    previous_time: f32,
+    time: f32,
+}
+
+impl App for Instancing {
+    fn init(context: &Context) -> Self {
+        let program = ShaderProgram::from_file(context, Path::new("res/shaders/instanced.glsl")).unwrap();
+        let view_projection_loc = program.get_uniform_location("view_projection");
+        let time_loc = program.get_uniform_location("time");
+        let grid_size_loc = program.get_uniform_location("grid_size");
+        let cube = Mesh::new(context, &common::cube()).unwrap();
+        let mut camera = Camera::new(Projection::Perspective {
+            fov_y: cgmath::Rad(1.0),
+            near: 0.1,
+            far: 100.0,
+        });
+        camera.position = cgmath::Point3::new(0.0, 14.0, 22.0);
+        camera.look_at(cgmath::Point3::new(0.0, 0.0, 0.0));
+        let fly = FlyController::from_camera(&camera);
+        Instancing {
+            program,
+            view_projection_loc,
+            time_loc,
+            grid_size_loc,
+            cube,
+            camera,
+            fly,
+            render_state: RenderState {
+                depth_test: Some(CompareFunc::Less),
+                cull_face: Some(CullFace::Back),
+                ..RenderState::default()
+            },
+            previous_time: 0.0,
+            time: 0.0,
+        }
+    }
+
+    fn update(&mut self, _context: &Context, input: &mut Input, dt: f32) {
+        self.fly.update(&mut self.camera, input, dt);
+        self.previous_time = self.time;
+        self.time += dt;
+    }
+
+    fn on_resize(&mut self, _context: &Context, size: FramebufferSize) {
+        self.camera.resize(size);
+    }
+
+    fn render(&mut self, context: &Context, alpha: f32) {
+        let time = self.previous_time + (self.time - self.previous_time) * alpha;
+        context.apply_render_state(&self.render_state);
+        unsafe {
+            gl::ClearColor(0.1, 0.1, 0.15, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        let view_projection = self.camera.view_projection();
+        {
+            let mut active_program = self.program.activate();
+            active_program.uniform_float_mat4(self.view_projection_loc, view_projection.as_ref());
+            active_program.uniform_float(self.time_loc, time);
+            active_program.uniform_integer(self.grid_size_loc, GRID_SIZE as _);
+        }
+        self.cube.draw_instanced(GRID_SIZE * GRID_SIZE);
+    }
+}
+
+fn main() {
+    let mut config = common::config("Instancing");
+    FlyController::bind_default_actions(&mut config.actions);
+    app::run::<Instancing>(config).unwrap();
+}
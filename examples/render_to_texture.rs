@@ -0,0 +1,150 @@
+extern crate cgmath;
+extern crate gl;
+extern crate gl_fun;
+
+mod common;
+
+use cgmath::SquareMatrix;
+use std::path::Path;
+
+use gl_fun::app::{self, App, FramebufferSize};
+use gl_fun::context::Context;
+use gl_fun::framebuffer::{Attachment, ClearValue, Framebuffer, Renderbuffer, RenderbufferFormat};
+use gl_fun::input::Input;
+use gl_fun::mesh::Mesh;
+use gl_fun::render_state::{CompareFunc, CullFace, Rect, RenderState};
+use gl_fun::shader_program::{ShaderProgram, UniformLocation};
+use gl_fun::texture::{ImageUnitFormat, Texture, TextureFilter};
+
+const TARGET_SIZE: usize = 512;
+
+struct RenderToTexture {
+    cube_program: ShaderProgram,
+    cube_wvp_loc: UniformLocation,
+    quad_program: ShaderProgram,
+    quad_tex_loc: UniformLocation,
+    quad_wvp_loc: UniformLocation,
+    cube: Mesh,
+    quad: Mesh,
+    color_texture: Texture,
+    // Attached to `framebuffer`; kept alive with it.
+    _depth_buffer: Renderbuffer,
+    framebuffer: Framebuffer,
+    view: cgmath::Matrix4<f32>,
+    offscreen_projection: cgmath::Matrix4<f32>,
+    onscreen_projection: cgmath::Matrix4<f32>,
+    offscreen_state: RenderState,
+    onscreen_state: RenderState,
+    previous_time: f32,
+    time: f32,
+}
+
+impl App for RenderToTexture {
+    fn init(context: &Context) -> Self {
+        let cube_program = ShaderProgram::from_file(context, Path::new("res/shaders/color.glsl")).unwrap();
+        let cube_wvp_loc = cube_program.get_uniform_location("wvp");
+        let quad_program = ShaderProgram::from_file(context, Path::new("res/shaders/basic.glsl")).unwrap();
+        let quad_tex_loc = quad_program.get_uniform_location("tex");
+        let quad_wvp_loc = quad_program.get_uniform_location("wvp");
+
+        let cube = Mesh::new(context, &common::cube()).unwrap();
+        let quad = Mesh::new(context, &common::quad([-1.0, -1.0], [1.0, 1.0])).unwrap();
+
+        let mut color_texture = Texture::new(context).unwrap();
+        {
+            let mut bound_texture = color_texture.bind(0).unwrap();
+            bound_texture.allocate_image_2d(ImageUnitFormat::Rgba8, TARGET_SIZE, TARGET_SIZE).unwrap();
+            bound_texture.set_filters(TextureFilter::Linear, TextureFilter::Linear);
+        }
+        let depth_buffer = Renderbuffer::new(context, RenderbufferFormat::Depth24, TARGET_SIZE, TARGET_SIZE).unwrap();
+        let mut framebuffer = Framebuffer::new(context).unwrap();
+        {
+            let mut bound_framebuffer = framebuffer.bind();
+            bound_framebuffer.attach_texture(Attachment::Color(0), &color_texture).unwrap();
+            bound_framebuffer.attach_renderbuffer(Attachment::Depth, &depth_buffer).unwrap();
+            bound_framebuffer.check_status().unwrap();
+        }
+        context.bind_default_framebuffer();
+
+        let offscreen_projection = cgmath::perspective(cgmath::Rad(1f32), 1.0, 0.1, 100.0);
+        let view = cgmath::Matrix4::look_at(
+            cgmath::Point3::new(0f32, 1.5, 3.0),
+            cgmath::Point3::new(0f32, 0.0, 0.0),
+            cgmath::Vector3::new(0f32, 1.0, 0.0),
+        );
+        RenderToTexture {
+            cube_program,
+            cube_wvp_loc,
+            quad_program,
+            quad_tex_loc,
+            quad_wvp_loc,
+            cube,
+            quad,
+            color_texture,
+            _depth_buffer: depth_buffer,
+            framebuffer,
+            view,
+            offscreen_projection,
+            onscreen_projection: cgmath::Matrix4::identity(),
+            offscreen_state: RenderState {
+                depth_test: Some(CompareFunc::Less),
+                cull_face: Some(CullFace::Back),
+                viewport: Some(Rect {
+                    x: 0,
+                    y: 0,
+                    width: TARGET_SIZE as _,
+                    height: TARGET_SIZE as _,
+                }),
+                ..RenderState::default()
+            },
+            onscreen_state: RenderState::default(),
+            previous_time: 0.0,
+            time: 0.0,
+        }
+    }
+
+    fn update(&mut self, _context: &Context, _input: &mut Input, dt: f32) {
+        self.previous_time = self.time;
+        self.time += dt;
+    }
+
+    fn on_resize(&mut self, _context: &Context, size: FramebufferSize) {
+        self.onscreen_projection = cgmath::perspective(cgmath::Rad(1f32), size.aspect_ratio(), 0.1, 100.0);
+        self.onscreen_state.viewport = Some(size.viewport());
+    }
+
+    fn render(&mut self, context: &Context, alpha: f32) {
+        let time = self.previous_time + (self.time - self.previous_time) * alpha;
+        {
+            let _group = context.debug_group("offscreen cube");
+            context.apply_render_state(&self.offscreen_state);
+            {
+                let mut bound_framebuffer = self.framebuffer.bind();
+                bound_framebuffer.clear_color(0, ClearValue::Float([0.9, 0.9, 0.8, 1.0]));
+                bound_framebuffer.clear_depth(1.0);
+            }
+            let world = cgmath::Matrix4::from_angle_y(cgmath::Rad(time))
+                * cgmath::Matrix4::from_angle_x(cgmath::Rad(0.5 * time));
+            let wvp = self.offscreen_projection * self.view * world;
+            self.cube_program.activate().uniform_float_mat4(self.cube_wvp_loc, wvp.as_ref());
+            self.cube.draw();
+        }
+        let _group = context.debug_group("textured quad");
+        context.bind_default_framebuffer();
+        context.apply_render_state(&self.onscreen_state);
+        unsafe {
+            gl::ClearColor(0.1, 0.1, 0.15, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        let wvp = self.onscreen_projection * self.view * cgmath::Matrix4::from_angle_y(cgmath::Rad(-0.3 * time));
+        let bound_texture = self.color_texture.bind(0).unwrap();
+        let mut active_program = self.quad_program.activate();
+        active_program.uniform_integer(self.quad_tex_loc, bound_texture.unit_index as _);
+        active_program.uniform_float_mat4(self.quad_wvp_loc, wvp.as_ref());
+        self.quad.draw();
+    }
+}
+
+fn main() {
+    app::run::<RenderToTexture>(common::config("Render to texture")).unwrap();
+}
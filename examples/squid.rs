@@ -0,0 +1,179 @@
+extern crate cgmath;
+extern crate gl;
+extern crate gl_fun;
+
+mod common;
+
+use cgmath::Rotation3;
+use std::path::Path;
+
+use gl_fun::app::{self, App, FramebufferSize, FrameStats};
+use gl_fun::camera::{Camera, Projection};
+use gl_fun::context::Context;
+use gl_fun::image::Image;
+use gl_fun::input::{ActionMap, Input};
+use gl_fun::mesh::Mesh;
+use gl_fun::query::{QueryRing, QueryTy};
+use gl_fun::render_state::{BlendState, CompareFunc, RenderState};
+use gl_fun::scene::{NodeId, Scene, Transform};
+use gl_fun::shader_program::{ShaderProgram, UniformLocation};
+use gl_fun::texture::Texture;
+
+/// Radians per second.
+const SPIN_SPEED: f32 = 3.0;
+/// Radians per second squared while `faster` or `slower` is held.
+const SPIN_ACCELERATION: f32 = 2.0;
+
+struct Squid {
+    program: ShaderProgram,
+    tex_loc: UniformLocation,
+    wvp_loc: UniformLocation,
+    texture: Texture,
+    quad: Mesh,
+    camera: Camera,
+    /// Squid quads labelled for debug groups; all share one texture and
+    /// mesh.
+    scene: Scene<&'static str>,
+    turntable: NodeId,
+    baby: NodeId,
+    render_state: RenderState,
+    timer: Option<QueryRing>,
+    previous_angle: f32,
+    angle: f32,
+    spin_speed: f32,
+    is_paused: bool,
+    should_quit: bool,
+}
+
+impl App for Squid {
+    fn init(context: &Context) -> Self {
+        let mut program = ShaderProgram::from_file(context, Path::new("res/shaders/basic.glsl")).unwrap();
+        program.set_label("basic");
+        let tex_loc = program.get_uniform_location("tex");
+        let wvp_loc = program.get_uniform_location("wvp");
+        let img = Image::from_file(Path::new("res/images/squid.png")).unwrap();
+        let mut texture = Texture::new(context).unwrap();
+        texture.set_label("squid");
+        texture
+            .bind(0)
+            .unwrap()
+            .upload_image_2d(img.format.to_texture_format().unwrap(), img.width, img.height, &img.pixels)
+            .unwrap();
+        let mut quad = Mesh::new(context, &common::quad([-1.0, -1.0], [1.0, 1.3])).unwrap();
+        quad.set_label("squid quad");
+        let mut camera = Camera::new(Projection::Perspective {
+            fov_y: cgmath::Rad(1.0),
+            near: 0.1,
+            far: 100.0,
+        });
+        camera.position = cgmath::Point3::new(0.0, 2.0, 10.0);
+        camera.look_at(cgmath::Point3::new(0.0, 0.0, 0.0));
+        let mut scene = Scene::new();
+        let turntable = scene.add(None, Transform::identity(), None).unwrap();
+        let squid = scene.add(Some(turntable), Transform::identity(), Some("squid")).unwrap();
+        let baby = scene.add(Some(squid), Transform::identity(), Some("baby squid")).unwrap();
+        Squid {
+            program,
+            tex_loc,
+            wvp_loc,
+            texture,
+            quad,
+            camera,
+            scene,
+            turntable,
+            baby,
+            render_state: RenderState {
+                blend: vec![Some(BlendState::alpha_blending())],
+                depth_test: Some(CompareFunc::Less),
+                ..RenderState::default()
+            },
+            timer: QueryRing::new(context, QueryTy::TimeElapsed, 4).ok(),
+            previous_angle: 0.0,
+            angle: 0.0,
+            spin_speed: SPIN_SPEED,
+            is_paused: false,
+            should_quit: false,
+        }
+    }
+
+    fn update(&mut self, _context: &Context, input: &mut Input, dt: f32) {
+        if input.was_action_pressed("pause") {
+            self.is_paused = !self.is_paused;
+        }
+        if input.is_action_down("faster") {
+            self.spin_speed += SPIN_ACCELERATION * dt;
+        }
+        if input.is_action_down("slower") {
+            self.spin_speed -= SPIN_ACCELERATION * dt;
+        }
+        self.should_quit |= input.was_action_pressed("quit");
+        self.previous_angle = self.angle;
+        if !self.is_paused {
+            self.angle += self.spin_speed * dt;
+        }
+    }
+
+    fn on_resize(&mut self, _context: &Context, size: FramebufferSize) {
+        self.camera.resize(size);
+    }
+
+    fn render(&mut self, context: &Context, alpha: f32) {
+        let angle = self.previous_angle + (self.angle - self.previous_angle) * alpha;
+        let spin = cgmath::Quaternion::from_angle_y(cgmath::Rad(angle));
+        self.scene.set_transform(self.turntable, Transform::from_rotation(spin)).unwrap();
+        self.scene
+            .set_transform(
+                self.baby,
+                Transform {
+                    translation: cgmath::Vector3::new(1.6, 0.8, 0.5),
+                    rotation: cgmath::Quaternion::from_angle_y(cgmath::Rad(-2.0 * angle)),
+                    scale: cgmath::Vector3::new(0.4, 0.4, 0.4),
+                },
+            )
+            .unwrap();
+        let view_projection = self.camera.view_projection();
+        context.apply_render_state(&self.render_state);
+        unsafe {
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        let _timer = self.timer.as_mut().map(|timer| timer.begin().unwrap());
+        let Squid {
+            ref scene,
+            ref mut program,
+            ref mut texture,
+            ref mut quad,
+            tex_loc,
+            wvp_loc,
+            ..
+        } = *self;
+        scene.render(&mut |_, world: &cgmath::Matrix4<f32>, label: &&'static str| {
+            let _group = context.debug_group(label);
+            let wvp = view_projection * world;
+            let bound_texture = texture.bind(0).unwrap();
+            let mut active_program = program.activate();
+            active_program.uniform_integer(tex_loc, bound_texture.unit_index as _);
+            active_program.uniform_float_mat4(wvp_loc, wvp.as_ref());
+            quad.draw();
+        });
+    }
+
+    fn on_stats(&mut self, stats: &FrameStats) {
+        if let Some(fps) = stats.fps() {
+            print!("{:.0} fps", fps);
+            if let Some(nanos) = self.timer.as_ref().and_then(|timer| timer.latest_result()) {
+                print!(", squid pass {:.3} ms", nanos as f64 / 1_000_000.0);
+            }
+            println!();
+        }
+    }
+
+    fn should_quit(&self) -> bool {
+        self.should_quit
+    }
+}
+
+fn main() {
+    let mut config = common::config("Open GL is fun!");
+    config.actions = ActionMap::from_file(Path::new("res/input/squid.cfg")).unwrap();
+    app::run::<Squid>(config).unwrap();
+}
@@ -0,0 +1,81 @@
+extern crate cgmath;
+extern crate gl;
+extern crate gl_fun;
+
+mod common;
+
+use std::path::Path;
+
+use gl_fun::app::{self, App, FramebufferSize};
+use gl_fun::context::Context;
+use gl_fun::image::Image;
+use gl_fun::input::Input;
+use gl_fun::mesh::Mesh;
+use gl_fun::render_state::{BlendState, RenderState};
+use gl_fun::shader_program::{ShaderProgram, UniformLocation};
+use gl_fun::texture::{Texture, TextureFilter};
+
+struct TexturedQuad {
+    program: ShaderProgram,
+    tex_loc: UniformLocation,
+    wvp_loc: UniformLocation,
+    texture: Texture,
+    quad: Mesh,
+    render_state: RenderState,
+    aspect_ratio: f32,
+}
+
+impl App for TexturedQuad {
+    fn init(context: &Context) -> Self {
+        let program = ShaderProgram::from_file(context, Path::new("res/shaders/basic.glsl")).unwrap();
+        let tex_loc = program.get_uniform_location("tex");
+        let wvp_loc = program.get_uniform_location("wvp");
+        let img = Image::from_file(Path::new("res/images/squid.png")).unwrap();
+        let mut texture = Texture::new(context).unwrap();
+        {
+            let mut bound_texture = texture.bind(0).unwrap();
+            bound_texture
+                .upload_image_2d(img.format.to_texture_format().unwrap(), img.width, img.height, &img.pixels)
+                .unwrap();
+            bound_texture.set_filters(TextureFilter::Linear, TextureFilter::Linear);
+        }
+        let quad = Mesh::new(context, &common::quad([-0.5, -0.5], [0.5, 0.5])).unwrap();
+        TexturedQuad {
+            program,
+            tex_loc,
+            wvp_loc,
+            texture,
+            quad,
+            render_state: RenderState {
+                blend: vec![Some(BlendState::alpha_blending())],
+                ..RenderState::default()
+            },
+            aspect_ratio: 1.0,
+        }
+    }
+
+    fn update(&mut self, _context: &Context, _input: &mut Input, _dt: f32) {}
+
+    fn on_resize(&mut self, _context: &Context, size: FramebufferSize) {
+        self.aspect_ratio = size.aspect_ratio();
+    }
+
+    fn render(&mut self, context: &Context, _alpha: f32) {
+        // Keeps the quad square regardless of the window shape.
+        let wvp = cgmath::Matrix4::from_nonuniform_scale(1.5 / self.aspect_ratio, 1.5, 1.0);
+        context.apply_render_state(&self.render_state);
+        unsafe {
+            gl::ClearColor(0.1, 0.1, 0.15, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+        }
+        let bound_texture = self.texture.bind(0).unwrap();
+        let mut active_program = self.program.activate();
+        active_program.uniform_integer(self.tex_loc, bound_texture.unit_index as _);
+        active_program.uniform_float_mat4(self.wvp_loc, wvp.as_ref());
+        self.quad.draw();
+    }
+}
+
+fn main() {
+    app::run::<TexturedQuad>(common::config("Textured quad")).unwrap();
+}
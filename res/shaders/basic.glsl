@@ -0,0 +1,27 @@
+#version 330 core
+
+#pragma stage vertex
+
+layout(location = 0) in vec3 position;
+layout(location = 2) in vec2 uv;
+
+uniform mat4 wvp;
+
+smooth out vec2 frag_uv;
+
+void main() {
+    gl_Position = wvp * vec4(position, 1);
+    frag_uv = uv;
+}
+
+#pragma stage fragment
+
+smooth in vec2 frag_uv;
+
+uniform sampler2D tex;
+
+out vec4 fs_out;
+
+void main() {
+    fs_out = texture(tex, frag_uv);
+}
@@ -0,0 +1,25 @@
+#version 330 core
+
+#pragma stage vertex
+
+layout(location = 0) in vec3 position;
+layout(location = 4) in vec3 color;
+
+uniform mat4 wvp;
+
+smooth out vec3 frag_color;
+
+void main() {
+    gl_Position = wvp * vec4(position, 1);
+    frag_color = color;
+}
+
+#pragma stage fragment
+
+smooth in vec3 frag_color;
+
+out vec4 fs_out;
+
+void main() {
+    fs_out = vec4(frag_color, 1);
+}
@@ -0,0 +1,35 @@
+#version 330 core
+
+#pragma stage vertex
+
+layout(location = 0) in vec3 position;
+layout(location = 4) in vec3 color;
+
+uniform mat4 view_projection;
+uniform float time;
+uniform int grid_size;
+
+smooth out vec3 frag_color;
+
+void main() {
+    vec2 cell = vec2(gl_InstanceID % grid_size, gl_InstanceID / grid_size) - 0.5 * float(grid_size - 1);
+    float angle = time + 0.3 * float(gl_InstanceID);
+    mat3 rotation = mat3(
+        cos(angle), 0, -sin(angle),
+        0, 1, 0,
+        sin(angle), 0, cos(angle)
+    );
+    vec3 world_position = rotation * (0.6 * position) + vec3(cell.x, 0, cell.y) * 1.5;
+    gl_Position = view_projection * vec4(world_position, 1);
+    frag_color = color;
+}
+
+#pragma stage fragment
+
+smooth in vec3 frag_color;
+
+out vec4 fs_out;
+
+void main() {
+    fs_out = vec4(frag_color, 1);
+}
@@ -0,0 +1,443 @@
+use gl;
+use glutin;
+use glutin::GlContext;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use context::Context;
+use debug::{DebugConfig, DebugMessage, DebugMessageTy, DebugSeverity, DebugSource};
+use input::{ActionMap, Input, InputEvent};
+use render_state::Rect;
+
+const FRAME_HISTORY_LEN: usize = 120;
+
+/// A demo or tool driven by `run`. `update` runs at the fixed rate set in
+/// `AppConfig`, independent of the display refresh rate, while `render` runs
+/// once per displayed frame.
+pub trait App {
+    fn init(context: &Context) -> Self where Self: Sized;
+
+    /// Advances the simulation by `dt` seconds, always `1 / update_rate`.
+    /// `input` reports presses and motion since the previous update.
+    fn update(&mut self, context: &Context, input: &mut Input, dt: f32);
+
+    /// Draws the current state. `alpha` in `[0, 1)` is how far real time has
+    /// progressed towards the next update, for interpolating between the
+    /// previous and current simulation states.
+    fn render(&mut self, context: &Context, alpha: f32);
+
+    fn on_event(&mut self, _context: &Context, _event: &glutin::WindowEvent) {}
+
+    /// Called after `init` and whenever the framebuffer size or scale factor
+    /// changes. The viewport already covers the new framebuffer.
+    fn on_resize(&mut self, _context: &Context, _size: FramebufferSize) {}
+
+    /// Called every `AppConfig::stats_interval`.
+    fn on_stats(&mut self, _stats: &FrameStats) {}
+
+    /// Checked after every frame; returning true stops the runner.
+    fn should_quit(&self) -> bool {
+        false
+    }
+
+    /// Called once before the window and context are destroyed, while GL
+    /// objects can still be released.
+    fn shutdown(&mut self, _context: &Context) {}
+}
+
+pub struct AppConfig {
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    pub gl_version: (u8, u8),
+    /// MSAA samples for the window, 0 to disable.
+    pub samples: u16,
+    pub vsync: bool,
+    /// Fixed updates per second.
+    pub update_rate: f64,
+    /// Caps the updates run to catch up after a slow frame; time beyond the
+    /// cap is dropped so a stall does not snowball.
+    pub max_updates_per_frame: u32,
+    pub stats_interval: Duration,
+    pub debug: Option<DebugConfig>,
+    /// Initial action bindings for the runner's `Input`.
+    pub actions: ActionMap,
+    /// Renders this many frames into a headless context instead of opening a
+    /// window, with simulated time, then exits.
+    pub headless_frames: Option<u32>,
+}
+
+/// Size of the window's framebuffer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FramebufferSize {
+    /// In physical pixels, as passed to `glViewport`.
+    pub width: u32,
+    pub height: u32,
+    /// Physical pixels per logical point; above 1 on HiDPI displays.
+    pub scale_factor: f32,
+}
+
+/// Frame times over the last `FRAME_HISTORY_LEN` frames.
+#[derive(Clone, Debug, Default)]
+pub struct FrameStats {
+    pub frame_count: u64,
+    pub update_count: u64,
+    frame_times: VecDeque<f32>,
+}
+
+#[derive(Debug)]
+pub struct AppError {
+    pub message: String,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            title: "gl_fun".into(),
+            width: 1024,
+            height: 768,
+            gl_version: (3, 3),
+            samples: 4,
+            vsync: true,
+            update_rate: 60.0,
+            max_updates_per_frame: 8,
+            stats_interval: Duration::from_secs(1),
+            debug: if cfg!(debug_assertions) { Some(DebugConfig::default()) } else { None },
+            actions: ActionMap::new(),
+            headless_frames: None,
+        }
+    }
+}
+
+impl FramebufferSize {
+    /// Width over height, or 1 while the window is minimized.
+    pub fn aspect_ratio(&self) -> f32 {
+        if self.is_empty() {
+            1.0
+        } else {
+            self.width as f32 / self.height as f32
+        }
+    }
+
+    /// Size in points, for laying out UI independently of the display density.
+    pub fn logical_size(&self) -> (f32, f32) {
+        (self.width as f32 / self.scale_factor, self.height as f32 / self.scale_factor)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub fn viewport(&self) -> Rect {
+        Rect {
+            x: 0,
+            y: 0,
+            width: self.width as _,
+            height: self.height as _,
+        }
+    }
+}
+
+impl FrameStats {
+    pub fn record_frame(&mut self, frame_time: f32, updates: u32) {
+        if self.frame_times.len() == FRAME_HISTORY_LEN {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+        self.frame_count += 1;
+        self.update_count += updates as u64;
+    }
+
+    pub fn last_frame_time(&self) -> Option<f32> {
+        self.frame_times.back().cloned()
+    }
+
+    pub fn average_frame_time(&self) -> Option<f32> {
+        if self.frame_times.is_empty() {
+            return None;
+        }
+        Some(self.frame_times.iter().sum::<f32>() / self.frame_times.len() as f32)
+    }
+
+    pub fn max_frame_time(&self) -> Option<f32> {
+        self.frame_times.iter().cloned().reduce(f32::max)
+    }
+
+    pub fn fps(&self) -> Option<f32> {
+        self.average_frame_time()
+            .and_then(|average| if average > 0.0 { Some(1.0 / average) } else { None })
+    }
+}
+
+/// Accumulates real time and hands it out in fixed steps.
+struct FixedTimestep {
+    dt: f64,
+    accumulator: f64,
+    max_steps: u32,
+}
+
+impl FixedTimestep {
+    fn new(update_rate: f64, max_steps: u32) -> Self {
+        FixedTimestep {
+            dt: 1.0 / update_rate,
+            accumulator: 0.0,
+            max_steps: max_steps.max(1),
+        }
+    }
+
+    /// Adds `elapsed` seconds and returns how many updates are due. Whole
+    /// steps past `max_steps` are dropped, keeping the fraction of a step so
+    /// `alpha` stays below 1.
+    fn advance(&mut self, elapsed: f64) -> u32 {
+        self.accumulator += elapsed;
+        let mut steps = 0;
+        while self.accumulator >= self.dt && steps < self.max_steps {
+            self.accumulator -= self.dt;
+            steps += 1;
+        }
+        if self.accumulator >= self.dt {
+            self.accumulator %= self.dt;
+        }
+        steps
+    }
+
+    fn alpha(&self) -> f32 {
+        (self.accumulator / self.dt) as f32
+    }
+}
+
+/// Creates the window or headless context described by `config` and drives
+/// `A` until the window is closed or `App::should_quit` returns true.
+pub fn run<A: App>(config: AppConfig) -> Result<(), AppError> {
+    if config.update_rate <= 0.0 {
+        return Err(AppError {
+            message: format!("Update rate must be positive, got {}", config.update_rate),
+        });
+    }
+    match config.headless_frames {
+        Some(frames) => run_headless::<A>(config, frames),
+        None => run_windowed::<A>(config),
+    }
+}
+
+fn create_context(config: &mut AppConfig, get_proc_address: &dyn Fn(&str) -> *const ()) -> Context {
+    let context = Context::load_with(|symbol| get_proc_address(symbol) as *const _);
+    if let Some(debug_config) = config.debug.take() {
+        context.enable_debug(debug_config);
+    }
+    context
+}
+
+fn resize<A: App>(app: &mut A, context: &Context, size: FramebufferSize) {
+    context.set_viewport(size.viewport());
+    app.on_resize(context, size);
+}
+
+fn run_windowed<A: App>(mut config: AppConfig) -> Result<(), AppError> {
+    let mut events_loop = glutin::EventsLoop::new();
+    let window = glutin::WindowBuilder::new()
+        .with_title(config.title.clone())
+        .with_dimensions(config.width, config.height);
+    let context_builder = glutin::ContextBuilder::new()
+        .with_gl(glutin::GlRequest::Specific(glutin::Api::OpenGl, config.gl_version))
+        .with_gl_debug_flag(config.debug.is_some())
+        .with_multisampling(config.samples)
+        .with_vsync(config.vsync)
+        .with_depth_buffer(24);
+    let gl_window = glutin::GlWindow::new(window, context_builder, &events_loop).map_err(|err| {
+        AppError {
+            message: format!("Failed to create window: {}", err),
+        }
+    })?;
+    unsafe {
+        gl_window.make_current().map_err(|err| {
+            AppError {
+                message: format!("Failed to make GL context current: {:?}", err),
+            }
+        })?;
+    }
+    let context = create_context(&mut config, &|symbol| gl_window.get_proc_address(symbol));
+    let mut app = A::init(&context);
+    let (width, height) = gl_window.get_inner_size().unwrap_or((config.width, config.height));
+    let mut size = FramebufferSize {
+        width,
+        height,
+        scale_factor: gl_window.hidpi_factor(),
+    };
+    resize(&mut app, &context, size);
+    let mut input = Input::new(config.actions);
+    let mut timestep = FixedTimestep::new(config.update_rate, config.max_updates_per_frame);
+    let mut stats = FrameStats::default();
+    let mut last_frame = Instant::now();
+    let mut last_stats = last_frame;
+    let mut running = true;
+    while running {
+        let mut new_size = size;
+        events_loop.poll_events(|event| {
+            match event {
+                glutin::Event::WindowEvent { event, .. } => {
+                    match event {
+                        glutin::WindowEvent::Closed => running = false,
+                        glutin::WindowEvent::Resized(width, height) => {
+                            new_size.width = width;
+                            new_size.height = height;
+                        },
+                        glutin::WindowEvent::HiDPIFactorChanged(scale_factor) => {
+                            new_size.scale_factor = scale_factor;
+                        },
+                        _ => (),
+                    }
+                    if let Some(input_event) = InputEvent::from_window_event(&event) {
+                        input.handle_event(input_event);
+                    }
+                    app.on_event(&context, &event);
+                },
+                glutin::Event::DeviceEvent { event, .. } => {
+                    if let Some(input_event) = InputEvent::from_device_event(&event) {
+                        input.handle_event(input_event);
+                    }
+                },
+                _ => (),
+            }
+        });
+        if new_size != size {
+            size = new_size;
+            gl_window.resize(size.width, size.height);
+            resize(&mut app, &context, size);
+        }
+        let now = Instant::now();
+        let frame_time = duration_secs(now - last_frame);
+        last_frame = now;
+        let updates = timestep.advance(frame_time);
+        for _ in 0..updates {
+            app.update(&context, &mut input, timestep.dt as f32);
+            input.end_update();
+        }
+        if let Some(is_grabbed) = input.take_cursor_grab_change() {
+            let cursor_state = if is_grabbed { glutin::CursorState::Grab } else { glutin::CursorState::Normal };
+            // The app sees the failure through `Input::is_cursor_grabbed`.
+            if let Err(err) = gl_window.set_cursor_state(cursor_state) {
+                context.log_debug_message(DebugMessage {
+                    source: DebugSource::WindowSystem,
+                    ty: DebugMessageTy::Other,
+                    severity: DebugSeverity::Medium,
+                    id: 0,
+                    message: format!("Failed to set cursor state: {}", err),
+                });
+                input.set_cursor_grabbed(false);
+            }
+        }
+        if !size.is_empty() {
+            app.render(&context, timestep.alpha());
+        }
+        gl_window.swap_buffers().map_err(|err| {
+            AppError {
+                message: format!("Failed to swap buffers: {:?}", err),
+            }
+        })?;
+        stats.record_frame(frame_time as f32, updates);
+        if now - last_stats >= config.stats_interval {
+            last_stats = now;
+            app.on_stats(&stats);
+        }
+        running = running && !app.should_quit();
+    }
+    app.shutdown(&context);
+    Ok(())
+}
+
+fn run_headless<A: App>(mut config: AppConfig, frames: u32) -> Result<(), AppError> {
+    let headless = glutin::HeadlessRendererBuilder::new(config.width, config.height)
+        .with_gl(glutin::GlRequest::Specific(glutin::Api::OpenGl, config.gl_version))
+        .with_gl_debug_flag(config.debug.is_some())
+        .build()
+        .map_err(|err| {
+            AppError {
+                message: format!("Failed to create headless GL context: {}", err),
+            }
+        })?;
+    unsafe {
+        headless.make_current().map_err(|err| {
+            AppError {
+                message: format!("Failed to make GL context current: {:?}", err),
+            }
+        })?;
+    }
+    let context = create_context(&mut config, &|symbol| headless.get_proc_address(symbol));
+    let mut app = A::init(&context);
+    let size = FramebufferSize {
+        width: config.width,
+        height: config.height,
+        scale_factor: 1.0,
+    };
+    resize(&mut app, &context, size);
+    let mut input = Input::new(config.actions);
+    let mut timestep = FixedTimestep::new(config.update_rate, config.max_updates_per_frame);
+    let mut stats = FrameStats::default();
+    // Simulates a display refreshing at the update rate.
+    let frame_time = timestep.dt;
+    for _ in 0..frames {
+        let updates = timestep.advance(frame_time);
+        for _ in 0..updates {
+            app.update(&context, &mut input, timestep.dt as f32);
+            input.end_update();
+        }
+        app.render(&context, timestep.alpha());
+        stats.record_frame(frame_time as f32, updates);
+        if app.should_quit() {
+            break;
+        }
+    }
+    unsafe {
+        gl::Finish();
+    }
+    context.check_errors("gl::Finish");
+    app.on_stats(&stats);
+    app.shutdown(&context);
+    Ok(())
+}
+
+fn duration_secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_are_due_once_their_time_has_passed() {
+        let mut timestep = FixedTimestep::new(4.0, 8);
+        assert_eq!(timestep.advance(0.125), 0);
+        assert_eq!(timestep.alpha(), 0.5);
+        assert_eq!(timestep.advance(0.125), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+        assert_eq!(timestep.advance(0.625), 2);
+        assert_eq!(timestep.alpha(), 0.5);
+        assert_eq!(timestep.advance(0.0), 0);
+        assert_eq!(timestep.alpha(), 0.5);
+    }
+
+    #[test]
+    fn slow_frames_drop_steps_past_the_cap() {
+        let mut timestep = FixedTimestep::new(4.0, 3);
+        assert_eq!(timestep.advance(2.125), 3);
+        assert_eq!(timestep.alpha(), 0.5);
+        // The dropped steps are not caught up on later.
+        assert_eq!(timestep.advance(0.125), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+        // Exactly one step over the cap leaves nothing to interpolate.
+        assert_eq!(timestep.advance(1.0), 3);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn alpha_stays_below_one() {
+        let mut timestep = FixedTimestep::new(60.0, 5);
+        for i in 0..1000 {
+            timestep.advance(0.001 * (i % 97) as f64);
+            let alpha = timestep.alpha();
+            assert!((0.0..1.0).contains(&alpha), "alpha {} after frame {}", alpha, i);
+        }
+    }
+}
@@ -2,6 +2,12 @@ use gl;
 use gl::types::*;
 use std;
 use std::marker::PhantomData;
+use std::slice;
+use std::time::Duration;
+
+use context::Context;
+use debug::{set_object_label, DebugMessage, DebugMessageTy, DebugSeverity, DebugSource};
+use fence::Fence;
 
 pub trait BufferTy {
     fn to_gl_buffer_ty() -> GLenum;
@@ -19,6 +25,15 @@ pub enum BufferUsageHint {
     StreamDraw,
 }
 
+pub enum MapAccess {
+    Read,
+    /// Discards the previous contents of the mapped range.
+    Write,
+    /// Like `Write`, but does not wait for the GPU to finish using the range.
+    /// The caller must make sure it is no longer in use, e.g. with a `Fence`.
+    WriteUnsynchronized,
+}
+
 #[derive(Debug)]
 pub struct BufferError {
     pub message: String,
@@ -26,17 +41,44 @@ pub struct BufferError {
 
 pub struct Buffer<Ty: BufferTy> {
     ty: PhantomData<Ty>,
+    context: Context,
     pub gl_id: GLuint,
+    size: usize,
 }
 
 pub struct BoundBuffer<'a, Ty: 'a + BufferTy> {
     buffer: &'a mut Buffer<Ty>,
 }
 
+/// A mapped range of a buffer. The buffer cannot be used for drawing while it
+/// is mapped. Dropping it unmaps it as well, but only reports lost contents
+/// as a debug message, where `unmap` returns them as an error.
+pub struct MappedBuffer<'a, Ty: 'a + BufferTy> {
+    buffer: &'a mut Buffer<Ty>,
+    data: *mut u8,
+    len: usize,
+    is_writable: bool,
+}
+
+/// A buffer split into segments that are written in turn, for data that
+/// changes every frame. A fence is placed after each segment is used, and a
+/// segment is only rewritten once the GPU has passed that fence, so writes
+/// neither stall on draws still reading other segments nor corrupt them.
+/// Without sync objects every write waits for the GPU instead.
+pub struct StreamBuffer<Ty: BufferTy> {
+    buffer: Buffer<Ty>,
+    segment_size: usize,
+    fences: Vec<Option<Fence>>,
+    current_segment: Option<usize>,
+    has_fences: bool,
+}
+
 pub type VertexBuffer = Buffer<VertexBufferTy>;
 pub type BoundVertexBuffer<'a> = BoundBuffer<'a, VertexBufferTy>;
 pub type IndexBuffer = Buffer<IndexBufferTy>;
 pub type BoundIndexBuffer<'a> = BoundBuffer<'a, IndexBufferTy>;
+pub type StreamVertexBuffer = StreamBuffer<VertexBufferTy>;
+pub type StreamIndexBuffer = StreamBuffer<IndexBufferTy>;
 
 impl BufferTy for VertexBufferTy {
     fn to_gl_buffer_ty() -> GLenum { gl::ARRAY_BUFFER }
@@ -51,16 +93,32 @@ impl BufferUsageHint {
         match *self {
             BufferUsageHint::DynamicRead => gl::DYNAMIC_READ,
             BufferUsageHint::DynamicDraw => gl::DYNAMIC_DRAW,
-            BufferUsageHint::StaticRead => gl::DYNAMIC_READ,
-            BufferUsageHint::StaticDraw => gl::DYNAMIC_DRAW,
+            BufferUsageHint::StaticRead => gl::STATIC_READ,
+            BufferUsageHint::StaticDraw => gl::STATIC_DRAW,
             BufferUsageHint::StreamRead => gl::STREAM_READ,
             BufferUsageHint::StreamDraw => gl::STREAM_DRAW,
         }
     }
 }
 
+impl MapAccess {
+    fn is_writable(&self) -> bool {
+        !matches!(*self, MapAccess::Read)
+    }
+
+    fn to_gl_access_flags(&self) -> GLbitfield {
+        match *self {
+            MapAccess::Read => gl::MAP_READ_BIT,
+            MapAccess::Write => gl::MAP_WRITE_BIT | gl::MAP_INVALIDATE_RANGE_BIT,
+            MapAccess::WriteUnsynchronized => {
+                gl::MAP_WRITE_BIT | gl::MAP_INVALIDATE_RANGE_BIT | gl::MAP_UNSYNCHRONIZED_BIT
+            },
+        }
+    }
+}
+
 impl<Ty: BufferTy> Buffer<Ty> {
-    pub fn new() -> Result<Self, BufferError> {
+    pub fn new(context: &Context) -> Result<Self, BufferError> {
         let gl_id = unsafe {
             let mut gl_id = 0;
             gl::GenBuffers(1, &mut gl_id);
@@ -71,12 +129,24 @@ impl<Ty: BufferTy> Buffer<Ty> {
             }
             gl_id
         };
+        context.check_errors("Buffer::new");
         Ok(Buffer::<Ty> {
             ty: PhantomData,
-            gl_id: gl_id,
+            context: context.clone(),
+            gl_id,
+            size: 0,
         })
     }
 
+    /// Size in bytes of the storage last allocated or uploaded.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn set_label(&mut self, label: &str) {
+        set_object_label(&self.context, gl::BUFFER, self.gl_id, label);
+    }
+
     pub fn bind<'a>(&'a mut self) -> BoundBuffer<'a, Ty> {
         BoundBuffer::new(self)
     }
@@ -84,6 +154,7 @@ impl<Ty: BufferTy> Buffer<Ty> {
 
 impl<Ty: BufferTy> Drop for Buffer<Ty> {
     fn drop(&mut self) {
+        self.context.forget_buffer(self.gl_id);
         unsafe {
             gl::DeleteBuffers(1, &self.gl_id);
         }
@@ -93,22 +164,233 @@ impl<Ty: BufferTy> Drop for Buffer<Ty> {
 
 impl<'a, Ty: BufferTy> BoundBuffer<'a, Ty> {
     fn new(buffer: &'a mut Buffer<Ty>) -> Self {
-        unsafe {
-            gl::BindBuffer(Ty::to_gl_buffer_ty(), buffer.gl_id);
-        }
+        buffer.context.bind_buffer(Ty::to_gl_buffer_ty(), buffer.gl_id);
+        buffer.context.check_errors("Buffer::bind");
         BoundBuffer {
-            buffer: buffer,
+            buffer,
         }
     }
 
+    /// Rebinds the buffer if something else was bound to its target since this
+    /// guard was created.
+    pub fn ensure_bound(&self) {
+        self.buffer.context.bind_buffer(Ty::to_gl_buffer_ty(), self.buffer.gl_id);
+    }
+
+    /// Allocates `size` bytes of uninitialized storage, e.g. as a transform
+    /// feedback target.
+    pub fn allocate(&mut self, size: usize, usage_hint: BufferUsageHint) {
+        self.ensure_bound();
+        unsafe {
+            gl::BufferData(
+                Ty::to_gl_buffer_ty(),
+                size as _,
+                std::ptr::null(),
+                usage_hint.to_gl_usage_hint(),
+            );
+        }
+        self.buffer.context.check_errors("BoundBuffer::allocate");
+        self.buffer.size = size;
+    }
+
     pub fn upload<Datum: Sized>(&mut self, data: &[Datum], usage_hint: BufferUsageHint) {
+        self.ensure_bound();
         unsafe {
             gl::BufferData(
                 Ty::to_gl_buffer_ty(),
-                (std::mem::size_of::<Datum>() * data.len()) as _,
+                std::mem::size_of_val(data) as _,
                 data.as_ptr() as *const _,
                 usage_hint.to_gl_usage_hint(),
             );
         }
+        self.buffer.context.check_errors("BoundBuffer::upload");
+        self.buffer.size = std::mem::size_of_val(data);
+    }
+
+    /// Maps `size` bytes starting at byte `offset`.
+    pub fn map_range<'b>(
+        &'b mut self,
+        offset: usize,
+        size: usize,
+        access: MapAccess,
+    ) -> Result<MappedBuffer<'b, Ty>, BufferError> {
+        if size == 0 || offset + size > self.buffer.size {
+            return Err(BufferError {
+                message: format!(
+                    "Cannot map {} bytes at offset {} of a {} byte buffer",
+                    size,
+                    offset,
+                    self.buffer.size,
+                ),
+            });
+        }
+        self.ensure_bound();
+        let data = unsafe {
+            gl::MapBufferRange(
+                Ty::to_gl_buffer_ty(),
+                offset as _,
+                size as _,
+                access.to_gl_access_flags(),
+            )
+        };
+        self.buffer.context.check_errors("BoundBuffer::map_range");
+        if data.is_null() {
+            return Err(BufferError {
+                message: "Failed to map GPU buffer".into(),
+            });
+        }
+        Ok(MappedBuffer {
+            buffer: &mut *self.buffer,
+            data: data as *mut u8,
+            len: size,
+            is_writable: access.is_writable(),
+        })
+    }
+}
+
+impl<'a, Ty: BufferTy> MappedBuffer<'a, Ty> {
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.data, self.len) }
+    }
+
+    /// Fails on a `MapAccess::Read` mapping.
+    pub fn as_mut_slice(&mut self) -> Result<&mut [u8], BufferError> {
+        self.check_writable()?;
+        Ok(unsafe { slice::from_raw_parts_mut(self.data, self.len) })
+    }
+
+    /// Copies `data` to the start of the mapped range.
+    pub fn write<Datum: Copy>(&mut self, data: &[Datum]) -> Result<(), BufferError> {
+        self.check_writable()?;
+        let size = std::mem::size_of_val(data);
+        if size > self.len {
+            return Err(BufferError {
+                message: format!("Cannot write {} bytes to a {} byte mapping", size, self.len),
+            });
+        }
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr() as *const u8, self.data, size);
+        }
+        Ok(())
+    }
+
+    /// Fails if the driver lost the data store while it was mapped, e.g. on
+    /// a display mode change, leaving the buffer contents undefined.
+    pub fn unmap(mut self) -> Result<(), BufferError> {
+        if self.release("MappedBuffer::unmap") {
+            Ok(())
+        } else {
+            Err(BufferError {
+                message: format!("Buffer {} was corrupted while mapped", self.buffer.gl_id),
+            })
+        }
+    }
+
+    /// Unmaps the buffer and returns whether its contents are intact.
+    fn release(&mut self, name: &str) -> bool {
+        self.buffer.context.bind_buffer(Ty::to_gl_buffer_ty(), self.buffer.gl_id);
+        let is_intact = unsafe { gl::UnmapBuffer(Ty::to_gl_buffer_ty()) } == gl::TRUE;
+        self.data = std::ptr::null_mut();
+        self.buffer.context.check_errors(name);
+        is_intact
+    }
+
+    fn check_writable(&self) -> Result<(), BufferError> {
+        if self.is_writable {
+            Ok(())
+        } else {
+            Err(BufferError {
+                message: "Cannot write to a buffer mapped for reading".into(),
+            })
+        }
+    }
+}
+
+impl<'a, Ty: BufferTy> Drop for MappedBuffer<'a, Ty> {
+    fn drop(&mut self) {
+        if !self.data.is_null() && !self.release("MappedBuffer::drop") {
+            self.buffer.context.log_debug_message(DebugMessage {
+                source: DebugSource::Application,
+                ty: DebugMessageTy::Error,
+                severity: DebugSeverity::High,
+                id: 0,
+                message: format!("Buffer {} was corrupted while mapped", self.buffer.gl_id),
+            });
+        }
+    }
+}
+
+impl<Ty: BufferTy> StreamBuffer<Ty> {
+    pub fn new(context: &Context, segment_size: usize, segment_count: usize) -> Result<Self, BufferError> {
+        if segment_size == 0 || segment_count == 0 {
+            return Err(BufferError {
+                message: "Stream buffer needs at least one non-empty segment".into(),
+            });
+        }
+        let mut buffer = Buffer::new(context)?;
+        buffer.bind().allocate(segment_size * segment_count, BufferUsageHint::StreamDraw);
+        Ok(StreamBuffer {
+            buffer,
+            segment_size,
+            fences: (0..segment_count).map(|_| None).collect(),
+            current_segment: None,
+            has_fences: context.capabilities().supports((3, 2), "GL_ARB_sync"),
+        })
+    }
+
+    pub fn segment_size(&self) -> usize {
+        self.segment_size
+    }
+
+    /// Fences the segment written last, whose draws must already have been
+    /// issued, then writes `data` into the next segment. Only waits if the GPU
+    /// is still reading that segment. Returns the byte offset of the data.
+    pub fn write<Datum: Copy>(&mut self, data: &[Datum]) -> Result<usize, BufferError> {
+        let size = std::mem::size_of_val(data);
+        if size > self.segment_size {
+            return Err(BufferError {
+                message: format!(
+                    "Cannot write {} bytes to a stream buffer with {} byte segments",
+                    size,
+                    self.segment_size,
+                ),
+            });
+        }
+        let segment = match self.current_segment {
+            Some(segment) => {
+                if self.has_fences {
+                    self.fences[segment] = Fence::new(&self.buffer.context).ok();
+                }
+                (segment + 1) % self.fences.len()
+            },
+            None => 0,
+        };
+        if let Some(mut fence) = self.fences[segment].take() {
+            loop {
+                let is_signaled = fence.client_wait(Duration::from_millis(100)).map_err(|err| {
+                    BufferError {
+                        message: err.message,
+                    }
+                })?;
+                if is_signaled {
+                    break;
+                }
+            }
+        }
+        self.current_segment = Some(segment);
+        let offset = segment * self.segment_size;
+        if size == 0 {
+            return Ok(offset);
+        }
+        let access = if self.has_fences { MapAccess::WriteUnsynchronized } else { MapAccess::Write };
+        let mut bound_buffer = self.buffer.bind();
+        let mut mapped = bound_buffer.map_range(offset, size, access)?;
+        mapped.write(data)?;
+        mapped.unmap()?;
+        Ok(offset)
+    }
+
+    pub fn bind<'a>(&'a mut self) -> BoundBuffer<'a, Ty> {
+        self.buffer.bind()
     }
 }
@@ -0,0 +1,552 @@
+//! Cameras, the controllers that move them from `Input`, and the frustum and
+//! ray queries needed for culling and picking.
+
+use cgmath;
+use cgmath::{EuclideanSpace, InnerSpace, Matrix, Matrix4, One, Point3, Quaternion, Rad, Rotation, Rotation3,
+             SquareMatrix, Vector3, Vector4, Zero};
+use std;
+
+use app::FramebufferSize;
+use input::{ActionMap, Binding, Input, MouseButton, VirtualKeyCode};
+
+/// Keeps the view just short of straight up or down, where yaw is undefined.
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    Perspective {
+        fov_y: Rad<f32>,
+        near: f32,
+        far: f32,
+    },
+    /// Shows `height` world units vertically, with the width following the
+    /// aspect ratio.
+    Orthographic {
+        height: f32,
+        near: f32,
+        far: f32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub projection: Projection,
+    pub position: Point3<f32>,
+    /// Rotates camera space, looking down -Z with +Y up, into world space.
+    pub orientation: Quaternion<f32>,
+    /// Width over height; keep in sync with `FramebufferSize::aspect_ratio`.
+    pub aspect_ratio: f32,
+}
+
+/// Points `p` with `normal.dot(p) + distance >= 0` are on the inner side.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub distance: f32,
+}
+
+/// The six planes bounding what a camera sees, with normals facing inwards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    pub left: Plane,
+    pub right: Plane,
+    pub bottom: Plane,
+    pub top: Plane,
+    pub near: Plane,
+    pub far: Plane,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    /// Unit length.
+    pub direction: Vector3<f32>,
+}
+
+/// Circles a target point while the `orbit` action is held and zooms with
+/// the mouse wheel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrbitController {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    pub yaw: Rad<f32>,
+    pub pitch: Rad<f32>,
+    /// Radians per unit of raw mouse motion.
+    pub rotate_sensitivity: f32,
+    /// Fraction of the distance covered per line scrolled.
+    pub zoom_sensitivity: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+}
+
+/// First-person movement along the view direction, looking around while the
+/// cursor is grabbed or the `look` action is held.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlyController {
+    pub yaw: Rad<f32>,
+    pub pitch: Rad<f32>,
+    /// World units per second.
+    pub move_speed: f32,
+    /// Multiplies `move_speed` while `sprint` is held.
+    pub sprint_factor: f32,
+    /// Radians per unit of raw mouse motion.
+    pub look_sensitivity: f32,
+}
+
+impl Projection {
+    pub fn matrix(&self, aspect_ratio: f32) -> Matrix4<f32> {
+        match *self {
+            Projection::Perspective { fov_y, near, far } => cgmath::perspective(fov_y, aspect_ratio, near, far),
+            Projection::Orthographic { height, near, far } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * aspect_ratio;
+                cgmath::ortho(-half_width, half_width, -half_height, half_height, near, far)
+            },
+        }
+    }
+}
+
+impl Camera {
+    /// At the origin looking down -Z.
+    pub fn new(projection: Projection) -> Self {
+        Camera {
+            projection,
+            position: Point3::origin(),
+            orientation: Quaternion::one(),
+            aspect_ratio: 1.0,
+        }
+    }
+
+    /// Call from `App::on_resize`.
+    pub fn resize(&mut self, size: FramebufferSize) {
+        self.aspect_ratio = size.aspect_ratio();
+    }
+
+    /// Sets the orientation from yaw about +Y, then pitch about the camera's
+    /// X axis. Zero for both looks down -Z.
+    pub fn set_yaw_pitch(&mut self, yaw: Rad<f32>, pitch: Rad<f32>) {
+        self.orientation = Quaternion::from_angle_y(yaw) * Quaternion::from_angle_x(pitch);
+    }
+
+    /// Turns towards `target` without roll. Does nothing if `target` is the
+    /// camera position.
+    pub fn look_at(&mut self, target: Point3<f32>) {
+        if let Some((yaw, pitch)) = yaw_pitch_towards(target - self.position) {
+            self.set_yaw_pitch(yaw, pitch);
+        }
+    }
+
+    pub fn forward(&self) -> Vector3<f32> {
+        self.orientation.rotate_vector(-Vector3::unit_z())
+    }
+
+    pub fn right(&self) -> Vector3<f32> {
+        self.orientation.rotate_vector(Vector3::unit_x())
+    }
+
+    pub fn up(&self) -> Vector3<f32> {
+        self.orientation.rotate_vector(Vector3::unit_y())
+    }
+
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::from(self.orientation.conjugate()) * Matrix4::from_translation(-self.position.to_vec())
+    }
+
+    pub fn projection_matrix(&self) -> Matrix4<f32> {
+        self.projection.matrix(self.aspect_ratio)
+    }
+
+    pub fn view_projection(&self) -> Matrix4<f32> {
+        self.projection_matrix() * self.view_matrix()
+    }
+
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(self.view_projection())
+    }
+
+    /// The ray through `cursor`, in pixels from the top-left of a framebuffer
+    /// of `size`, starting on the near plane. `None` while the framebuffer
+    /// is empty.
+    pub fn screen_ray(&self, cursor: (f64, f64), size: FramebufferSize) -> Option<Ray> {
+        if size.is_empty() {
+            return None;
+        }
+        let x = (2.0 * cursor.0 / size.width as f64 - 1.0) as f32;
+        let y = (1.0 - 2.0 * cursor.1 / size.height as f64) as f32;
+        let inverse = self.view_projection().invert()?;
+        let unproject = |z: f32| {
+            let point = inverse * Vector4::new(x, y, z, 1.0);
+            Point3::new(point.x, point.y, point.z) / point.w
+        };
+        let near = unproject(-1.0);
+        let far = unproject(1.0);
+        Some(Ray {
+            origin: near,
+            direction: (far - near).normalize(),
+        })
+    }
+}
+
+impl Plane {
+    /// From the coefficients of `ax + by + cz + d = 0`.
+    pub fn from_coefficients(coefficients: Vector4<f32>) -> Self {
+        let normal = coefficients.truncate();
+        let length = normal.magnitude();
+        Plane {
+            normal: normal / length,
+            distance: coefficients.w / length,
+        }
+    }
+
+    /// Positive on the side the normal faces.
+    pub fn signed_distance(&self, point: Point3<f32>) -> f32 {
+        self.normal.dot(point.to_vec()) + self.distance
+    }
+}
+
+impl Frustum {
+    /// Extracts the planes of a view-projection matrix, following Gribb and
+    /// Hartmann. Planes are in whatever space the matrix maps from.
+    pub fn from_matrix(matrix: Matrix4<f32>) -> Self {
+        let x = matrix.row(0);
+        let y = matrix.row(1);
+        let z = matrix.row(2);
+        let w = matrix.row(3);
+        Frustum {
+            left: Plane::from_coefficients(w + x),
+            right: Plane::from_coefficients(w - x),
+            bottom: Plane::from_coefficients(w + y),
+            top: Plane::from_coefficients(w - y),
+            near: Plane::from_coefficients(w + z),
+            far: Plane::from_coefficients(w - z),
+        }
+    }
+
+    pub fn planes(&self) -> [Plane; 6] {
+        [self.left, self.right, self.bottom, self.top, self.near, self.far]
+    }
+
+    pub fn contains_point(&self, point: Point3<f32>) -> bool {
+        self.planes().iter().all(|plane| plane.signed_distance(point) >= 0.0)
+    }
+
+    /// Conservative: may accept spheres just outside a corner.
+    pub fn intersects_sphere(&self, center: Point3<f32>, radius: f32) -> bool {
+        self.planes().iter().all(|plane| plane.signed_distance(center) >= -radius)
+    }
+
+    /// Conservative in the same way as `intersects_sphere`.
+    pub fn intersects_aabb(&self, min: Point3<f32>, max: Point3<f32>) -> bool {
+        self.planes().iter().all(|plane| {
+            // The corner furthest along the normal.
+            let corner = Point3::new(
+                if plane.normal.x >= 0.0 { max.x } else { min.x },
+                if plane.normal.y >= 0.0 { max.y } else { min.y },
+                if plane.normal.z >= 0.0 { max.z } else { min.z },
+            );
+            plane.signed_distance(corner) >= 0.0
+        })
+    }
+}
+
+impl Ray {
+    pub fn at(&self, t: f32) -> Point3<f32> {
+        self.origin + self.direction * t
+    }
+
+    /// Distance along the ray to where it crosses `plane`, if ahead of the
+    /// origin.
+    pub fn intersect_plane(&self, plane: &Plane) -> Option<f32> {
+        let denominator = plane.normal.dot(self.direction);
+        if denominator.abs() < f32::EPSILON {
+            return None;
+        }
+        let t = -plane.signed_distance(self.origin) / denominator;
+        if t >= 0.0 { Some(t) } else { None }
+    }
+
+    /// Distance along the ray to where it enters the box, or 0 if the origin
+    /// is inside.
+    pub fn intersect_aabb(&self, min: Point3<f32>, max: Point3<f32>) -> Option<f32> {
+        let mut t_min = 0.0f32;
+        let mut t_max = f32::INFINITY;
+        for axis in 0..3 {
+            let inverse = 1.0 / self.direction[axis];
+            let mut t0 = (min[axis] - self.origin[axis]) * inverse;
+            let mut t1 = (max[axis] - self.origin[axis]) * inverse;
+            if inverse < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_min > t_max {
+                return None;
+            }
+        }
+        Some(t_min)
+    }
+}
+
+impl OrbitController {
+    pub const ORBIT: &'static str = "orbit";
+
+    pub fn new(target: Point3<f32>, distance: f32) -> Self {
+        OrbitController {
+            target,
+            distance,
+            yaw: Rad(0.0),
+            pitch: Rad(0.0),
+            rotate_sensitivity: 0.005,
+            zoom_sensitivity: 0.1,
+            min_distance: 0.1,
+            max_distance: 1000.0,
+        }
+    }
+
+    /// Starts from wherever `camera` is, keeping its distance to `target`.
+    pub fn from_camera(camera: &Camera, target: Point3<f32>) -> Self {
+        let mut controller = OrbitController::new(target, (target - camera.position).magnitude());
+        if let Some((yaw, pitch)) = yaw_pitch_towards(target - camera.position) {
+            controller.yaw = yaw;
+            controller.pitch = pitch;
+        }
+        controller
+    }
+
+    /// Binds `orbit` to the left mouse button.
+    pub fn bind_default_actions(actions: &mut ActionMap) {
+        actions.bind(OrbitController::ORBIT, Binding::MouseButton(MouseButton::Left));
+    }
+
+    pub fn update(&mut self, camera: &mut Camera, input: &Input) {
+        if input.is_action_down(OrbitController::ORBIT) {
+            let (dx, dy) = input.mouse_delta();
+            self.yaw -= Rad(dx as f32 * self.rotate_sensitivity);
+            self.pitch = clamp_pitch(self.pitch - Rad(dy as f32 * self.rotate_sensitivity));
+        }
+        let (_, scroll) = input.wheel_delta();
+        self.distance = (self.distance * (1.0 - scroll * self.zoom_sensitivity))
+            .max(self.min_distance)
+            .min(self.max_distance);
+        camera.set_yaw_pitch(self.yaw, self.pitch);
+        camera.position = self.target + camera.forward() * -self.distance;
+    }
+}
+
+impl FlyController {
+    pub const MOVE_FORWARD: &'static str = "move_forward";
+    pub const MOVE_BACK: &'static str = "move_back";
+    pub const MOVE_LEFT: &'static str = "move_left";
+    pub const MOVE_RIGHT: &'static str = "move_right";
+    pub const MOVE_UP: &'static str = "move_up";
+    pub const MOVE_DOWN: &'static str = "move_down";
+    pub const SPRINT: &'static str = "sprint";
+    pub const LOOK: &'static str = "look";
+
+    pub fn new() -> Self {
+        FlyController {
+            yaw: Rad(0.0),
+            pitch: Rad(0.0),
+            move_speed: 5.0,
+            sprint_factor: 4.0,
+            look_sensitivity: 0.003,
+        }
+    }
+
+    /// Starts looking wherever `camera` looks.
+    pub fn from_camera(camera: &Camera) -> Self {
+        let mut controller = FlyController::new();
+        if let Some((yaw, pitch)) = yaw_pitch_towards(camera.forward()) {
+            controller.yaw = yaw;
+            controller.pitch = pitch;
+        }
+        controller
+    }
+
+    /// Binds WASD to movement, space and control to rising and sinking,
+    /// shift to `sprint` and the right mouse button to `look`.
+    pub fn bind_default_actions(actions: &mut ActionMap) {
+        let keys = [
+            (FlyController::MOVE_FORWARD, VirtualKeyCode::W),
+            (FlyController::MOVE_BACK, VirtualKeyCode::S),
+            (FlyController::MOVE_LEFT, VirtualKeyCode::A),
+            (FlyController::MOVE_RIGHT, VirtualKeyCode::D),
+            (FlyController::MOVE_UP, VirtualKeyCode::Space),
+            (FlyController::MOVE_DOWN, VirtualKeyCode::LControl),
+            (FlyController::SPRINT, VirtualKeyCode::LShift),
+        ];
+        for &(action, key) in keys.iter() {
+            actions.bind(action, Binding::Key(key));
+        }
+        actions.bind(FlyController::LOOK, Binding::MouseButton(MouseButton::Right));
+    }
+
+    pub fn update(&mut self, camera: &mut Camera, input: &Input, dt: f32) {
+        if input.is_cursor_grabbed() || input.is_action_down(FlyController::LOOK) {
+            let (dx, dy) = input.mouse_delta();
+            self.yaw -= Rad(dx as f32 * self.look_sensitivity);
+            self.pitch = clamp_pitch(self.pitch - Rad(dy as f32 * self.look_sensitivity));
+        }
+        camera.set_yaw_pitch(self.yaw, self.pitch);
+        let axis = |positive: &str, negative: &str| {
+            input.is_action_down(positive) as i32 as f32 - input.is_action_down(negative) as i32 as f32
+        };
+        let direction = camera.forward() * axis(FlyController::MOVE_FORWARD, FlyController::MOVE_BACK)
+            + camera.right() * axis(FlyController::MOVE_RIGHT, FlyController::MOVE_LEFT)
+            + Vector3::unit_y() * axis(FlyController::MOVE_UP, FlyController::MOVE_DOWN);
+        if direction != Vector3::zero() {
+            let speed = if input.is_action_down(FlyController::SPRINT) {
+                self.move_speed * self.sprint_factor
+            } else {
+                self.move_speed
+            };
+            camera.position += direction.normalize() * speed * dt;
+        }
+    }
+}
+
+impl Default for FlyController {
+    fn default() -> Self {
+        FlyController::new()
+    }
+}
+
+/// The yaw and pitch for `Camera::set_yaw_pitch` that look along `direction`.
+fn yaw_pitch_towards(direction: Vector3<f32>) -> Option<(Rad<f32>, Rad<f32>)> {
+    if direction == Vector3::zero() {
+        return None;
+    }
+    let direction = direction.normalize();
+    let yaw = Rad((-direction.x).atan2(-direction.z));
+    let pitch = clamp_pitch(Rad(direction.y.asin()));
+    Some((yaw, pitch))
+}
+
+fn clamp_pitch(pitch: Rad<f32>) -> Rad<f32> {
+    Rad(pitch.0.clamp(-MAX_PITCH, MAX_PITCH))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn perspective_camera() -> Camera {
+        let mut camera = Camera::new(Projection::Perspective {
+            fov_y: Rad(std::f32::consts::FRAC_PI_2),
+            near: 1.0,
+            far: 10.0,
+        });
+        camera.resize(FramebufferSize {
+            width: 800,
+            height: 800,
+            scale_factor: 1.0,
+        });
+        camera
+    }
+
+    fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).magnitude() < EPSILON, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn screen_centre_ray_points_forward() {
+        let size = FramebufferSize {
+            width: 800,
+            height: 600,
+            scale_factor: 2.0,
+        };
+        let mut camera = perspective_camera();
+        camera.resize(size);
+        camera.position = Point3::new(1.0, 2.0, 3.0);
+        camera.look_at(Point3::new(-4.0, 0.0, 1.0));
+        let ray = camera.screen_ray((400.0, 300.0), size).unwrap();
+        assert_close(ray.direction, camera.forward());
+        assert_close(ray.origin.to_vec(), camera.position.to_vec() + camera.forward());
+        // The top-left corner is up and to the left of the view direction.
+        let corner = camera.screen_ray((0.0, 0.0), size).unwrap();
+        assert!(corner.direction.dot(camera.right()) < 0.0);
+        assert!(corner.direction.dot(camera.up()) > 0.0);
+        assert!(camera.screen_ray((0.0, 0.0), FramebufferSize { width: 0, ..size }).is_none());
+    }
+
+    #[test]
+    fn frustum_planes_face_inwards() {
+        let mut camera = perspective_camera();
+        // Looking down -X from (0, 0, 5), so the frustum is in world space.
+        camera.position = Point3::new(0.0, 0.0, 5.0);
+        camera.set_yaw_pitch(Rad(std::f32::consts::FRAC_PI_2), Rad(0.0));
+        let frustum = camera.frustum();
+        // Five units ahead, the 90 degree frustum is ten units across.
+        let ahead = |right: f32, up: f32, distance: f32| Point3::new(-distance, up, 5.0 - right);
+        let cases = [
+            (frustum.left, ahead(-4.9, 0.0, 5.0), ahead(-5.1, 0.0, 5.0)),
+            (frustum.right, ahead(4.9, 0.0, 5.0), ahead(5.1, 0.0, 5.0)),
+            (frustum.bottom, ahead(0.0, -4.9, 5.0), ahead(0.0, -5.1, 5.0)),
+            (frustum.top, ahead(0.0, 4.9, 5.0), ahead(0.0, 5.1, 5.0)),
+            (frustum.near, ahead(0.0, 0.0, 1.1), ahead(0.0, 0.0, 0.9)),
+            (frustum.far, ahead(0.0, 0.0, 9.9), ahead(0.0, 0.0, 10.1)),
+        ];
+        for &(plane, inside, outside) in cases.iter() {
+            assert!((plane.normal.magnitude() - 1.0).abs() < EPSILON);
+            assert!(plane.signed_distance(inside) > 0.0, "{:?} should be inside {:?}", inside, plane);
+            assert!(plane.signed_distance(outside) < 0.0, "{:?} should be outside {:?}", outside, plane);
+            assert!(frustum.contains_point(inside));
+            assert!(!frustum.contains_point(outside));
+        }
+    }
+
+    #[test]
+    fn frustum_culls_spheres_and_boxes() {
+        let frustum = perspective_camera().frustum();
+        // The right plane is 0.1 * sqrt(0.5) away from (5.1, 0, -5).
+        assert!(!frustum.intersects_sphere(Point3::new(5.1, 0.0, -5.0), 0.05));
+        assert!(frustum.intersects_sphere(Point3::new(5.1, 0.0, -5.0), 0.1));
+        assert!(frustum.intersects_sphere(Point3::new(0.0, 0.0, -5.0), 0.1));
+        assert!(!frustum.intersects_sphere(Point3::new(0.0, 0.0, 5.0), 1.0));
+        assert!(!frustum.intersects_aabb(Point3::new(5.1, -1.0, -5.0), Point3::new(6.0, 1.0, -4.5)));
+        assert!(frustum.intersects_aabb(Point3::new(4.5, -1.0, -5.0), Point3::new(6.0, 1.0, -4.5)));
+        assert!(frustum.intersects_aabb(Point3::new(-20.0, -20.0, -9.0), Point3::new(20.0, 20.0, -8.0)));
+        assert!(!frustum.intersects_aabb(Point3::new(-1.0, -1.0, -12.0), Point3::new(1.0, 1.0, -11.0)));
+    }
+
+    #[test]
+    fn ray_hits_misses_and_starts_inside_boxes() {
+        let min = Point3::new(-1.0, -1.0, -1.0);
+        let max = Point3::new(1.0, 1.0, 1.0);
+        let ray = |origin: Point3<f32>, direction: Vector3<f32>| Ray {
+            origin,
+            direction: direction.normalize(),
+        };
+        let hit = ray(Point3::new(0.0, 0.0, 5.0), -Vector3::unit_z());
+        assert_eq!(hit.intersect_aabb(min, max), Some(4.0));
+        assert_close(hit.at(4.0).to_vec(), Vector3::new(0.0, 0.0, 1.0));
+        let diagonal = ray(Point3::new(3.0, 3.0, 0.5), Vector3::new(-1.0, -1.0, 0.0));
+        let t = diagonal.intersect_aabb(min, max).unwrap();
+        assert!((t - 2.0 * 2.0f32.sqrt()).abs() < EPSILON);
+        assert_eq!(ray(Point3::new(3.0, 0.0, 5.0), -Vector3::unit_z()).intersect_aabb(min, max), None);
+        assert_eq!(ray(Point3::new(0.0, 0.0, 5.0), Vector3::unit_z()).intersect_aabb(min, max), None);
+        let inside = ray(Point3::new(0.5, 0.0, 0.0), Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(inside.intersect_aabb(min, max), Some(0.0));
+    }
+
+    #[test]
+    fn ray_crosses_planes_ahead() {
+        let ground = Plane {
+            normal: Vector3::unit_y(),
+            distance: 0.0,
+        };
+        let down = Ray {
+            origin: Point3::new(0.0, 2.0, 0.0),
+            direction: Vector3::new(1.0, -1.0, 0.0).normalize(),
+        };
+        let t = down.intersect_plane(&ground).unwrap();
+        assert!((t - 2.0 * 2.0f32.sqrt()).abs() < EPSILON);
+        assert_close(down.at(t).to_vec(), Vector3::new(2.0, 0.0, 0.0));
+        let up = Ray { direction: -down.direction, ..down };
+        assert_eq!(up.intersect_plane(&ground), None);
+        let level = Ray { direction: Vector3::unit_x(), ..down };
+        assert_eq!(level.intersect_plane(&ground), None);
+    }
+}
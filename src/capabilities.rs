@@ -0,0 +1,94 @@
+use gl;
+use gl::types::*;
+use std::collections::HashSet;
+use std::ffi::CStr;
+
+/// What the driver supports, queried once when the `Context` is created.
+#[derive(Clone, Debug)]
+pub struct Capabilities {
+    /// `(major, minor)` version of the context.
+    pub version: (u32, u32),
+    /// GLSL version in `#version` form, e.g. 330.
+    pub glsl_version: u32,
+    pub vendor: String,
+    pub renderer: String,
+    pub version_string: String,
+    pub extensions: HashSet<String>,
+    pub max_texture_size: u32,
+    pub max_texture_units: u32,
+    pub max_vertex_attribs: u32,
+    pub max_uniform_block_size: u32,
+    pub max_samples: u32,
+    pub max_color_attachments: u32,
+    pub max_draw_buffers: u32,
+}
+
+impl Capabilities {
+    /// Queries the context that is current on this thread.
+    pub fn query() -> Self {
+        let mut num_extensions = 0;
+        unsafe {
+            gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut num_extensions);
+        }
+        let extensions = (0..num_extensions.max(0) as GLuint)
+            .map(|i| unsafe { gl_string(gl::GetStringi(gl::EXTENSIONS, i)) })
+            .collect();
+        Capabilities {
+            version: (get_integer(gl::MAJOR_VERSION), get_integer(gl::MINOR_VERSION)),
+            glsl_version: parse_glsl_version(&get_string(gl::SHADING_LANGUAGE_VERSION)),
+            vendor: get_string(gl::VENDOR),
+            renderer: get_string(gl::RENDERER),
+            version_string: get_string(gl::VERSION),
+            extensions,
+            max_texture_size: get_integer(gl::MAX_TEXTURE_SIZE),
+            max_texture_units: get_integer(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS),
+            max_vertex_attribs: get_integer(gl::MAX_VERTEX_ATTRIBS),
+            max_uniform_block_size: get_integer(gl::MAX_UNIFORM_BLOCK_SIZE),
+            max_samples: get_integer(gl::MAX_SAMPLES),
+            max_color_attachments: get_integer(gl::MAX_COLOR_ATTACHMENTS),
+            max_draw_buffers: get_integer(gl::MAX_DRAW_BUFFERS),
+        }
+    }
+
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.contains(name)
+    }
+
+    /// True if the context is at least `min_version` or exposes `extension`.
+    pub fn supports(&self, min_version: (u32, u32), extension: &str) -> bool {
+        self.version >= min_version || self.has_extension(extension)
+    }
+}
+
+fn get_integer(name: GLenum) -> u32 {
+    let mut value = 0;
+    unsafe {
+        gl::GetIntegerv(name, &mut value);
+    }
+    value.max(0) as _
+}
+
+fn get_string(name: GLenum) -> String {
+    unsafe { gl_string(gl::GetString(name)) }
+}
+
+unsafe fn gl_string(ptr: *const GLubyte) -> String {
+    if ptr.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(ptr as *const _).to_string_lossy().into_owned()
+    }
+}
+
+/// Parses strings such as `4.60 NVIDIA` or `1.50` into 460 and 150.
+fn parse_glsl_version(version: &str) -> u32 {
+    let number = version.split_whitespace().next().unwrap_or("");
+    let mut parts = number.split('.');
+    let major = parts.next().and_then(|major| major.parse::<u32>().ok()).unwrap_or(0);
+    let minor = parts
+        .next()
+        .map(|minor| minor.chars().take_while(|c| c.is_ascii_digit()).take(2).collect::<String>())
+        .and_then(|minor| format!("{:0<2}", minor).parse::<u32>().ok())
+        .unwrap_or(0);
+    major * 100 + minor
+}
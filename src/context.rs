@@ -0,0 +1,255 @@
+use gl;
+use gl::types::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::os::raw::c_void;
+use std::rc::Rc;
+
+use capabilities::Capabilities;
+use debug::{self, DebugConfig, DebugGroup, DebugMessage, DebugState};
+use render_state::{Rect, RenderState, RenderStateCache};
+
+/// Handle to the current GL context. Every wrapper object keeps a clone so that
+/// binds are routed through one cache of what is actually bound, and GL calls
+/// that would not change anything are skipped.
+#[derive(Clone)]
+pub struct Context {
+    inner: Rc<ContextInner>,
+}
+
+struct ContextInner {
+    capabilities: Capabilities,
+    bindings: RefCell<BindingCache>,
+    render_state: RefCell<RenderStateCache>,
+    debug: RefCell<Option<DebugState>>,
+}
+
+/// `None` means the binding is unknown and the next bind must be issued.
+#[derive(Default)]
+struct BindingCache {
+    program: Option<GLuint>,
+    vertex_array: Option<GLuint>,
+    buffers: HashMap<GLenum, GLuint>,
+    active_texture_unit: Option<u32>,
+    textures: HashMap<(u32, GLenum), GLuint>,
+    draw_framebuffer: Option<GLuint>,
+    read_framebuffer: Option<GLuint>,
+}
+
+impl Context {
+    /// Loads the GL function pointers for the context that is current on this
+    /// thread. Only one `Context` should be created per GL context.
+    pub fn load_with<F>(loader: F) -> Self
+    where
+        F: FnMut(&str) -> *const c_void,
+    {
+        gl::load_with(loader);
+        Context {
+            inner: Rc::new(ContextInner {
+                capabilities: Capabilities::query(),
+                bindings: RefCell::new(BindingCache::default()),
+                render_state: RefCell::new(RenderStateCache::new()),
+                debug: RefCell::new(None),
+            }),
+        }
+    }
+
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.inner.capabilities
+    }
+
+    pub fn apply_render_state(&self, state: &RenderState) {
+        self.inner.render_state.borrow_mut().apply(state, &self.inner.capabilities);
+        self.check_errors("Context::apply_render_state");
+    }
+
+    /// Viewport used by render states that leave `viewport` as `None`.
+    pub fn set_viewport(&self, rect: Rect) {
+        self.inner.render_state.borrow_mut().set_viewport(rect);
+        self.check_errors("Context::set_viewport");
+    }
+
+    /// Routes GL debug output to `config.logger`. Uses `glDebugMessageCallback`
+    /// when KHR_debug is available, which works best on a context created with
+    /// the debug flag, and otherwise polls `glGetError` after every wrapped
+    /// call. Returns whether the callback is in use.
+    pub fn enable_debug(&self, config: DebugConfig) -> bool {
+        self.disable_debug();
+        *self.inner.debug.borrow_mut() = Some(DebugState::new(config));
+        if self.inner.capabilities.supports((4, 3), "GL_KHR_debug") {
+            debug::install_callback(&self.inner.debug)
+        } else {
+            false
+        }
+    }
+
+    /// Pushes a named debug group that lasts until the returned guard is
+    /// dropped, e.g. around a render pass.
+    pub fn debug_group<'a>(&'a self, message: &str) -> DebugGroup<'a> {
+        DebugGroup::push(self, message)
+    }
+
+    pub fn disable_debug(&self) {
+        if self.inner.debug.borrow_mut().take().is_some() {
+            debug::uninstall_callback();
+        }
+    }
+
+    /// Reports GL errors raised by the wrapper method named `call`. Does
+    /// nothing unless debugging is enabled.
+    pub fn check_errors(&self, call: &str) {
+        debug::check_errors(&self.inner.debug, call);
+    }
+
+    /// Routes a message detected by this crate rather than the driver, like
+    /// one from the driver. Does nothing unless debugging is enabled.
+    pub(crate) fn log_debug_message(&self, message: DebugMessage) {
+        debug::log_message(&self.inner.debug, message);
+    }
+
+    /// Forgets all cached bindings and render state, for use after code outside
+    /// this crate has made GL calls.
+    pub fn invalidate(&self) {
+        *self.inner.bindings.borrow_mut() = BindingCache::default();
+        self.inner.render_state.borrow_mut().invalidate();
+    }
+
+    /// Makes the window the target of draws and reads again after rendering
+    /// to a `Framebuffer`.
+    pub fn bind_default_framebuffer(&self) {
+        self.bind_framebuffer(gl::FRAMEBUFFER, 0);
+    }
+
+    pub(crate) fn use_program(&self, gl_id: GLuint) {
+        let mut bindings = self.inner.bindings.borrow_mut();
+        if bindings.program != Some(gl_id) {
+            unsafe {
+                gl::UseProgram(gl_id);
+            }
+            bindings.program = Some(gl_id);
+        }
+    }
+
+    pub(crate) fn bind_vertex_array(&self, gl_id: GLuint) {
+        let mut bindings = self.inner.bindings.borrow_mut();
+        if bindings.vertex_array != Some(gl_id) {
+            unsafe {
+                gl::BindVertexArray(gl_id);
+            }
+            bindings.vertex_array = Some(gl_id);
+            // The element array binding is part of vertex array state.
+            bindings.buffers.remove(&gl::ELEMENT_ARRAY_BUFFER);
+        }
+    }
+
+    pub(crate) fn bind_buffer(&self, target: GLenum, gl_id: GLuint) {
+        let mut bindings = self.inner.bindings.borrow_mut();
+        if bindings.buffers.get(&target) != Some(&gl_id) {
+            unsafe {
+                gl::BindBuffer(target, gl_id);
+            }
+            bindings.buffers.insert(target, gl_id);
+        }
+    }
+
+    pub(crate) fn active_texture(&self, unit_index: u32) {
+        let mut bindings = self.inner.bindings.borrow_mut();
+        if bindings.active_texture_unit != Some(unit_index) {
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0 + unit_index);
+            }
+            bindings.active_texture_unit = Some(unit_index);
+        }
+    }
+
+    /// Binds the texture to `unit_index` and leaves that unit active, so
+    /// texture parameter calls that follow affect this texture.
+    pub(crate) fn bind_texture(&self, unit_index: u32, target: GLenum, gl_id: GLuint) {
+        self.active_texture(unit_index);
+        let mut bindings = self.inner.bindings.borrow_mut();
+        if bindings.textures.get(&(unit_index, target)) != Some(&gl_id) {
+            unsafe {
+                gl::BindTexture(target, gl_id);
+            }
+            bindings.textures.insert((unit_index, target), gl_id);
+        }
+    }
+
+    /// `target` is `FRAMEBUFFER`, which sets both the draw and read binding,
+    /// or one of `DRAW_FRAMEBUFFER` and `READ_FRAMEBUFFER`.
+    pub(crate) fn bind_framebuffer(&self, target: GLenum, gl_id: GLuint) {
+        let mut bindings = self.inner.bindings.borrow_mut();
+        let binds_draw = target != gl::READ_FRAMEBUFFER;
+        let binds_read = target != gl::DRAW_FRAMEBUFFER;
+        let is_bound = (!binds_draw || bindings.draw_framebuffer == Some(gl_id))
+            && (!binds_read || bindings.read_framebuffer == Some(gl_id));
+        if !is_bound {
+            unsafe {
+                gl::BindFramebuffer(target, gl_id);
+            }
+            if binds_draw {
+                bindings.draw_framebuffer = Some(gl_id);
+            }
+            if binds_read {
+                bindings.read_framebuffer = Some(gl_id);
+            }
+        }
+    }
+
+    /// Deleting a program that is in use only flags it for deletion, so it is
+    /// unbound first.
+    pub(crate) fn forget_program(&self, gl_id: GLuint) {
+        let mut bindings = self.inner.bindings.borrow_mut();
+        if bindings.program == Some(gl_id) {
+            unsafe {
+                gl::UseProgram(0);
+            }
+            bindings.program = Some(0);
+        }
+    }
+
+    /// Deleted names revert to 0 and may be reused by the next `glGen*` call.
+    pub(crate) fn forget_vertex_array(&self, gl_id: GLuint) {
+        let mut bindings = self.inner.bindings.borrow_mut();
+        if bindings.vertex_array == Some(gl_id) {
+            bindings.vertex_array = Some(0);
+            bindings.buffers.remove(&gl::ELEMENT_ARRAY_BUFFER);
+        }
+    }
+
+    pub(crate) fn forget_buffer(&self, gl_id: GLuint) {
+        let mut bindings = self.inner.bindings.borrow_mut();
+        for bound_id in bindings.buffers.values_mut() {
+            if *bound_id == gl_id {
+                *bound_id = 0;
+            }
+        }
+    }
+
+    pub(crate) fn forget_framebuffer(&self, gl_id: GLuint) {
+        let mut bindings = self.inner.bindings.borrow_mut();
+        if bindings.draw_framebuffer == Some(gl_id) {
+            bindings.draw_framebuffer = Some(0);
+        }
+        if bindings.read_framebuffer == Some(gl_id) {
+            bindings.read_framebuffer = Some(0);
+        }
+    }
+
+    pub(crate) fn forget_texture(&self, gl_id: GLuint) {
+        let mut bindings = self.inner.bindings.borrow_mut();
+        for bound_id in bindings.textures.values_mut() {
+            if *bound_id == gl_id {
+                *bound_id = 0;
+            }
+        }
+    }
+}
+
+impl Drop for ContextInner {
+    fn drop(&mut self) {
+        if self.debug.get_mut().is_some() {
+            debug::uninstall_callback();
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use gl;
+use gl::types::*;
+use std;
+use std::cell::RefCell;
+use std::os::raw::c_void;
+use std::slice;
+
+use context::Context;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DebugSeverity {
+    Notification,
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugSource {
+    Api,
+    Application,
+    Other,
+    ShaderCompiler,
+    ThirdParty,
+    WindowSystem,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugMessageTy {
+    DeprecatedBehavior,
+    Error,
+    Marker,
+    Other,
+    Performance,
+    PopGroup,
+    Portability,
+    PushGroup,
+    UndefinedBehavior,
+}
+
+#[derive(Clone, Debug)]
+pub struct DebugMessage {
+    pub source: DebugSource,
+    pub ty: DebugMessageTy,
+    pub severity: DebugSeverity,
+    pub id: u32,
+    pub message: String,
+}
+
+pub trait DebugLogger {
+    fn log(&self, message: &DebugMessage);
+}
+
+/// Logs every message to stderr.
+pub struct StderrLogger;
+
+/// Where debug messages go and which ones are kept. With `panic_on_error` set,
+/// the wrapper call that caused a GL error panics, which is useful in tests.
+pub struct DebugConfig {
+    pub logger: Box<dyn DebugLogger>,
+    pub min_severity: DebugSeverity,
+    /// `None` accepts messages from every source.
+    pub sources: Option<Vec<DebugSource>>,
+    pub panic_on_error: bool,
+}
+
+/// Scope shown as a named group in GPU debuggers such as RenderDoc. The group
+/// is popped when this guard is dropped.
+pub struct DebugGroup<'a> {
+    context: &'a Context,
+    is_pushed: bool,
+}
+
+pub(crate) struct DebugState {
+    config: DebugConfig,
+    has_callback: bool,
+    pending_errors: Vec<String>,
+}
+
+impl DebugSeverity {
+    pub fn from_gl_severity(severity: GLenum) -> Self {
+        match severity {
+            gl::DEBUG_SEVERITY_HIGH => DebugSeverity::High,
+            gl::DEBUG_SEVERITY_MEDIUM => DebugSeverity::Medium,
+            gl::DEBUG_SEVERITY_LOW => DebugSeverity::Low,
+            _ => DebugSeverity::Notification,
+        }
+    }
+}
+
+impl DebugSource {
+    pub fn from_gl_source(source: GLenum) -> Self {
+        match source {
+            gl::DEBUG_SOURCE_API => DebugSource::Api,
+            gl::DEBUG_SOURCE_APPLICATION => DebugSource::Application,
+            gl::DEBUG_SOURCE_SHADER_COMPILER => DebugSource::ShaderCompiler,
+            gl::DEBUG_SOURCE_THIRD_PARTY => DebugSource::ThirdParty,
+            gl::DEBUG_SOURCE_WINDOW_SYSTEM => DebugSource::WindowSystem,
+            _ => DebugSource::Other,
+        }
+    }
+}
+
+impl DebugMessageTy {
+    pub fn from_gl_ty(ty: GLenum) -> Self {
+        match ty {
+            gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => DebugMessageTy::DeprecatedBehavior,
+            gl::DEBUG_TYPE_ERROR => DebugMessageTy::Error,
+            gl::DEBUG_TYPE_MARKER => DebugMessageTy::Marker,
+            gl::DEBUG_TYPE_PERFORMANCE => DebugMessageTy::Performance,
+            gl::DEBUG_TYPE_POP_GROUP => DebugMessageTy::PopGroup,
+            gl::DEBUG_TYPE_PORTABILITY => DebugMessageTy::Portability,
+            gl::DEBUG_TYPE_PUSH_GROUP => DebugMessageTy::PushGroup,
+            gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => DebugMessageTy::UndefinedBehavior,
+            _ => DebugMessageTy::Other,
+        }
+    }
+}
+
+impl DebugLogger for StderrLogger {
+    fn log(&self, message: &DebugMessage) {
+        eprintln!(
+            "[gl {:?}] {:?} {:?} {}: {}",
+            message.severity,
+            message.source,
+            message.ty,
+            message.id,
+            message.message,
+        );
+    }
+}
+
+impl Default for DebugConfig {
+    fn default() -> Self {
+        DebugConfig {
+            logger: Box::new(StderrLogger),
+            min_severity: DebugSeverity::Low,
+            sources: None,
+            panic_on_error: false,
+        }
+    }
+}
+
+impl DebugConfig {
+    fn accepts(&self, message: &DebugMessage) -> bool {
+        message.severity >= self.min_severity
+            && self.sources.as_ref().is_none_or(|sources| sources.contains(&message.source))
+    }
+}
+
+impl DebugState {
+    pub(crate) fn new(config: DebugConfig) -> Self {
+        DebugState {
+            config,
+            has_callback: false,
+            pending_errors: Vec::new(),
+        }
+    }
+
+    fn route(&mut self, message: DebugMessage) {
+        if message.ty == DebugMessageTy::Error && self.config.panic_on_error {
+            self.pending_errors.push(message.message.clone());
+        }
+        if self.config.accepts(&message) {
+            self.config.logger.log(&message);
+        }
+    }
+}
+
+impl<'a> DebugGroup<'a> {
+    pub(crate) fn push(context: &'a Context, message: &str) -> Self {
+        let is_pushed = context.capabilities().supports((4, 3), "GL_KHR_debug");
+        if is_pushed {
+            unsafe {
+                gl::PushDebugGroup(
+                    gl::DEBUG_SOURCE_APPLICATION,
+                    0,
+                    message.len() as _,
+                    message.as_ptr() as *const _,
+                );
+            }
+            context.check_errors("Context::debug_group");
+        }
+        DebugGroup {
+            context,
+            is_pushed,
+        }
+    }
+}
+
+impl<'a> Drop for DebugGroup<'a> {
+    fn drop(&mut self) {
+        if self.is_pushed {
+            unsafe {
+                gl::PopDebugGroup();
+            }
+            self.context.check_errors("DebugGroup::drop");
+        }
+    }
+}
+
+/// Names a GL object for GPU debuggers. Does nothing without KHR_debug.
+pub(crate) fn set_object_label(context: &Context, identifier: GLenum, gl_id: GLuint, label: &str) {
+    if !context.capabilities().supports((4, 3), "GL_KHR_debug") {
+        return;
+    }
+    unsafe {
+        gl::ObjectLabel(identifier, gl_id, label.len() as _, label.as_ptr() as *const _);
+    }
+    context.check_errors("set_label");
+}
+
+/// Installs a `glDebugMessageCallback` routing into `state`. `state` must
+/// outlive the callback, see `uninstall_callback`.
+pub(crate) fn install_callback(state: &RefCell<Option<DebugState>>) -> bool {
+    if !gl::DebugMessageCallback::is_loaded() {
+        return false;
+    }
+    unsafe {
+        gl::Enable(gl::DEBUG_OUTPUT);
+        // Synchronous output makes errors show up before the wrapped call
+        // returns, so `check_errors` can attribute them.
+        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl::DebugMessageCallback(debug_callback, state as *const _ as *const c_void);
+    }
+    if let Some(ref mut state) = *state.borrow_mut() {
+        state.has_callback = true;
+    }
+    true
+}
+
+pub(crate) fn uninstall_callback() {
+    if gl::DebugMessageCallback::is_loaded() {
+        unsafe {
+            gl::DebugMessageCallback(noop_callback, std::ptr::null());
+            gl::Disable(gl::DEBUG_OUTPUT);
+        }
+    }
+}
+
+/// Polls `glGetError` when there is no callback, then panics if an error was
+/// seen and `panic_on_error` is set. `call` names the wrapper that just ran.
+pub(crate) fn check_errors(state: &RefCell<Option<DebugState>>, call: &str) {
+    let mut state = state.borrow_mut();
+    let state = match *state {
+        Some(ref mut state) => state,
+        None => return,
+    };
+    if !state.has_callback {
+        loop {
+            let error = unsafe { gl::GetError() };
+            if error == gl::NO_ERROR {
+                break;
+            }
+            state.route(DebugMessage {
+                source: DebugSource::Api,
+                ty: DebugMessageTy::Error,
+                severity: DebugSeverity::High,
+                id: error,
+                message: format!("{} after {}", gl_error_name(error), call),
+            });
+        }
+    }
+    if !state.pending_errors.is_empty() {
+        let errors = state.pending_errors.split_off(0);
+        panic!("GL error in {}: {}", call, errors.join("; "));
+    }
+}
+
+/// Errors logged this way make the next `check_errors` panic, like GL errors,
+/// when `panic_on_error` is set.
+pub(crate) fn log_message(state: &RefCell<Option<DebugState>>, message: DebugMessage) {
+    if let Some(ref mut state) = *state.borrow_mut() {
+        state.route(message);
+    }
+}
+
+fn gl_error_name(error: GLenum) -> &'static str {
+    match error {
+        gl::INVALID_ENUM => "GL_INVALID_ENUM",
+        gl::INVALID_VALUE => "GL_INVALID_VALUE",
+        gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
+        gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+        gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+        gl::STACK_UNDERFLOW => "GL_STACK_UNDERFLOW",
+        gl::STACK_OVERFLOW => "GL_STACK_OVERFLOW",
+        _ => "unknown GL error",
+    }
+}
+
+extern "system" fn debug_callback(
+    source: GLenum,
+    ty: GLenum,
+    id: GLuint,
+    severity: GLenum,
+    length: GLsizei,
+    message: *const GLchar,
+    user_param: *mut c_void,
+) {
+    if user_param.is_null() || message.is_null() {
+        return;
+    }
+    let state = unsafe { &*(user_param as *const RefCell<Option<DebugState>>) };
+    // Messages raised while the state is already borrowed are dropped rather
+    // than panicking across the FFI boundary.
+    let mut state = match state.try_borrow_mut() {
+        Ok(state) => state,
+        Err(_) => return,
+    };
+    if let Some(ref mut state) = *state {
+        let bytes = unsafe { slice::from_raw_parts(message as *const u8, length.max(0) as usize) };
+        state.route(DebugMessage {
+            source: DebugSource::from_gl_source(source),
+            ty: DebugMessageTy::from_gl_ty(ty),
+            severity: DebugSeverity::from_gl_severity(severity),
+            id,
+            message: String::from_utf8_lossy(bytes).into_owned(),
+        });
+    }
+}
+
+extern "system" fn noop_callback(
+    _source: GLenum,
+    _ty: GLenum,
+    _id: GLuint,
+    _severity: GLenum,
+    _length: GLsizei,
+    _message: *const GLchar,
+    _user_param: *mut c_void,
+) {
+}
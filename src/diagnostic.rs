@@ -0,0 +1,250 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use preprocessor::{find_line_reference, PreprocessedSource};
+
+const CONTEXT_LINES: usize = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// A single message from a driver info log. `file` and `line` refer to the
+/// original source when a line map was available.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: Option<PathBuf>,
+    pub line: Option<usize>,
+    pub message: String,
+    pub context: Vec<(usize, String)>,
+}
+
+impl Severity {
+    fn parse(word: &str) -> Option<Self> {
+        match word.to_ascii_lowercase().as_str() {
+            "error" | "fatal" => Some(Severity::Error),
+            "warning" => Some(Severity::Warning),
+            "info" | "note" => Some(Severity::Info),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Info => write!(f, "info"),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)?;
+        match (self.file.as_ref(), self.line) {
+            (Some(file), Some(line)) => write!(f, "\n --> {}:{}", file.display(), line)?,
+            (None, Some(line)) => write!(f, "\n --> line {}", line)?,
+            _ => (),
+        }
+        let width = self.context
+            .iter()
+            .map(|&(line, _)| line.to_string().len())
+            .max()
+            .unwrap_or(0);
+        for &(line, ref text) in &self.context {
+            let marker = if Some(line) == self.line { '>' } else { ' ' };
+            write!(f, "\n{} {:>width$} | {}", marker, line, text, width = width)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses an info log in the NVIDIA (`0(12) : error C0000: ...`), Mesa
+/// (`0:12(3): error: ...`) or AMD/Intel (`ERROR: 0:12: ...`) format. Lines
+/// that match none of these are appended to the previous diagnostic.
+pub fn parse_info_log(info_log: &str, preprocessed: Option<&PreprocessedSource>) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    for log_line in info_log.lines() {
+        let log_line = log_line.trim_end_matches('\0').trim();
+        if log_line.is_empty() {
+            continue;
+        }
+        let (prefix, rest, line) = match find_line_reference(log_line) {
+            Some((start, end, line)) => (&log_line[..start], &log_line[end..], Some(line)),
+            None => ("", log_line, None),
+        };
+        let rest = strip_separators(skip_column(rest));
+        let (severity, message) = match (take_severity(prefix), take_severity(rest)) {
+            (Some((severity, _)), _) => (Some(severity), rest),
+            (None, Some((severity, message))) => (Some(severity), message),
+            (None, None) => (None, rest),
+        };
+        if severity.is_none() && line.is_none() {
+            if let Some(last) = diagnostics.last_mut() {
+                last.message.push('\n');
+                last.message.push_str(log_line);
+                continue;
+            }
+        }
+        let mut diagnostic = Diagnostic {
+            severity: severity.unwrap_or(Severity::Info),
+            file: None,
+            line,
+            message: message.into(),
+            context: Vec::new(),
+        };
+        if let (Some(line), Some(preprocessed)) = (line, preprocessed) {
+            map_to_source(&mut diagnostic, line, preprocessed);
+        }
+        diagnostics.push(diagnostic);
+    }
+    diagnostics
+}
+
+/// Rewrites the diagnostic location to the original file and collects the
+/// surrounding lines from that file as context.
+fn map_to_source(diagnostic: &mut Diagnostic, line: usize, preprocessed: &PreprocessedSource) {
+    let line_map = &preprocessed.line_map;
+    let (file, original_line) = match line_map.lookup(line) {
+        Some((file, original_line)) => (file.to_path_buf(), original_line),
+        None => return,
+    };
+    let first = line.saturating_sub(CONTEXT_LINES).max(1);
+    let last = (line + CONTEXT_LINES).min(line_map.num_lines());
+    for (i, text) in preprocessed.source.lines().enumerate().take(last).skip(first - 1) {
+        if let Some((context_file, context_line)) = line_map.lookup(i + 1) {
+            let is_new_line = diagnostic.context.last().is_none_or(|&(prev, _)| prev != context_line);
+            if context_file == file.as_path() && is_new_line {
+                diagnostic.context.push((context_line, text.into()));
+            }
+        }
+    }
+    diagnostic.file = Some(file);
+    diagnostic.line = Some(original_line);
+}
+
+fn skip_column(rest: &str) -> &str {
+    if rest.starts_with('(') {
+        if let Some(close) = rest.find(')') {
+            if rest[1..close].chars().all(|c| c.is_ascii_digit()) {
+                return &rest[close + 1..];
+            }
+        }
+    }
+    rest
+}
+
+fn strip_separators(text: &str) -> &str {
+    text.trim_start_matches(|c: char| c == ':' || c.is_whitespace())
+}
+
+/// Splits a leading severity word, and an NVIDIA-style error code such as
+/// `C1008`, from the message.
+fn take_severity(text: &str) -> Option<(Severity, &str)> {
+    let text = text.trim_start();
+    let word_len = text.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(text.len());
+    let severity = Severity::parse(&text[..word_len])?;
+    let mut rest = &text[word_len..];
+    let trimmed = rest.trim_start();
+    if let Some(code_len) = trimmed.find(':') {
+        let code = &trimmed[..code_len];
+        if !code.is_empty() && code.chars().all(|c| c.is_ascii_alphanumeric()) {
+            rest = &trimmed[code_len..];
+        }
+    }
+    Some((severity, strip_separators(rest)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use preprocessor::ShaderPreprocessor;
+    use std::path::Path;
+
+    fn parse_one(log_line: &str) -> Diagnostic {
+        let mut diagnostics = parse_info_log(log_line, None);
+        assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+        diagnostics.remove(0)
+    }
+
+    #[test]
+    fn nvidia() {
+        let diagnostic = parse_one("0(12) : error C1008: undefined variable \"foo\"");
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.line, Some(12));
+        assert_eq!(diagnostic.message, "undefined variable \"foo\"");
+        let diagnostic = parse_one(
+            "0(7) : warning C7533: global variable gl_FragColor is deprecated after version 120",
+        );
+        assert_eq!(diagnostic.severity, Severity::Warning);
+        assert_eq!(diagnostic.line, Some(7));
+        assert_eq!(diagnostic.message, "global variable gl_FragColor is deprecated after version 120");
+    }
+
+    #[test]
+    fn mesa() {
+        let diagnostic = parse_one("0:12(5): error: `foo' undeclared");
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.line, Some(12));
+        assert_eq!(diagnostic.message, "`foo' undeclared");
+        let diagnostic = parse_one("0:3(10): warning: `color' used uninitialized");
+        assert_eq!(diagnostic.severity, Severity::Warning);
+        assert_eq!(diagnostic.line, Some(3));
+        assert_eq!(diagnostic.message, "`color' used uninitialized");
+    }
+
+    #[test]
+    fn amd() {
+        let diagnostic = parse_one("ERROR: 0:12: 'foo' : undeclared identifier");
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.line, Some(12));
+        assert_eq!(diagnostic.message, "'foo' : undeclared identifier");
+        let diagnostic = parse_one("WARNING: 0:4: extension 'GL_ARB_foo' is not supported");
+        assert_eq!(diagnostic.severity, Severity::Warning);
+        assert_eq!(diagnostic.line, Some(4));
+        assert_eq!(diagnostic.message, "extension 'GL_ARB_foo' is not supported");
+    }
+
+    #[test]
+    fn unlocated_lines_continue_the_previous_diagnostic() {
+        let diagnostics = parse_info_log(
+            "ERROR: 0:12: 'foo' : undeclared identifier\nERROR: 1 compilation errors.  No code generated.\n\0",
+            None,
+        );
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[1].severity, Severity::Error);
+        assert_eq!(diagnostics[1].line, None);
+        assert_eq!(diagnostics[1].message, "1 compilation errors.  No code generated.");
+        let diagnostics = parse_info_log("0:2(1): error: syntax error\n  unexpected '}'", None);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "syntax error\nunexpected '}'");
+    }
+
+    #[test]
+    fn lines_map_through_the_preprocessor() {
+        let preprocessed = ShaderPreprocessor::new()
+            .define("SCALE", "2.0")
+            .preprocess_source("#version 330\nvoid main() {\n    foo = SCALE;\n}\n")
+            .unwrap();
+        for log in &[
+            "0(4) : error C1008: undefined variable \"foo\"",
+            "0:4(5): error: `foo' undeclared",
+            "ERROR: 0:4: 'foo' : undeclared identifier",
+        ] {
+            let diagnostics = parse_info_log(log, Some(&preprocessed));
+            assert_eq!(diagnostics.len(), 1);
+            let diagnostic = &diagnostics[0];
+            assert_eq!(diagnostic.severity, Severity::Error);
+            assert_eq!(diagnostic.file.as_deref(), Some(Path::new("<source>")));
+            assert_eq!(diagnostic.line, Some(3));
+            assert!(diagnostic.context.contains(&(3, "    foo = SCALE;".into())), "{:?}", diagnostic.context);
+            assert!(diagnostic.context.contains(&(4, "}".into())), "{:?}", diagnostic.context);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use gl;
+use gl::types::*;
+use std;
+use std::fmt;
+use std::time::Duration;
+
+use context::Context;
+
+/// Signaled once the GPU has finished every command issued before the fence
+/// was created.
+pub struct Fence {
+    context: Context,
+    sync: GLsync,
+    is_flushed: bool,
+}
+
+impl Fence {
+    pub fn new(context: &Context) -> Result<Fence, FenceError> {
+        if !context.capabilities().supports((3, 2), "GL_ARB_sync") {
+            return Err(FenceError {
+                message: "Fence sync objects are not supported by this driver".into(),
+            });
+        }
+        let sync = unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) };
+        if sync.is_null() {
+            return Err(FenceError {
+                message: "Failed to create GPU fence".into(),
+            });
+        }
+        context.check_errors("Fence::new");
+        Ok(Fence {
+            context: context.clone(),
+            sync,
+            is_flushed: false,
+        })
+    }
+
+    pub fn is_signaled(&mut self) -> Result<bool, FenceError> {
+        self.client_wait(Duration::from_secs(0))
+    }
+
+    /// Blocks the CPU until the fence is signaled or `timeout` passes.
+    /// Returns whether the fence was signaled.
+    pub fn client_wait(&mut self, timeout: Duration) -> Result<bool, FenceError> {
+        // The first wait flushes, otherwise the fence may never reach the GPU.
+        let flags = if self.is_flushed { 0 } else { gl::SYNC_FLUSH_COMMANDS_BIT };
+        let timeout_nanos = timeout.as_secs()
+            .saturating_mul(1_000_000_000)
+            .saturating_add(timeout.subsec_nanos() as u64);
+        let status = unsafe { gl::ClientWaitSync(self.sync, flags, timeout_nanos) };
+        self.is_flushed = true;
+        self.context.check_errors("Fence::client_wait");
+        match status {
+            gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED => Ok(true),
+            gl::TIMEOUT_EXPIRED => Ok(false),
+            _ => Err(FenceError {
+                message: "Failed to wait on GPU fence".into(),
+            }),
+        }
+    }
+
+    /// Makes the GPU wait for the fence before running later commands; the CPU
+    /// does not block. Useful when the fence comes from a shared context.
+    pub fn server_wait(&self) {
+        unsafe {
+            gl::WaitSync(self.sync, 0, gl::TIMEOUT_IGNORED);
+        }
+        self.context.check_errors("Fence::server_wait");
+    }
+}
+
+impl Drop for Fence {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteSync(self.sync);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct FenceError {
+    pub message: String,
+}
+
+impl fmt::Display for FenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for FenceError {}
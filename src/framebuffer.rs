@@ -0,0 +1,409 @@
+use gl;
+use gl::types::*;
+
+use context::Context;
+use debug::set_object_label;
+use texture::{MultisampleTexture, Texture};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Attachment {
+    Color(u32),
+    Depth,
+    DepthStencil,
+    Stencil,
+}
+
+/// Value written by `BoundFramebuffer::clear_color`. Use the variant matching
+/// the attachment format: `Int` and `Uint` for integer formats, `Float` for
+/// everything else.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClearValue {
+    Float([f32; 4]),
+    Int([i32; 4]),
+    Uint([u32; 4]),
+}
+
+pub enum ResolveBuffer {
+    Color,
+    Depth,
+    Stencil,
+}
+
+pub enum RenderbufferFormat {
+    Depth24,
+    Depth24Stencil8,
+    Depth32F,
+    R32F,
+    Rgba16F,
+    Rgba8,
+}
+
+#[derive(Debug)]
+pub struct FramebufferError {
+    pub message: String,
+}
+
+/// Storage that can only be rendered to, not sampled, e.g. the depth buffer
+/// of an offscreen pass.
+pub struct Renderbuffer {
+    context: Context,
+    pub gl_id: GLuint,
+    pub samples: u32,
+}
+
+pub struct Framebuffer {
+    context: Context,
+    pub gl_id: GLuint,
+}
+
+/// Framebuffer bound for both drawing and reading. Attached textures and
+/// renderbuffers are not borrowed; deleting one detaches it.
+pub struct BoundFramebuffer<'a> {
+    framebuffer: &'a mut Framebuffer,
+}
+
+impl Attachment {
+    pub fn to_gl_attachment(self) -> GLenum {
+        match self {
+            Attachment::Color(index) => gl::COLOR_ATTACHMENT0 + index,
+            Attachment::Depth => gl::DEPTH_ATTACHMENT,
+            Attachment::DepthStencil => gl::DEPTH_STENCIL_ATTACHMENT,
+            Attachment::Stencil => gl::STENCIL_ATTACHMENT,
+        }
+    }
+}
+
+impl ResolveBuffer {
+    pub fn to_gl_buffer_bit(&self) -> GLbitfield {
+        match *self {
+            ResolveBuffer::Color => gl::COLOR_BUFFER_BIT,
+            ResolveBuffer::Depth => gl::DEPTH_BUFFER_BIT,
+            ResolveBuffer::Stencil => gl::STENCIL_BUFFER_BIT,
+        }
+    }
+}
+
+impl RenderbufferFormat {
+    pub fn to_gl_internal_format(&self) -> GLenum {
+        match *self {
+            RenderbufferFormat::Depth24 => gl::DEPTH_COMPONENT24,
+            RenderbufferFormat::Depth24Stencil8 => gl::DEPTH24_STENCIL8,
+            RenderbufferFormat::Depth32F => gl::DEPTH_COMPONENT32F,
+            RenderbufferFormat::R32F => gl::R32F,
+            RenderbufferFormat::Rgba16F => gl::RGBA16F,
+            RenderbufferFormat::Rgba8 => gl::RGBA8,
+        }
+    }
+}
+
+impl Renderbuffer {
+    pub fn new(
+        context: &Context,
+        format: RenderbufferFormat,
+        width: usize,
+        height: usize,
+    ) -> Result<Self, FramebufferError> {
+        Renderbuffer::new_multisample(context, format, width, height, 0)
+    }
+
+    /// Allocates storage with `samples` samples per pixel, or a single-sample
+    /// renderbuffer when `samples` is 0.
+    pub fn new_multisample(
+        context: &Context,
+        format: RenderbufferFormat,
+        width: usize,
+        height: usize,
+        samples: u32,
+    ) -> Result<Self, FramebufferError> {
+        let max_samples = context.capabilities().max_samples;
+        if samples > max_samples {
+            return Err(FramebufferError {
+                message: format!("{} samples exceed the {} supported", samples, max_samples),
+            });
+        }
+        let max_texture_size = context.capabilities().max_texture_size as usize;
+        if width > max_texture_size || height > max_texture_size {
+            return Err(FramebufferError {
+                message: format!(
+                    "Renderbuffer size {}x{} exceeds the maximum of {}",
+                    width,
+                    height,
+                    max_texture_size,
+                ),
+            });
+        }
+        let gl_id = unsafe {
+            let mut gl_id = 0;
+            gl::GenRenderbuffers(1, &mut gl_id);
+            if gl_id == 0 {
+                return Err(FramebufferError {
+                    message: "Failed to create GPU renderbuffer".into(),
+                });
+            }
+            gl::BindRenderbuffer(gl::RENDERBUFFER, gl_id);
+            gl::RenderbufferStorageMultisample(
+                gl::RENDERBUFFER,
+                samples as _,
+                format.to_gl_internal_format(),
+                width as _,
+                height as _,
+            );
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+            gl_id
+        };
+        context.check_errors("Renderbuffer::new");
+        Ok(Renderbuffer {
+            context: context.clone(),
+            gl_id,
+            samples,
+        })
+    }
+
+    pub fn set_label(&mut self, label: &str) {
+        set_object_label(&self.context, gl::RENDERBUFFER, self.gl_id, label);
+    }
+}
+
+impl Drop for Renderbuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteRenderbuffers(1, &self.gl_id);
+        }
+        self.gl_id = 0;
+    }
+}
+
+impl Framebuffer {
+    pub fn new(context: &Context) -> Result<Self, FramebufferError> {
+        let gl_id = unsafe {
+            let mut gl_id = 0;
+            gl::GenFramebuffers(1, &mut gl_id);
+            if gl_id == 0 {
+                return Err(FramebufferError {
+                    message: "Failed to create GPU framebuffer".into(),
+                });
+            }
+            gl_id
+        };
+        context.check_errors("Framebuffer::new");
+        Ok(Framebuffer {
+            context: context.clone(),
+            gl_id,
+        })
+    }
+
+    pub fn set_label(&mut self, label: &str) {
+        set_object_label(&self.context, gl::FRAMEBUFFER, self.gl_id, label);
+    }
+
+    pub fn bind<'a>(&'a mut self) -> BoundFramebuffer<'a> {
+        BoundFramebuffer::new(self)
+    }
+
+    /// Copies the read buffer (color attachment 0 unless changed) and the
+    /// depth and stencil attachments listed in `buffers` into `destination`,
+    /// or into the window when it is `None`. Resolving a multisample
+    /// framebuffer averages its samples; both sides must have the same size.
+    pub fn resolve_into(
+        &mut self,
+        destination: Option<&mut Framebuffer>,
+        width: usize,
+        height: usize,
+        buffers: &[ResolveBuffer],
+    ) {
+        let destination_gl_id = destination.map_or(0, |destination| destination.gl_id);
+        let mask = buffers.iter().fold(0, |mask, buffer| mask | buffer.to_gl_buffer_bit());
+        self.context.bind_framebuffer(gl::READ_FRAMEBUFFER, self.gl_id);
+        self.context.bind_framebuffer(gl::DRAW_FRAMEBUFFER, destination_gl_id);
+        unsafe {
+            gl::BlitFramebuffer(
+                0,
+                0,
+                width as _,
+                height as _,
+                0,
+                0,
+                width as _,
+                height as _,
+                mask,
+                gl::NEAREST,
+            );
+        }
+        self.context.check_errors("Framebuffer::resolve_into");
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        self.context.forget_framebuffer(self.gl_id);
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.gl_id);
+        }
+        self.gl_id = 0;
+    }
+}
+
+impl<'a> BoundFramebuffer<'a> {
+    fn new(framebuffer: &'a mut Framebuffer) -> Self {
+        framebuffer.context.bind_framebuffer(gl::FRAMEBUFFER, framebuffer.gl_id);
+        framebuffer.context.check_errors("Framebuffer::bind");
+        BoundFramebuffer {
+            framebuffer,
+        }
+    }
+
+    /// Rebinds the framebuffer if another one was bound since this guard was
+    /// created.
+    pub fn ensure_bound(&self) {
+        self.framebuffer.context.bind_framebuffer(gl::FRAMEBUFFER, self.framebuffer.gl_id);
+    }
+
+    fn check_attachment(&self, attachment: Attachment) -> Result<(), FramebufferError> {
+        let max_color_attachments = self.framebuffer.context.capabilities().max_color_attachments;
+        match attachment {
+            Attachment::Color(index) if index >= max_color_attachments => Err(FramebufferError {
+                message: format!(
+                    "Color attachment {} exceeds the {} attachments supported",
+                    index,
+                    max_color_attachments,
+                ),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn attach_texture(&mut self, attachment: Attachment, texture: &Texture) -> Result<(), FramebufferError> {
+        self.check_attachment(attachment)?;
+        self.ensure_bound();
+        unsafe {
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                attachment.to_gl_attachment(),
+                gl::TEXTURE_2D,
+                texture.gl_id,
+                0,
+            );
+        }
+        self.framebuffer.context.check_errors("BoundFramebuffer::attach_texture");
+        Ok(())
+    }
+
+    pub fn attach_multisample_texture(
+        &mut self,
+        attachment: Attachment,
+        texture: &MultisampleTexture,
+    ) -> Result<(), FramebufferError> {
+        self.check_attachment(attachment)?;
+        self.ensure_bound();
+        unsafe {
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                attachment.to_gl_attachment(),
+                gl::TEXTURE_2D_MULTISAMPLE,
+                texture.gl_id,
+                0,
+            );
+        }
+        self.framebuffer.context.check_errors("BoundFramebuffer::attach_multisample_texture");
+        Ok(())
+    }
+
+    pub fn attach_renderbuffer(
+        &mut self,
+        attachment: Attachment,
+        renderbuffer: &Renderbuffer,
+    ) -> Result<(), FramebufferError> {
+        self.check_attachment(attachment)?;
+        self.ensure_bound();
+        unsafe {
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                attachment.to_gl_attachment(),
+                gl::RENDERBUFFER,
+                renderbuffer.gl_id,
+            );
+        }
+        self.framebuffer.context.check_errors("BoundFramebuffer::attach_renderbuffer");
+        Ok(())
+    }
+
+    /// Selects the color attachment each fragment output location writes to;
+    /// entry `i` is for location `i`, and `None` discards that output.
+    pub fn set_draw_buffers(&mut self, color_attachments: &[Option<u32>]) -> Result<(), FramebufferError> {
+        let capabilities = self.framebuffer.context.capabilities();
+        if color_attachments.len() > capabilities.max_draw_buffers as usize {
+            return Err(FramebufferError {
+                message: format!(
+                    "{} draw buffers exceed the {} supported",
+                    color_attachments.len(),
+                    capabilities.max_draw_buffers,
+                ),
+            });
+        }
+        let mut gl_draw_buffers = Vec::with_capacity(color_attachments.len());
+        for color_attachment in color_attachments {
+            gl_draw_buffers.push(match *color_attachment {
+                Some(index) => {
+                    self.check_attachment(Attachment::Color(index))?;
+                    Attachment::Color(index).to_gl_attachment()
+                },
+                None => gl::NONE,
+            });
+        }
+        self.ensure_bound();
+        unsafe {
+            gl::DrawBuffers(gl_draw_buffers.len() as _, gl_draw_buffers.as_ptr());
+        }
+        self.framebuffer.context.check_errors("BoundFramebuffer::set_draw_buffers");
+        Ok(())
+    }
+
+    pub fn check_status(&self) -> Result<(), FramebufferError> {
+        self.ensure_bound();
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+        let reason = match status {
+            gl::FRAMEBUFFER_COMPLETE => return Ok(()),
+            gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "an attachment is incomplete",
+            gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "it has no attachments",
+            gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => "a draw buffer has no attachment",
+            gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => "the read buffer has no attachment",
+            gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => "attachments have different sample counts",
+            gl::FRAMEBUFFER_UNSUPPORTED => "the attachment formats are not supported together",
+            _ => "of an unknown reason",
+        };
+        Err(FramebufferError {
+            message: format!("Framebuffer is incomplete because {}", reason),
+        })
+    }
+
+    /// Clears the attachment selected as draw buffer `draw_buffer`. Like
+    /// `glClear`, this honors the current color mask and scissor.
+    pub fn clear_color(&mut self, draw_buffer: u32, value: ClearValue) {
+        self.ensure_bound();
+        unsafe {
+            match value {
+                ClearValue::Float(color) => gl::ClearBufferfv(gl::COLOR, draw_buffer as _, color.as_ptr()),
+                ClearValue::Int(color) => gl::ClearBufferiv(gl::COLOR, draw_buffer as _, color.as_ptr()),
+                ClearValue::Uint(color) => gl::ClearBufferuiv(gl::COLOR, draw_buffer as _, color.as_ptr()),
+            }
+        }
+        self.framebuffer.context.check_errors("BoundFramebuffer::clear_color");
+    }
+
+    /// Clears the depth attachment; needs depth writes enabled.
+    pub fn clear_depth(&mut self, depth: f32) {
+        self.ensure_bound();
+        unsafe {
+            gl::ClearBufferfv(gl::DEPTH, 0, &depth);
+        }
+        self.framebuffer.context.check_errors("BoundFramebuffer::clear_depth");
+    }
+
+    pub fn clear_stencil(&mut self, stencil: i32) {
+        self.ensure_bound();
+        unsafe {
+            gl::ClearBufferiv(gl::STENCIL, 0, &stencil);
+        }
+        self.framebuffer.context.check_errors("BoundFramebuffer::clear_stencil");
+    }
+}
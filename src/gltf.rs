@@ -0,0 +1,1888 @@
+//! glTF 2.0 assets, from `.gltf` JSON with external or embedded buffers and
+//! images, or from binary `.glb` files.
+//!
+//! `GltfAsset::load` reads everything into CPU-side data: one `MeshData` per
+//! primitive, metallic-roughness materials, decoded images, node hierarchies,
+//! cameras, skins and animations. `GltfAsset::upload` then creates a `Mesh`
+//! per primitive and a `Texture` per texture, and `GltfAsset::instantiate`
+//! builds a `Scene` from one of the asset's scenes.
+//!
+//! Texture coordinates are flipped vertically to match `Image`, which stores
+//! the bottom row first. Only the first set of texture coordinates, colors,
+//! joints and weights is read. Morph targets and extensions are not
+//! supported.
+
+use cgmath::{
+    EuclideanSpace, InnerSpace, Matrix3, Matrix4, Point3, Quaternion, Rad, SquareMatrix, Vector3, Vector4,
+};
+use gl;
+use serde_json;
+use std;
+use std::borrow::Cow;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+use camera::{Camera, Projection};
+use context::Context;
+use image::Image;
+use mesh::{Mesh, MeshData, Submesh};
+use scene::{NodeId, Scene, Transform};
+use texture::{Texture, TextureFilter, TextureFormat, TextureWrap};
+use vertex_array::DrawTy;
+
+/// glTF allows perspective cameras without a far plane; they get one this
+/// many times as far as the near plane.
+const INFINITE_FAR_SCALE: f32 = 1.0e4;
+
+const GLB_MAGIC: &[u8] = b"glTF";
+const GLB_JSON_CHUNK: u32 = 0x4E4F_534A;
+const GLB_BIN_CHUNK: u32 = 0x004E_4942;
+
+pub struct GltfAsset {
+    pub meshes: Vec<GltfMesh>,
+    pub materials: Vec<PbrMaterial>,
+    pub images: Vec<Image>,
+    pub textures: Vec<GltfTexture>,
+    pub nodes: Vec<GltfNode>,
+    pub scenes: Vec<GltfScene>,
+    /// The scene to show when the application does not pick one.
+    pub default_scene: Option<usize>,
+    pub cameras: Vec<GltfCamera>,
+    pub skins: Vec<Skin>,
+    pub animations: Vec<Animation>,
+}
+
+pub struct GltfMesh {
+    pub name: Option<String>,
+    /// One per glTF primitive, each with a single submesh that carries the
+    /// primitive's material.
+    pub primitives: Vec<MeshData>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureRef {
+    /// Index into `GltfAsset::textures`.
+    pub texture: usize,
+    /// The texture coordinate set the glTF file asked for. Only set 0 ends
+    /// up in `MeshData::uvs`.
+    pub tex_coord: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaMode {
+    Opaque,
+    /// Fully transparent below `cutoff`, opaque otherwise.
+    Mask { cutoff: f32 },
+    Blend,
+}
+
+/// The metallic-roughness material model. Base color and emissive textures
+/// hold sRGB values; the other textures are linear.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PbrMaterial {
+    pub name: Option<String>,
+    pub base_color_factor: [f32; 4],
+    pub base_color_texture: Option<TextureRef>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    /// Roughness in the green channel, metalness in the blue one.
+    pub metallic_roughness_texture: Option<TextureRef>,
+    pub normal_texture: Option<TextureRef>,
+    pub normal_scale: f32,
+    /// Occlusion in the red channel.
+    pub occlusion_texture: Option<TextureRef>,
+    pub occlusion_strength: f32,
+    pub emissive_texture: Option<TextureRef>,
+    pub emissive_factor: [f32; 3],
+    pub alpha_mode: AlphaMode,
+    /// Whether back faces are shown, so culling should be off.
+    pub double_sided: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sampler {
+    pub min_filter: TextureFilter,
+    pub mag_filter: TextureFilter,
+    pub wrap_s: TextureWrap,
+    pub wrap_t: TextureWrap,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GltfTexture {
+    /// Index into `GltfAsset::images`.
+    pub image: usize,
+    pub sampler: Sampler,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfNode {
+    pub name: Option<String>,
+    pub transform: Transform,
+    pub children: Vec<usize>,
+    pub mesh: Option<usize>,
+    pub camera: Option<usize>,
+    pub skin: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfScene {
+    pub name: Option<String>,
+    /// Indices into `GltfAsset::nodes`.
+    pub roots: Vec<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfCamera {
+    pub name: Option<String>,
+    pub projection: Projection,
+    /// Set if the camera was made for one aspect ratio. Orthographic cameras
+    /// always have one.
+    pub aspect_ratio: Option<f32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Skin {
+    pub name: Option<String>,
+    /// Indices into `GltfAsset::nodes`; `MeshData::joints` indexes this.
+    pub joints: Vec<usize>,
+    /// One per joint, moving the mesh into the joint's space.
+    pub inverse_bind_matrices: Vec<Matrix4<f32>>,
+    /// The common root of the joints, if the file names one.
+    pub skeleton: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    /// Hermite splines with an in-tangent and out-tangent stored around each
+    /// key value.
+    CubicSpline,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnimationProperty {
+    Translation,
+    Rotation,
+    Scale,
+}
+
+/// Keyframes for one property of one node.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationChannel {
+    /// Index into `GltfAsset::nodes`.
+    pub node: usize,
+    pub property: AnimationProperty,
+    pub interpolation: Interpolation,
+    /// Key times in seconds, increasing.
+    pub times: Vec<f32>,
+    /// `property.components()` values per key, or three times as many for
+    /// cubic splines: in-tangent, value, out-tangent. Rotations are `xyzw`.
+    pub values: Vec<f32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Animation {
+    pub name: Option<String>,
+    pub channels: Vec<AnimationChannel>,
+    /// The time of the last key of any channel.
+    pub duration: f32,
+}
+
+/// GPU objects for a `GltfAsset`, indexed like the asset's meshes and
+/// textures.
+pub struct GltfResources {
+    pub meshes: Vec<Vec<Mesh>>,
+    pub textures: Vec<Texture>,
+}
+
+/// One of an asset's scenes, with each scene node's item being the index of
+/// the glTF node it came from.
+pub struct GltfSceneInstance {
+    pub scene: Scene<usize>,
+    /// The scene node of each glTF node, or `None` for glTF nodes outside
+    /// the instantiated scene.
+    pub node_ids: Vec<Option<NodeId>>,
+}
+
+#[derive(Debug)]
+pub struct GltfError {
+    pub message: String,
+    pub io_error: Option<io::Error>,
+}
+
+/// The parts of the glTF JSON schema that are imported.
+mod json {
+    use std::collections::HashMap;
+
+    #[derive(Default, Deserialize)]
+    #[serde(default, rename_all = "camelCase")]
+    pub struct Root {
+        pub asset: Asset,
+        pub scene: Option<usize>,
+        pub scenes: Vec<Scene>,
+        pub nodes: Vec<Node>,
+        pub meshes: Vec<Mesh>,
+        pub accessors: Vec<Accessor>,
+        pub buffer_views: Vec<BufferView>,
+        pub buffers: Vec<Buffer>,
+        pub materials: Vec<Material>,
+        pub textures: Vec<Texture>,
+        pub images: Vec<Image>,
+        pub samplers: Vec<Sampler>,
+        pub cameras: Vec<Camera>,
+        pub skins: Vec<Skin>,
+        pub animations: Vec<Animation>,
+        pub extensions_required: Vec<String>,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default)]
+    pub struct Asset {
+        pub version: String,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default)]
+    pub struct Scene {
+        pub name: Option<String>,
+        pub nodes: Vec<usize>,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default)]
+    pub struct Node {
+        pub name: Option<String>,
+        pub children: Vec<usize>,
+        pub mesh: Option<usize>,
+        pub camera: Option<usize>,
+        pub skin: Option<usize>,
+        pub matrix: Option<[f32; 16]>,
+        pub translation: Option<[f32; 3]>,
+        pub rotation: Option<[f32; 4]>,
+        pub scale: Option<[f32; 3]>,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default)]
+    pub struct Mesh {
+        pub name: Option<String>,
+        pub primitives: Vec<Primitive>,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default)]
+    pub struct Primitive {
+        pub attributes: HashMap<String, usize>,
+        pub indices: Option<usize>,
+        pub material: Option<usize>,
+        pub mode: Option<u32>,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default, rename_all = "camelCase")]
+    pub struct Accessor {
+        pub buffer_view: Option<usize>,
+        pub byte_offset: usize,
+        pub component_type: u32,
+        pub normalized: bool,
+        pub count: usize,
+        #[serde(rename = "type")]
+        pub ty: String,
+        pub sparse: Option<Sparse>,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default)]
+    pub struct Sparse {
+        pub count: usize,
+        pub indices: SparseIndices,
+        pub values: SparseValues,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default, rename_all = "camelCase")]
+    pub struct SparseIndices {
+        pub buffer_view: usize,
+        pub byte_offset: usize,
+        pub component_type: u32,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default, rename_all = "camelCase")]
+    pub struct SparseValues {
+        pub buffer_view: usize,
+        pub byte_offset: usize,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default, rename_all = "camelCase")]
+    pub struct BufferView {
+        pub buffer: usize,
+        pub byte_offset: usize,
+        pub byte_length: usize,
+        pub byte_stride: Option<usize>,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default, rename_all = "camelCase")]
+    pub struct Buffer {
+        pub uri: Option<String>,
+        pub byte_length: usize,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default, rename_all = "camelCase")]
+    pub struct Material {
+        pub name: Option<String>,
+        pub pbr_metallic_roughness: Option<PbrMetallicRoughness>,
+        pub normal_texture: Option<TextureInfo>,
+        pub occlusion_texture: Option<TextureInfo>,
+        pub emissive_texture: Option<TextureInfo>,
+        pub emissive_factor: Option<[f32; 3]>,
+        pub alpha_mode: Option<String>,
+        pub alpha_cutoff: Option<f32>,
+        pub double_sided: bool,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default, rename_all = "camelCase")]
+    pub struct PbrMetallicRoughness {
+        pub base_color_factor: Option<[f32; 4]>,
+        pub base_color_texture: Option<TextureInfo>,
+        pub metallic_factor: Option<f32>,
+        pub roughness_factor: Option<f32>,
+        pub metallic_roughness_texture: Option<TextureInfo>,
+    }
+
+    /// Also covers the normal and occlusion variants, which add `scale` and
+    /// `strength` respectively.
+    #[derive(Default, Deserialize)]
+    #[serde(default, rename_all = "camelCase")]
+    pub struct TextureInfo {
+        pub index: usize,
+        pub tex_coord: u32,
+        pub scale: Option<f32>,
+        pub strength: Option<f32>,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default)]
+    pub struct Texture {
+        pub sampler: Option<usize>,
+        pub source: Option<usize>,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default, rename_all = "camelCase")]
+    pub struct Image {
+        pub uri: Option<String>,
+        pub buffer_view: Option<usize>,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default, rename_all = "camelCase")]
+    pub struct Sampler {
+        pub mag_filter: Option<u32>,
+        pub min_filter: Option<u32>,
+        pub wrap_s: Option<u32>,
+        pub wrap_t: Option<u32>,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default)]
+    pub struct Camera {
+        pub name: Option<String>,
+        #[serde(rename = "type")]
+        pub ty: String,
+        pub perspective: Option<Perspective>,
+        pub orthographic: Option<Orthographic>,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default, rename_all = "camelCase")]
+    pub struct Perspective {
+        pub aspect_ratio: Option<f32>,
+        pub yfov: f32,
+        pub znear: f32,
+        pub zfar: Option<f32>,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default)]
+    pub struct Orthographic {
+        pub xmag: f32,
+        pub ymag: f32,
+        pub znear: f32,
+        pub zfar: f32,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default, rename_all = "camelCase")]
+    pub struct Skin {
+        pub name: Option<String>,
+        pub inverse_bind_matrices: Option<usize>,
+        pub skeleton: Option<usize>,
+        pub joints: Vec<usize>,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default)]
+    pub struct Animation {
+        pub name: Option<String>,
+        pub channels: Vec<Channel>,
+        pub samplers: Vec<AnimationSampler>,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default)]
+    pub struct Channel {
+        pub sampler: usize,
+        pub target: Target,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default)]
+    pub struct Target {
+        pub node: Option<usize>,
+        pub path: String,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default)]
+    pub struct AnimationSampler {
+        pub input: usize,
+        pub output: usize,
+        pub interpolation: Option<String>,
+    }
+}
+
+/// Resolves the references of a parsed document against its buffers.
+struct Loader<'a> {
+    path: &'a Path,
+    document: &'a json::Root,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl Default for PbrMaterial {
+    /// The material of primitives that do not name one.
+    fn default() -> Self {
+        PbrMaterial {
+            name: None,
+            base_color_factor: [1.0, 1.0, 1.0, 1.0],
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive_texture: None,
+            emissive_factor: [0.0, 0.0, 0.0],
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+        }
+    }
+}
+
+impl Default for Sampler {
+    /// What glTF suggests when a texture has no sampler.
+    fn default() -> Self {
+        Sampler {
+            min_filter: TextureFilter::LinearMipmapLinear,
+            mag_filter: TextureFilter::Linear,
+            wrap_s: TextureWrap::Repeat,
+            wrap_t: TextureWrap::Repeat,
+        }
+    }
+}
+
+impl GltfCamera {
+    /// A camera placed by a node's world matrix, which should not scale.
+    pub fn to_camera(&self, world: &Matrix4<f32>) -> Camera {
+        let (translation, rotation, _) = decompose(world);
+        let mut camera = Camera::new(self.projection);
+        camera.position = Point3::from_vec(translation);
+        camera.orientation = rotation;
+        if let Some(aspect_ratio) = self.aspect_ratio {
+            camera.aspect_ratio = aspect_ratio;
+        }
+        camera
+    }
+}
+
+impl Skin {
+    /// The world matrix of each joint times its inverse bind matrix. These
+    /// skin vertices straight into world space, so draw the mesh without its
+    /// own node's world matrix. Joints outside the instance get identity.
+    pub fn joint_matrices(&self, instance: &GltfSceneInstance) -> Vec<Matrix4<f32>> {
+        self.joints
+            .iter()
+            .zip(self.inverse_bind_matrices.iter())
+            .map(|(&joint, inverse_bind_matrix)| {
+                instance
+                    .node_ids
+                    .get(joint)
+                    .and_then(|&id| id)
+                    .and_then(|id| instance.scene.world_matrix(id))
+                    .map_or(Matrix4::identity(), |world| world * inverse_bind_matrix)
+            })
+            .collect()
+    }
+}
+
+impl AnimationProperty {
+    pub fn components(self) -> usize {
+        match self {
+            AnimationProperty::Rotation => 4,
+            AnimationProperty::Translation | AnimationProperty::Scale => 3,
+        }
+    }
+}
+
+impl AnimationChannel {
+    /// The property's value at `time`, holding the first and last keys
+    /// outside their range. Only the first `property.components()` values
+    /// are meaningful.
+    pub fn sample(&self, time: f32) -> [f32; 4] {
+        let next = match self.times.iter().position(|&key_time| key_time > time) {
+            Some(0) => return self.key_value(0).into(),
+            Some(next) => next,
+            None => return self.key_value(self.times.len() - 1).into(),
+        };
+        let previous = next - 1;
+        let key_duration = self.times[next] - self.times[previous];
+        let t = (time - self.times[previous]) / key_duration;
+        let start = self.key_value(previous);
+        let end = self.key_value(next);
+        let value = match self.interpolation {
+            Interpolation::Step => start,
+            Interpolation::Linear if self.property == AnimationProperty::Rotation => {
+                let start = Quaternion::new(start.w, start.x, start.y, start.z);
+                let mut end = Quaternion::new(end.w, end.x, end.y, end.z);
+                // Take the shorter way around.
+                if start.dot(end) < 0.0 {
+                    end = -end;
+                }
+                let rotation = start.slerp(end, t);
+                Vector4::new(rotation.v.x, rotation.v.y, rotation.v.z, rotation.s)
+            },
+            Interpolation::Linear => start + (end - start) * t,
+            Interpolation::CubicSpline => {
+                let start_tangent = self.key_tangent(previous, 2) * key_duration;
+                let end_tangent = self.key_tangent(next, 0) * key_duration;
+                let (t2, t3) = (t * t, t * t * t);
+                start * (2.0 * t3 - 3.0 * t2 + 1.0)
+                    + start_tangent * (t3 - 2.0 * t2 + t)
+                    + end * (-2.0 * t3 + 3.0 * t2)
+                    + end_tangent * (t3 - t2)
+            },
+        };
+        if self.property == AnimationProperty::Rotation {
+            value.normalize().into()
+        } else {
+            value.into()
+        }
+    }
+
+    fn key_value(&self, key: usize) -> Vector4<f32> {
+        match self.interpolation {
+            Interpolation::CubicSpline => self.key_tangent(key, 1),
+            _ => self.key_element(key),
+        }
+    }
+
+    /// Element `part` of the in-tangent, value, out-tangent triple of a
+    /// cubic spline key.
+    fn key_tangent(&self, key: usize, part: usize) -> Vector4<f32> {
+        self.key_element(3 * key + part)
+    }
+
+    fn key_element(&self, element: usize) -> Vector4<f32> {
+        let components = self.property.components();
+        let mut value = Vector4::new(0.0, 0.0, 0.0, 0.0);
+        for (i, &component) in self.values[element * components..(element + 1) * components].iter().enumerate() {
+            value[i] = component;
+        }
+        value
+    }
+}
+
+impl Animation {
+    /// Sets the transforms of the animated nodes in `instance` to their
+    /// values at `time`. Wrap `time` by `duration` to loop.
+    pub fn apply(&self, time: f32, instance: &mut GltfSceneInstance) {
+        for channel in self.channels.iter() {
+            let id = match instance.node_ids.get(channel.node) {
+                Some(&Some(id)) => id,
+                _ => continue,
+            };
+            let mut transform = *instance.scene.transform(id).unwrap();
+            let [x, y, z, w] = channel.sample(time);
+            match channel.property {
+                AnimationProperty::Translation => transform.translation = Vector3::new(x, y, z),
+                AnimationProperty::Rotation => transform.rotation = Quaternion::new(w, x, y, z),
+                AnimationProperty::Scale => transform.scale = Vector3::new(x, y, z),
+            }
+            instance.scene.set_transform(id, transform).unwrap();
+        }
+    }
+}
+
+impl GltfAsset {
+    /// Loads a `.gltf` or `.glb` file, telling them apart by content.
+    pub fn load(path: &Path) -> Result<GltfAsset, GltfError> {
+        let bytes = read_file(path)?;
+        GltfAsset::parse(&bytes, path)
+    }
+
+    /// Parses glTF JSON or a GLB container as if read from `path`, which is
+    /// used in errors and to find external buffers and images.
+    pub fn parse(bytes: &[u8], path: &Path) -> Result<GltfAsset, GltfError> {
+        let error = |message: String| {
+            GltfError {
+                message: format!("{}: {}", path.display(), message),
+                io_error: None,
+            }
+        };
+        let (json_bytes, bin_chunk) = if bytes.starts_with(GLB_MAGIC) {
+            parse_glb(bytes).map_err(error)?
+        } else {
+            (bytes, None)
+        };
+        let document: json::Root = serde_json::from_slice(json_bytes).map_err(|err| error(err.to_string()))?;
+        if !document.asset.version.starts_with("2.") {
+            return Err(error(format!("glTF version {:?} is not supported", document.asset.version)));
+        }
+        if !document.extensions_required.is_empty() {
+            return Err(error(format!("Required extensions {:?} are not supported", document.extensions_required)));
+        }
+        let mut loader = Loader {
+            path,
+            document: &document,
+            buffers: Vec::new(),
+        };
+        for (i, buffer) in document.buffers.iter().enumerate() {
+            let data = loader.load_buffer(i, buffer, bin_chunk)?;
+            loader.buffers.push(data);
+        }
+        loader.load_asset()
+    }
+
+    /// Creates a `Mesh` for every primitive and a `Texture` for every
+    /// texture, with mipmaps if its sampler uses them.
+    pub fn upload(&self, context: &Context) -> Result<GltfResources, GltfError> {
+        let mut meshes = Vec::with_capacity(self.meshes.len());
+        for (i, gltf_mesh) in self.meshes.iter().enumerate() {
+            let mut primitives = Vec::with_capacity(gltf_mesh.primitives.len());
+            for (j, data) in gltf_mesh.primitives.iter().enumerate() {
+                let mut mesh = Mesh::new(context, data).map_err(|err| {
+                    GltfError {
+                        message: format!("Mesh {} primitive {}: {}", i, j, err.message),
+                        io_error: None,
+                    }
+                })?;
+                if let Some(ref name) = gltf_mesh.name {
+                    mesh.set_label(&format!("{} {}", name, j));
+                }
+                primitives.push(mesh);
+            }
+            meshes.push(primitives);
+        }
+        let mut textures = Vec::with_capacity(self.textures.len());
+        for (i, gltf_texture) in self.textures.iter().enumerate() {
+            let error = |message: String| {
+                GltfError {
+                    message: format!("Texture {}: {}", i, message),
+                    io_error: None,
+                }
+            };
+            let image = &self.images[gltf_texture.image];
+            let (format, pixels) = texture_pixels(image);
+            let sampler = gltf_texture.sampler;
+            let mut texture = Texture::new(context).map_err(|err| error(err.message))?;
+            {
+                let mut bound_texture = texture.bind(0).map_err(|err| error(err.message))?;
+                bound_texture
+                    .upload_image_2d(format, image.width, image.height, &pixels)
+                    .map_err(|err| error(err.message))?;
+                bound_texture.set_filters(sampler.min_filter, sampler.mag_filter);
+                bound_texture.set_wrap(sampler.wrap_s, sampler.wrap_t);
+                if sampler.min_filter.uses_mipmaps() {
+                    bound_texture.generate_mipmaps();
+                }
+            }
+            textures.push(texture);
+        }
+        Ok(GltfResources {
+            meshes,
+            textures,
+        })
+    }
+
+    /// Builds scene `index` with the local transform of every node in it.
+    pub fn instantiate(&self, index: usize) -> Result<GltfSceneInstance, GltfError> {
+        let gltf_scene = self.scenes.get(index).ok_or_else(|| {
+            GltfError {
+                message: format!("Scene {} of {} does not exist", index, self.scenes.len()),
+                io_error: None,
+            }
+        })?;
+        let mut instance = GltfSceneInstance {
+            scene: Scene::new(),
+            node_ids: vec![None; self.nodes.len()],
+        };
+        let mut pending: Vec<(usize, Option<NodeId>)> =
+            gltf_scene.roots.iter().rev().map(|&root| (root, None)).collect();
+        while let Some((node, parent)) = pending.pop() {
+            if instance.node_ids[node].is_some() {
+                return Err(GltfError {
+                    message: format!("Node {} appears more than once in scene {}", node, index),
+                    io_error: None,
+                });
+            }
+            let id = instance.scene.add(parent, self.nodes[node].transform, Some(node)).unwrap();
+            instance.node_ids[node] = Some(id);
+            pending.extend(self.nodes[node].children.iter().rev().map(|&child| (child, Some(id))));
+        }
+        Ok(instance)
+    }
+}
+
+impl<'a> Loader<'a> {
+    fn error(&self, message: String) -> GltfError {
+        GltfError {
+            message: format!("{}: {}", self.path.display(), message),
+            io_error: None,
+        }
+    }
+
+    fn check_index(&self, index: usize, len: usize, name: &str) -> Result<usize, GltfError> {
+        if index < len {
+            Ok(index)
+        } else {
+            Err(self.error(format!("{} {} does not exist", name, index)))
+        }
+    }
+
+    fn load_buffer(
+        &self,
+        index: usize,
+        buffer: &json::Buffer,
+        bin_chunk: Option<&[u8]>,
+    ) -> Result<Vec<u8>, GltfError> {
+        let data = match (buffer.uri.as_ref(), bin_chunk) {
+            (Some(uri), _) => self.load_uri(uri)?,
+            (None, Some(bin_chunk)) if index == 0 => bin_chunk.to_vec(),
+            (None, _) => return Err(self.error(format!("Buffer {} has no data", index))),
+        };
+        if data.len() < buffer.byte_length {
+            return Err(self.error(format!(
+                "Buffer {} has {} bytes instead of {}",
+                index,
+                data.len(),
+                buffer.byte_length,
+            )));
+        }
+        Ok(data)
+    }
+
+    /// Decodes a base64 data URI or reads a file relative to the asset.
+    fn load_uri(&self, uri: &str) -> Result<Vec<u8>, GltfError> {
+        if uri.starts_with("data:") {
+            let data = match uri.find(";base64,") {
+                Some(start) => decode_base64(&uri[start + ";base64,".len()..]),
+                None => None,
+            };
+            return data.ok_or_else(|| self.error(format!("Unsupported data URI {:?}", truncate(uri, 40))));
+        }
+        let directory = self.path.parent().unwrap_or_else(|| Path::new(""));
+        read_file(&directory.join(decode_percents(uri))).map_err(|err| {
+            GltfError {
+                message: format!("{}: {}", self.path.display(), err.message),
+                io_error: err.io_error,
+            }
+        })
+    }
+
+    fn load_asset(&self) -> Result<GltfAsset, GltfError> {
+        let document = self.document;
+        let mut meshes = Vec::with_capacity(document.meshes.len());
+        for (i, mesh) in document.meshes.iter().enumerate() {
+            let mut primitives = Vec::with_capacity(mesh.primitives.len());
+            for (j, primitive) in mesh.primitives.iter().enumerate() {
+                primitives.push(self.load_primitive(i, j, primitive)?);
+            }
+            meshes.push(GltfMesh {
+                name: mesh.name.clone(),
+                primitives,
+            });
+        }
+        let mut images = Vec::with_capacity(document.images.len());
+        for (i, image) in document.images.iter().enumerate() {
+            images.push(self.load_image(i, image)?);
+        }
+        let mut textures = Vec::with_capacity(document.textures.len());
+        for (i, texture) in document.textures.iter().enumerate() {
+            let image = texture.source.ok_or_else(|| self.error(format!("Texture {} has no image", i)))?;
+            let sampler = match texture.sampler {
+                Some(sampler) => {
+                    let sampler = self.check_index(sampler, document.samplers.len(), "Sampler")?;
+                    self.load_sampler(&document.samplers[sampler])?
+                },
+                None => Sampler::default(),
+            };
+            textures.push(GltfTexture {
+                image: self.check_index(image, images.len(), "Image")?,
+                sampler,
+            });
+        }
+        let mut materials = Vec::with_capacity(document.materials.len());
+        for material in document.materials.iter() {
+            materials.push(self.load_material(material)?);
+        }
+        let mut nodes = Vec::with_capacity(document.nodes.len());
+        for node in document.nodes.iter() {
+            nodes.push(self.load_node(node)?);
+        }
+        let mut scenes = Vec::with_capacity(document.scenes.len());
+        for scene in document.scenes.iter() {
+            for &root in scene.nodes.iter() {
+                self.check_index(root, nodes.len(), "Node")?;
+            }
+            scenes.push(GltfScene {
+                name: scene.name.clone(),
+                roots: scene.nodes.clone(),
+            });
+        }
+        let default_scene = match document.scene {
+            Some(scene) => Some(self.check_index(scene, scenes.len(), "Scene")?),
+            None => None,
+        };
+        let mut cameras = Vec::with_capacity(document.cameras.len());
+        for (i, camera) in document.cameras.iter().enumerate() {
+            cameras.push(self.load_camera(i, camera)?);
+        }
+        let mut skins = Vec::with_capacity(document.skins.len());
+        for (i, skin) in document.skins.iter().enumerate() {
+            skins.push(self.load_skin(i, skin)?);
+        }
+        let mut animations = Vec::with_capacity(document.animations.len());
+        for (i, animation) in document.animations.iter().enumerate() {
+            animations.push(self.load_animation(i, animation)?);
+        }
+        Ok(GltfAsset {
+            meshes,
+            materials,
+            images,
+            textures,
+            nodes,
+            scenes,
+            default_scene,
+            cameras,
+            skins,
+            animations,
+        })
+    }
+
+    fn load_primitive(
+        &self,
+        mesh: usize,
+        index: usize,
+        primitive: &json::Primitive,
+    ) -> Result<MeshData, GltfError> {
+        let error = |message: String| self.error(format!("Mesh {} primitive {}: {}", mesh, index, message));
+        let attribute = |name: &str| primitive.attributes.get(name).cloned();
+        let mode = primitive.mode.unwrap_or(gl::TRIANGLES);
+        let topology = match mode {
+            gl::POINTS => DrawTy::Points,
+            gl::LINES | gl::LINE_LOOP | gl::LINE_STRIP => DrawTy::Lines,
+            gl::TRIANGLES | gl::TRIANGLE_FAN => DrawTy::Triangles,
+            gl::TRIANGLE_STRIP => DrawTy::TriangleStrip,
+            _ => return Err(error(format!("Unknown primitive mode {}", mode))),
+        };
+        let mut data = MeshData::new(topology);
+        let positions = attribute("POSITION").ok_or_else(|| error("No POSITION attribute".into()))?;
+        data.positions = self.read_vectors(positions)?;
+        if let Some(normals) = attribute("NORMAL") {
+            data.normals = self.read_vectors(normals)?;
+        }
+        if let Some(uvs) = attribute("TEXCOORD_0") {
+            data.uvs = self.read_vectors(uvs)?.into_iter().map(|uv: [f32; 2]| [uv[0], 1.0 - uv[1]]).collect();
+        }
+        if let Some(tangents) = attribute("TANGENT") {
+            data.tangents = self.read_vectors(tangents)?;
+        }
+        if let Some(colors) = attribute("COLOR_0") {
+            let (components, values) = self.read_accessor(colors)?;
+            data.colors = match components {
+                3 => values.chunks(3).map(|rgb| [rgb[0] as f32, rgb[1] as f32, rgb[2] as f32, 1.0]).collect(),
+                _ => vectors(colors, components, &values).map_err(|message| self.error(message))?,
+            };
+        }
+        if let Some(joints) = attribute("JOINTS_0") {
+            data.joints = self.read_vectors(joints)?;
+        }
+        if let Some(weights) = attribute("WEIGHTS_0") {
+            data.weights = self.read_vectors(weights)?;
+        }
+        if let Some(indices) = primitive.indices {
+            data.indices = Some(self.read_indices(indices)?);
+        }
+        // Fans and connected lines become lists of separate primitives.
+        let elements: Vec<u32> = (0..data.element_count()).map(|i| data.element(i)).collect();
+        match mode {
+            gl::TRIANGLE_FAN if elements.len() >= 3 => {
+                data.indices = Some(
+                    (1..elements.len() - 1)
+                        .flat_map(|i| vec![elements[0], elements[i], elements[i + 1]])
+                        .collect(),
+                );
+            },
+            gl::LINE_STRIP | gl::LINE_LOOP if elements.len() >= 2 => {
+                let mut indices: Vec<u32> = elements.windows(2).flat_map(|pair| pair.to_vec()).collect();
+                if mode == gl::LINE_LOOP {
+                    indices.extend_from_slice(&[elements[elements.len() - 1], elements[0]]);
+                }
+                data.indices = Some(indices);
+            },
+            gl::TRIANGLE_FAN | gl::LINE_STRIP | gl::LINE_LOOP => data.indices = Some(Vec::new()),
+            _ => (),
+        }
+        if data.normals.is_empty() && topology != DrawTy::Points && topology != DrawTy::Lines {
+            data.normals = data.smooth_normals();
+        }
+        let material = match primitive.material {
+            Some(material) => Some(self.check_index(material, self.document.materials.len(), "Material")?),
+            None => None,
+        };
+        data.submeshes = vec![Submesh {
+            first: 0,
+            count: data.element_count(),
+            material,
+        }];
+        data.validate().map_err(|err| error(err.message))?;
+        Ok(data)
+    }
+
+    fn load_image(&self, index: usize, image: &json::Image) -> Result<Image, GltfError> {
+        let bytes = match (image.uri.as_ref(), image.buffer_view) {
+            (Some(uri), _) => Cow::Owned(self.load_uri(uri)?),
+            (None, Some(view)) => Cow::Borrowed(self.view_bytes(view)?),
+            (None, None) => return Err(self.error(format!("Image {} has no data", index))),
+        };
+        Image::from_memory(&bytes).map_err(|err| self.error(format!("Image {}: {}", index, err.message)))
+    }
+
+    fn load_sampler(&self, sampler: &json::Sampler) -> Result<Sampler, GltfError> {
+        let defaults = Sampler::default();
+        let filter = |filter: Option<u32>, default: TextureFilter| {
+            match filter {
+                None => Ok(default),
+                Some(gl::NEAREST) => Ok(TextureFilter::Nearest),
+                Some(gl::LINEAR) => Ok(TextureFilter::Linear),
+                Some(gl::NEAREST_MIPMAP_NEAREST) => Ok(TextureFilter::NearestMipmapNearest),
+                Some(gl::LINEAR_MIPMAP_NEAREST) => Ok(TextureFilter::LinearMipmapNearest),
+                Some(gl::NEAREST_MIPMAP_LINEAR) => Ok(TextureFilter::NearestMipmapLinear),
+                Some(gl::LINEAR_MIPMAP_LINEAR) => Ok(TextureFilter::LinearMipmapLinear),
+                Some(other) => Err(self.error(format!("Unknown texture filter {}", other))),
+            }
+        };
+        let wrap = |wrap: Option<u32>| {
+            match wrap {
+                None | Some(gl::REPEAT) => Ok(TextureWrap::Repeat),
+                Some(gl::MIRRORED_REPEAT) => Ok(TextureWrap::MirroredRepeat),
+                Some(gl::CLAMP_TO_EDGE) => Ok(TextureWrap::ClampToEdge),
+                Some(other) => Err(self.error(format!("Unknown texture wrap mode {}", other))),
+            }
+        };
+        let mag_filter = filter(sampler.mag_filter, defaults.mag_filter)?;
+        if mag_filter.uses_mipmaps() {
+            return Err(self.error(format!("{:?} is not a magnification filter", mag_filter)));
+        }
+        Ok(Sampler {
+            min_filter: filter(sampler.min_filter, defaults.min_filter)?,
+            mag_filter,
+            wrap_s: wrap(sampler.wrap_s)?,
+            wrap_t: wrap(sampler.wrap_t)?,
+        })
+    }
+
+    fn load_material(&self, material: &json::Material) -> Result<PbrMaterial, GltfError> {
+        let texture_ref = |info: &Option<json::TextureInfo>| {
+            match *info {
+                Some(ref info) => {
+                    Ok(Some(TextureRef {
+                        texture: self.check_index(info.index, self.document.textures.len(), "Texture")?,
+                        tex_coord: info.tex_coord,
+                    }))
+                },
+                None => Ok(None),
+            }
+        };
+        let defaults = PbrMaterial::default();
+        let mut pbr_material = PbrMaterial {
+            name: material.name.clone(),
+            normal_texture: texture_ref(&material.normal_texture)?,
+            normal_scale: material.normal_texture.as_ref().and_then(|info| info.scale).unwrap_or(1.0),
+            occlusion_texture: texture_ref(&material.occlusion_texture)?,
+            occlusion_strength: material.occlusion_texture.as_ref().and_then(|info| info.strength).unwrap_or(1.0),
+            emissive_texture: texture_ref(&material.emissive_texture)?,
+            emissive_factor: material.emissive_factor.unwrap_or(defaults.emissive_factor),
+            alpha_mode: match material.alpha_mode.as_deref() {
+                None | Some("OPAQUE") => AlphaMode::Opaque,
+                Some("MASK") => AlphaMode::Mask { cutoff: material.alpha_cutoff.unwrap_or(0.5) },
+                Some("BLEND") => AlphaMode::Blend,
+                Some(other) => return Err(self.error(format!("Unknown alpha mode {:?}", other))),
+            },
+            double_sided: material.double_sided,
+            ..defaults
+        };
+        if let Some(ref pbr) = material.pbr_metallic_roughness {
+            pbr_material.base_color_factor = pbr.base_color_factor.unwrap_or(defaults.base_color_factor);
+            pbr_material.base_color_texture = texture_ref(&pbr.base_color_texture)?;
+            pbr_material.metallic_factor = pbr.metallic_factor.unwrap_or(defaults.metallic_factor);
+            pbr_material.roughness_factor = pbr.roughness_factor.unwrap_or(defaults.roughness_factor);
+            pbr_material.metallic_roughness_texture = texture_ref(&pbr.metallic_roughness_texture)?;
+        }
+        Ok(pbr_material)
+    }
+
+    fn load_node(&self, node: &json::Node) -> Result<GltfNode, GltfError> {
+        let document = self.document;
+        for &child in node.children.iter() {
+            self.check_index(child, document.nodes.len(), "Node")?;
+        }
+        let optional_index = |index: Option<usize>, len: usize, name: &str| {
+            match index {
+                Some(index) => self.check_index(index, len, name).map(Some),
+                None => Ok(None),
+            }
+        };
+        let transform = match node.matrix {
+            Some(ref matrix) => {
+                let (translation, rotation, scale) = decompose(&matrix_from_columns(matrix));
+                Transform {
+                    translation,
+                    rotation,
+                    scale,
+                }
+            },
+            None => {
+                let identity = Transform::identity();
+                Transform {
+                    translation: node.translation.map_or(identity.translation, Vector3::from),
+                    rotation: node.rotation.map_or(identity.rotation, |[x, y, z, w]| Quaternion::new(w, x, y, z)),
+                    scale: node.scale.map_or(identity.scale, Vector3::from),
+                }
+            },
+        };
+        Ok(GltfNode {
+            name: node.name.clone(),
+            transform,
+            children: node.children.clone(),
+            mesh: optional_index(node.mesh, document.meshes.len(), "Mesh")?,
+            camera: optional_index(node.camera, document.cameras.len(), "Camera")?,
+            skin: optional_index(node.skin, document.skins.len(), "Skin")?,
+        })
+    }
+
+    fn load_camera(&self, index: usize, camera: &json::Camera) -> Result<GltfCamera, GltfError> {
+        let (projection, aspect_ratio) = match (camera.ty.as_str(), &camera.perspective, &camera.orthographic) {
+            ("perspective", Some(perspective), _) => {
+                let projection = Projection::Perspective {
+                    fov_y: Rad(perspective.yfov),
+                    near: perspective.znear,
+                    far: perspective.zfar.unwrap_or(perspective.znear * INFINITE_FAR_SCALE),
+                };
+                (projection, perspective.aspect_ratio)
+            },
+            ("orthographic", _, Some(orthographic)) => {
+                let projection = Projection::Orthographic {
+                    height: 2.0 * orthographic.ymag,
+                    near: orthographic.znear,
+                    far: orthographic.zfar,
+                };
+                (projection, Some(orthographic.xmag / orthographic.ymag))
+            },
+            (ty, _, _) => return Err(self.error(format!("Camera {} has no {:?} projection", index, ty))),
+        };
+        Ok(GltfCamera {
+            name: camera.name.clone(),
+            projection,
+            aspect_ratio,
+        })
+    }
+
+    fn load_skin(&self, index: usize, skin: &json::Skin) -> Result<Skin, GltfError> {
+        for &joint in skin.joints.iter() {
+            self.check_index(joint, self.document.nodes.len(), "Node")?;
+        }
+        let inverse_bind_matrices = match skin.inverse_bind_matrices {
+            Some(accessor) => {
+                let matrices: Vec<[f32; 16]> = self.read_vectors(accessor)?;
+                if matrices.len() < skin.joints.len() {
+                    return Err(self.error(format!(
+                        "Skin {} has {} inverse bind matrices for {} joints",
+                        index,
+                        matrices.len(),
+                        skin.joints.len(),
+                    )));
+                }
+                matrices.iter().map(matrix_from_columns).collect()
+            },
+            None => vec![Matrix4::identity(); skin.joints.len()],
+        };
+        let skeleton = match skin.skeleton {
+            Some(skeleton) => Some(self.check_index(skeleton, self.document.nodes.len(), "Node")?),
+            None => None,
+        };
+        Ok(Skin {
+            name: skin.name.clone(),
+            joints: skin.joints.clone(),
+            inverse_bind_matrices,
+            skeleton,
+        })
+    }
+
+    /// Channels that animate morph target weights are skipped.
+    fn load_animation(&self, index: usize, animation: &json::Animation) -> Result<Animation, GltfError> {
+        let error = |message: String| self.error(format!("Animation {}: {}", index, message));
+        let mut channels = Vec::with_capacity(animation.channels.len());
+        for channel in animation.channels.iter() {
+            let property = match channel.target.path.as_str() {
+                "translation" => AnimationProperty::Translation,
+                "rotation" => AnimationProperty::Rotation,
+                "scale" => AnimationProperty::Scale,
+                "weights" => continue,
+                other => return Err(error(format!("Unknown target path {:?}", other))),
+            };
+            // Extensions may point channels at things other than nodes.
+            let node = match channel.target.node {
+                Some(node) => self.check_index(node, self.document.nodes.len(), "Node")?,
+                None => continue,
+            };
+            let sampler = animation
+                .samplers
+                .get(channel.sampler)
+                .ok_or_else(|| error(format!("Sampler {} does not exist", channel.sampler)))?;
+            let interpolation = match sampler.interpolation.as_deref() {
+                None | Some("LINEAR") => Interpolation::Linear,
+                Some("STEP") => Interpolation::Step,
+                Some("CUBICSPLINE") => Interpolation::CubicSpline,
+                Some(other) => return Err(error(format!("Unknown interpolation {:?}", other))),
+            };
+            let (_, times) = self.read_accessor(sampler.input)?;
+            let times: Vec<f32> = times.into_iter().map(|time| time as f32).collect();
+            let (components, values) = self.read_accessor(sampler.output)?;
+            let keys_per_time = if interpolation == Interpolation::CubicSpline { 3 } else { 1 };
+            if times.is_empty()
+                || components != property.components()
+                || values.len() != times.len() * keys_per_time * components
+            {
+                return Err(error(format!(
+                    "Sampler {} has {} times and {} values of {} components for {:?}",
+                    channel.sampler,
+                    times.len(),
+                    values.len() / components.max(1),
+                    components,
+                    property,
+                )));
+            }
+            channels.push(AnimationChannel {
+                node,
+                property,
+                interpolation,
+                times,
+                values: values.into_iter().map(|value| value as f32).collect(),
+            });
+        }
+        let duration = channels.iter().filter_map(|channel| channel.times.last()).fold(0.0, |a: f32, &b| a.max(b));
+        Ok(Animation {
+            name: animation.name.clone(),
+            channels,
+            duration,
+        })
+    }
+
+    fn view_bytes(&self, index: usize) -> Result<&[u8], GltfError> {
+        let view_index = self.check_index(index, self.document.buffer_views.len(), "Buffer view")?;
+        let view = &self.document.buffer_views[view_index];
+        let buffer = &self.buffers[self.check_index(view.buffer, self.buffers.len(), "Buffer")?];
+        view.byte_offset
+            .checked_add(view.byte_length)
+            .and_then(|end| buffer.get(view.byte_offset..end))
+            .ok_or_else(|| self.error(format!("Buffer view {} exceeds buffer {}", index, view.buffer)))
+    }
+
+    /// Reads an accessor as a flat list of numbers and the number of
+    /// components per element. Normalized integers are scaled to 0 to 1, or
+    /// -1 to 1 if signed; `f64` keeps 32 bit indices exact.
+    fn read_accessor(&self, index: usize) -> Result<(usize, Vec<f64>), GltfError> {
+        let accessor_index = self.check_index(index, self.document.accessors.len(), "Accessor")?;
+        let accessor = &self.document.accessors[accessor_index];
+        let error = |message: String| self.error(format!("Accessor {}: {}", index, message));
+        let components = match accessor.ty.as_str() {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            "VEC4" | "MAT2" => 4,
+            "MAT3" => 9,
+            "MAT4" => 16,
+            other => return Err(error(format!("Unknown type {:?}", other))),
+        };
+        let element_size = components
+            * component_size(accessor.component_type)
+                .ok_or_else(|| error(format!("Unknown component type {}", accessor.component_type)))?;
+        // The count is checked against the data before allocating, so a bogus
+        // one is an error rather than an allocation failure. Accessors
+        // without a buffer view start out as zeros, and are held to the size
+        // of all buffers instead.
+        let (bytes, stride) = match accessor.buffer_view {
+            Some(view) => {
+                let bytes = self.view_bytes(view)?;
+                (bytes, self.document.buffer_views[view].byte_stride.unwrap_or(element_size))
+            },
+            None => (&[][..], element_size),
+        };
+        let (needed, available) = match (accessor.buffer_view, accessor.count) {
+            (Some(_), 0) => (Some(0), bytes.len()),
+            (Some(_), count) => (
+                (count - 1)
+                    .checked_mul(stride)
+                    .and_then(|size| size.checked_add(element_size))
+                    .and_then(|size| size.checked_add(accessor.byte_offset)),
+                bytes.len(),
+            ),
+            (None, count) => (
+                count.checked_mul(element_size),
+                self.buffers.iter().map(|buffer| buffer.len()).sum(),
+            ),
+        };
+        if needed.is_none_or(|needed| needed > available) {
+            return Err(error(format!(
+                "{} elements of {} bytes do not fit in the {} bytes available",
+                accessor.count,
+                element_size,
+                available,
+            )));
+        }
+        let mut values = vec![0.0; accessor.count * components];
+        if accessor.buffer_view.is_some() {
+            for (i, element) in values.chunks_mut(components).enumerate() {
+                let start = accessor.byte_offset + i * stride;
+                read_components(
+                    &bytes[start..start + element_size],
+                    accessor.component_type,
+                    accessor.normalized,
+                    element,
+                );
+            }
+        }
+        if let Some(ref sparse) = accessor.sparse {
+            let index_size = component_size(sparse.indices.component_type)
+                .ok_or_else(|| error(format!("Unknown sparse index type {}", sparse.indices.component_type)))?;
+            let index_bytes = self.view_bytes(sparse.indices.buffer_view)?;
+            let value_bytes = self.view_bytes(sparse.values.buffer_view)?;
+            let index_end = sparse.count
+                .checked_mul(index_size)
+                .and_then(|size| size.checked_add(sparse.indices.byte_offset));
+            let value_end = sparse.count
+                .checked_mul(element_size)
+                .and_then(|size| size.checked_add(sparse.values.byte_offset));
+            if index_end.is_none_or(|end| end > index_bytes.len())
+                || value_end.is_none_or(|end| end > value_bytes.len())
+            {
+                return Err(error("Sparse data exceeds its buffer views".into()));
+            }
+            let mut sparse_index = [0.0];
+            for i in 0..sparse.count {
+                let index_start = sparse.indices.byte_offset + i * index_size;
+                read_components(
+                    &index_bytes[index_start..index_start + index_size],
+                    sparse.indices.component_type,
+                    false,
+                    &mut sparse_index,
+                );
+                let target = sparse_index[0] as usize;
+                if target >= accessor.count {
+                    return Err(error(format!(
+                        "Sparse index {} is out of range for {} elements",
+                        target,
+                        accessor.count,
+                    )));
+                }
+                let value_start = sparse.values.byte_offset + i * element_size;
+                read_components(
+                    &value_bytes[value_start..value_start + element_size],
+                    accessor.component_type,
+                    accessor.normalized,
+                    &mut values[target * components..(target + 1) * components],
+                );
+            }
+        }
+        Ok((components, values))
+    }
+
+    fn read_vectors<const N: usize>(&self, index: usize) -> Result<Vec<[f32; N]>, GltfError> {
+        let (components, values) = self.read_accessor(index)?;
+        vectors(index, components, &values).map_err(|message| self.error(message))
+    }
+
+    fn read_indices(&self, index: usize) -> Result<Vec<u32>, GltfError> {
+        let (components, values) = self.read_accessor(index)?;
+        if components != 1 {
+            return Err(self.error(format!("Index accessor {} has {} components", index, components)));
+        }
+        Ok(values.into_iter().map(|value| value as u32).collect())
+    }
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for GltfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.io_error.as_ref().map(|err| err as _)
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, GltfError> {
+    let mut file = File::open(path).map_err(|err| {
+        GltfError {
+            message: format!("Failed to open file {:?}", path),
+            io_error: Some(err),
+        }
+    })?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).map_err(|err| {
+        GltfError {
+            message: format!("Failed to read file {:?}", path),
+            io_error: Some(err),
+        }
+    })?;
+    Ok(bytes)
+}
+
+/// Splits a GLB container into its JSON chunk and optional binary chunk.
+fn parse_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), String> {
+    let read_u32 = |offset: usize| {
+        bytes
+            .get(offset..offset + 4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]) as usize)
+    };
+    let version = read_u32(4).ok_or("Truncated GLB header")?;
+    if version != 2 {
+        return Err(format!("GLB version {} is not supported", version));
+    }
+    let length = read_u32(8).ok_or("Truncated GLB header")?.min(bytes.len());
+    let mut chunks = Vec::new();
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let chunk_length = read_u32(offset).unwrap();
+        let chunk_ty = read_u32(offset + 4).unwrap() as u32;
+        let chunk = bytes
+            .get(offset + 8..offset + 8 + chunk_length)
+            .ok_or_else(|| format!("GLB chunk at byte {} is truncated", offset))?;
+        chunks.push((chunk_ty, chunk));
+        offset += 8 + chunk_length;
+    }
+    match chunks.first() {
+        Some(&(GLB_JSON_CHUNK, json)) => {
+            let bin = chunks.get(1).filter(|&&(chunk_ty, _)| chunk_ty == GLB_BIN_CHUNK).map(|&(_, bin)| bin);
+            Ok((json, bin))
+        },
+        _ => Err("GLB does not start with a JSON chunk".into()),
+    }
+}
+
+fn component_size(component_type: u32) -> Option<usize> {
+    match component_type {
+        gl::BYTE | gl::UNSIGNED_BYTE => Some(1),
+        gl::SHORT | gl::UNSIGNED_SHORT => Some(2),
+        gl::UNSIGNED_INT | gl::FLOAT => Some(4),
+        _ => None,
+    }
+}
+
+/// Reads little-endian components of a type `component_size` knows.
+fn read_components(bytes: &[u8], component_type: u32, normalized: bool, components: &mut [f64]) {
+    let size = component_size(component_type).unwrap();
+    for (component, bytes) in components.iter_mut().zip(bytes.chunks(size)) {
+        *component = match component_type {
+            gl::BYTE => {
+                let value = f64::from(bytes[0] as i8);
+                if normalized { (value / 127.0).max(-1.0) } else { value }
+            },
+            gl::UNSIGNED_BYTE => {
+                let value = f64::from(bytes[0]);
+                if normalized { value / 255.0 } else { value }
+            },
+            gl::SHORT => {
+                let value = f64::from(i16::from_le_bytes([bytes[0], bytes[1]]));
+                if normalized { (value / 32767.0).max(-1.0) } else { value }
+            },
+            gl::UNSIGNED_SHORT => {
+                let value = f64::from(u16::from_le_bytes([bytes[0], bytes[1]]));
+                if normalized { value / 65535.0 } else { value }
+            },
+            gl::UNSIGNED_INT => f64::from(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+            _ => f64::from(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        };
+    }
+}
+
+/// Groups accessor values into arrays of exactly `N` components.
+fn vectors<const N: usize>(accessor: usize, components: usize, values: &[f64]) -> Result<Vec<[f32; N]>, String> {
+    if components != N {
+        return Err(format!("Accessor {} has {} components instead of {}", accessor, components, N));
+    }
+    Ok(values
+        .chunks(N)
+        .map(|chunk| {
+            let mut vector = [0.0; N];
+            for (component, &value) in vector.iter_mut().zip(chunk) {
+                *component = value as f32;
+            }
+            vector
+        })
+        .collect())
+}
+
+/// Pixels in a format `Texture` can take, expanding gray with alpha to RGBA.
+fn texture_pixels(image: &Image) -> (TextureFormat, Cow<'_, [u8]>) {
+    match image.format.to_texture_format() {
+        Some(format) => (format, Cow::Borrowed(&image.pixels)),
+        None => {
+            let pixels = image
+                .pixels
+                .chunks(2)
+                .flat_map(|gray_alpha| vec![gray_alpha[0], gray_alpha[0], gray_alpha[0], gray_alpha[1]])
+                .collect();
+            (TextureFormat::Rgba, Cow::Owned(pixels))
+        },
+    }
+}
+
+fn matrix_from_columns(m: &[f32; 16]) -> Matrix4<f32> {
+    Matrix4::from([
+        [m[0], m[1], m[2], m[3]],
+        [m[4], m[5], m[6], m[7]],
+        [m[8], m[9], m[10], m[11]],
+        [m[12], m[13], m[14], m[15]],
+    ])
+}
+
+/// Splits an affine matrix into translation, rotation and scale, folding any
+/// mirroring into a negative X scale.
+fn decompose(matrix: &Matrix4<f32>) -> (Vector3<f32>, Quaternion<f32>, Vector3<f32>) {
+    let translation = matrix.w.truncate();
+    let (x, y, z) = (matrix.x.truncate(), matrix.y.truncate(), matrix.z.truncate());
+    let mut scale = Vector3::new(x.magnitude(), y.magnitude(), z.magnitude());
+    if Matrix3::from_cols(x, y, z).determinant() < 0.0 {
+        scale.x = -scale.x;
+    }
+    let unscale = |axis: Vector3<f32>, scale: f32| if scale != 0.0 { axis / scale } else { axis };
+    let rotation = Matrix3::from_cols(unscale(x, scale.x), unscale(y, scale.y), unscale(z, scale.z));
+    (translation, Quaternion::from(rotation).normalize(), scale)
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+    for c in text.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => return None,
+        };
+        bits = (bits << 6) | u32::from(value);
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            bytes.push((bits >> bit_count) as u8);
+            bits &= (1 << bit_count) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+/// Turns `%20` and the like in relative URIs back into bytes.
+fn decode_percents(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            },
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn truncate(text: &str, max_chars: usize) -> &str {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Rotation3;
+    use serde_json::Value;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn encode_base64(bytes: &[u8]) -> String {
+        let alphabet = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut text = String::new();
+        for chunk in bytes.chunks(3) {
+            let bits = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |bits, (i, &byte)| bits | u32::from(byte) << (16 - 8 * i));
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    text.push(alphabet[(bits >> (18 - 6 * i) & 63) as usize] as char);
+                } else {
+                    text.push('=');
+                }
+            }
+        }
+        text
+    }
+
+    fn push_floats(buffer: &mut Vec<u8>, values: &[f32]) {
+        for value in values {
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    /// A buffer with the data of `document`, and the byte range of each of
+    /// its buffer views.
+    fn buffer() -> (Vec<u8>, Vec<(usize, usize)>) {
+        let mut buffer = Vec::new();
+        let mut views = Vec::new();
+        let mut view = |buffer: &mut Vec<u8>, fill: &dyn Fn(&mut Vec<u8>)| {
+            let start = buffer.len();
+            fill(buffer);
+            views.push((start, buffer.len() - start));
+            while !buffer.len().is_multiple_of(4) {
+                buffer.push(0);
+            }
+        };
+        // Positions of a unit square.
+        view(&mut buffer, &|buffer| {
+            push_floats(buffer, &[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0])
+        });
+        // Normalized 16 bit texture coordinates.
+        view(&mut buffer, &|buffer| {
+            for &value in &[0u16, 0, 65535, 0, 65535, 65535, 0, 65535] {
+                buffer.extend_from_slice(&value.to_le_bytes());
+            }
+        });
+        // 16 bit indices.
+        view(&mut buffer, &|buffer| {
+            for &value in &[0u16, 1, 2, 3] {
+                buffer.extend_from_slice(&value.to_le_bytes());
+            }
+        });
+        // Key times.
+        view(&mut buffer, &|buffer| push_floats(buffer, &[0.0, 1.0]));
+        // Translations.
+        view(&mut buffer, &|buffer| push_floats(buffer, &[0.0, 0.0, 0.0, 2.0, 4.0, 6.0]));
+        // Rotations, from none to half a turn around Z.
+        view(&mut buffer, &|buffer| push_floats(buffer, &[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0]));
+        // Scales.
+        view(&mut buffer, &|buffer| push_floats(buffer, &[1.0, 1.0, 1.0, 2.0, 2.0, 2.0]));
+        // Cubic spline translations as in-tangent, value and out-tangent.
+        view(&mut buffer, &|buffer| {
+            push_floats(buffer, &[0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+            push_floats(buffer, &[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        });
+        // Sparse indices and values.
+        view(&mut buffer, &|buffer| buffer.push(2));
+        view(&mut buffer, &|buffer| push_floats(buffer, &[5.0, 5.0, 5.0]));
+        (buffer, views)
+    }
+
+    /// A document with a square drawn as a fan, a line loop and two point
+    /// lists, a small node hierarchy and an animation of every kind.
+    fn document(buffer_uri: Option<String>) -> (Value, Vec<u8>) {
+        let (data, views) = buffer();
+        let mut buffer_views: Vec<Value> = views
+            .iter()
+            .map(|&(offset, length)| serde_json::json!({"buffer": 0, "byteOffset": offset, "byteLength": length}))
+            .collect();
+        // The positions again, reading every other one.
+        buffer_views.push(serde_json::json!({"buffer": 0, "byteLength": 48, "byteStride": 24}));
+        let mut buffer = serde_json::json!({"byteLength": data.len()});
+        if let Some(uri) = buffer_uri {
+            buffer["uri"] = Value::String(uri);
+        }
+        let matrix = [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 3.0, 1.0];
+        let document = serde_json::json!({
+            "asset": {"version": "2.0"},
+            "scene": 0,
+            "scenes": [{"nodes": [0, 2]}],
+            "nodes": [
+                {"translation": [1.0, 0.0, 0.0], "children": [1], "mesh": 0},
+                {"translation": [0.0, 1.0, 0.0], "scale": [2.0, 2.0, 2.0]},
+                {"matrix": matrix},
+            ],
+            "meshes": [{
+                "name": "square",
+                "primitives": [
+                    {"attributes": {"POSITION": 0, "TEXCOORD_0": 1}, "indices": 2, "mode": 6},
+                    {"attributes": {"POSITION": 0}, "mode": 2},
+                    {"attributes": {"POSITION": 8}, "mode": 0},
+                    {"attributes": {"POSITION": 9}, "mode": 0},
+                ],
+            }],
+            "accessors": [
+                {"bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3"},
+                {"bufferView": 1, "componentType": 5123, "normalized": true, "count": 4, "type": "VEC2"},
+                {"bufferView": 2, "componentType": 5123, "count": 4, "type": "SCALAR"},
+                {"bufferView": 3, "componentType": 5126, "count": 2, "type": "SCALAR"},
+                {"bufferView": 4, "componentType": 5126, "count": 2, "type": "VEC3"},
+                {"bufferView": 5, "componentType": 5126, "count": 2, "type": "VEC4"},
+                {"bufferView": 6, "componentType": 5126, "count": 2, "type": "VEC3"},
+                {"bufferView": 7, "componentType": 5126, "count": 6, "type": "VEC3"},
+                {
+                    "componentType": 5126,
+                    "count": 3,
+                    "type": "VEC3",
+                    "sparse": {
+                        "count": 1,
+                        "indices": {"bufferView": 8, "componentType": 5121},
+                        "values": {"bufferView": 9},
+                    },
+                },
+                {"bufferView": 10, "componentType": 5126, "count": 2, "type": "VEC3"},
+            ],
+            "bufferViews": buffer_views,
+            "buffers": [buffer],
+            "animations": [{
+                "channels": [
+                    {"sampler": 0, "target": {"node": 0, "path": "translation"}},
+                    {"sampler": 1, "target": {"node": 0, "path": "rotation"}},
+                    {"sampler": 2, "target": {"node": 0, "path": "scale"}},
+                    {"sampler": 3, "target": {"node": 1, "path": "translation"}},
+                ],
+                "samplers": [
+                    {"input": 3, "output": 4},
+                    {"input": 3, "output": 5, "interpolation": "LINEAR"},
+                    {"input": 3, "output": 6, "interpolation": "STEP"},
+                    {"input": 3, "output": 7, "interpolation": "CUBICSPLINE"},
+                ],
+            }],
+        });
+        (document, data)
+    }
+
+    fn embedded() -> Vec<u8> {
+        let (_, data) = document(None);
+        let uri = format!("data:application/octet-stream;base64,{}", encode_base64(&data));
+        document(Some(uri)).0.to_string().into_bytes()
+    }
+
+    fn glb(json: &[u8], bin: &[u8]) -> Vec<u8> {
+        let mut chunks = Vec::new();
+        for &(chunk_ty, chunk, padding) in &[(GLB_JSON_CHUNK, json, b' '), (GLB_BIN_CHUNK, bin, 0)] {
+            let padded_len = chunk.len().div_ceil(4) * 4;
+            chunks.extend_from_slice(&(padded_len as u32).to_le_bytes());
+            chunks.extend_from_slice(&chunk_ty.to_le_bytes());
+            chunks.extend_from_slice(chunk);
+            chunks.resize(chunks.len() + padded_len - chunk.len(), padding);
+        }
+        let mut bytes = GLB_MAGIC.to_vec();
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        bytes.extend_from_slice(&(12 + chunks.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&chunks);
+        bytes
+    }
+
+    fn parse(bytes: &[u8]) -> Result<GltfAsset, GltfError> {
+        GltfAsset::parse(bytes, Path::new("models/test.gltf"))
+    }
+
+    fn parse_error(bytes: &[u8]) -> String {
+        match parse(bytes) {
+            Ok(_) => panic!("parsing succeeded"),
+            Err(err) => err.message,
+        }
+    }
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        assert!(a.iter().zip(b).all(|(a, b)| (a - b).abs() < EPSILON), "{:?} is not {:?}", a, b);
+    }
+
+    #[test]
+    fn decodes_base64_and_percents() {
+        assert_eq!(decode_base64("").unwrap(), b"");
+        assert_eq!(decode_base64("Zm9vYmFy").unwrap(), b"foobar");
+        assert_eq!(decode_base64("Zm9vYg==").unwrap(), b"foob");
+        assert_eq!(decode_base64("-_8=").unwrap(), [0xFB, 0xFF]);
+        assert_eq!(decode_base64("Zm9v YmFy"), None);
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode_base64(&encode_base64(&bytes)).unwrap(), bytes);
+        assert_eq!(decode_percents("my%20model%2Fbuffer.bin"), "my model/buffer.bin");
+        assert_eq!(decode_percents("100%"), "100%");
+        assert_eq!(decode_percents("%zz%4"), "%zz%4");
+    }
+
+    #[test]
+    fn splits_glb_chunks() {
+        let bytes = glb(b"{}", b"\x01\x02");
+        let (json, bin) = parse_glb(&bytes).unwrap();
+        assert_eq!(json, b"{}  ");
+        assert_eq!(bin, Some(&b"\x01\x02\0\0"[..]));
+        assert!(parse_glb(&bytes[..10]).is_err());
+        let mut version_1 = bytes.clone();
+        version_1[4] = 1;
+        assert_eq!(parse_glb(&version_1).unwrap_err(), "GLB version 1 is not supported");
+        let mut truncated = bytes.clone();
+        truncated.truncate(22);
+        truncated[8] = 40;
+        assert!(parse_glb(&truncated).unwrap_err().contains("truncated"));
+        let mut bin_first = bytes;
+        bin_first[16..20].copy_from_slice(&GLB_BIN_CHUNK.to_le_bytes());
+        assert!(parse_glb(&bin_first).is_err());
+    }
+
+    #[test]
+    fn reads_primitives() {
+        let asset = parse(&embedded()).unwrap();
+        assert_eq!(asset.meshes.len(), 1);
+        assert_eq!(asset.meshes[0].name.as_deref(), Some("square"));
+        let primitives = &asset.meshes[0].primitives;
+        assert_eq!(primitives.len(), 4);
+        let square = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]];
+        // The fan becomes a list, and texture coordinates are flipped.
+        let fan = &primitives[0];
+        assert_eq!(fan.topology, DrawTy::Triangles);
+        assert_eq!(fan.positions, square);
+        assert_eq!(fan.uvs, vec![[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]]);
+        assert_eq!(fan.indices, Some(vec![0, 1, 2, 0, 2, 3]));
+        assert!(fan.normals.iter().all(|&normal| normal == [0.0, 0.0, 1.0]));
+        assert_eq!(fan.submeshes.len(), 1);
+        assert_eq!(fan.submeshes[0].count, 6);
+        assert_eq!(fan.submeshes[0].material, None);
+        let lines = &primitives[1];
+        assert_eq!(lines.topology, DrawTy::Lines);
+        assert_eq!(lines.indices, Some(vec![0, 1, 1, 2, 2, 3, 3, 0]));
+        assert!(lines.normals.is_empty());
+        // Sparse values on top of zeros.
+        let sparse = &primitives[2];
+        assert_eq!(sparse.topology, DrawTy::Points);
+        assert_eq!(sparse.positions, vec![[0.0, 0.0, 0.0], [0.0, 0.0, 0.0], [5.0, 5.0, 5.0]]);
+        // A stride of two positions.
+        assert_eq!(primitives[3].positions, vec![[0.0, 0.0, 0.0], [1.0, 1.0, 0.0]]);
+    }
+
+    #[test]
+    fn reads_glb_with_binary_chunk() {
+        let (json, data) = document(None);
+        let asset = GltfAsset::parse(&glb(json.to_string().as_bytes(), &data), Path::new("test.glb")).unwrap();
+        let embedded = parse(&embedded()).unwrap();
+        assert_eq!(asset.meshes[0].primitives, embedded.meshes[0].primitives);
+        assert_eq!(asset.nodes, embedded.nodes);
+        assert_eq!(asset.animations, embedded.animations);
+    }
+
+    #[test]
+    fn instantiates_node_transforms() {
+        let asset = parse(&embedded()).unwrap();
+        assert_eq!(asset.default_scene, Some(0));
+        assert_eq!(asset.scenes[0].roots, vec![0, 2]);
+        assert_eq!(asset.nodes[0].mesh, Some(0));
+        assert_eq!(asset.nodes[0].children, vec![1]);
+        // The matrix is split into translation, rotation and scale.
+        let matrix_node = &asset.nodes[2].transform;
+        assert_eq!(matrix_node.translation, Vector3::new(0.0, 0.0, 3.0));
+        assert_eq!(matrix_node.scale, Vector3::new(1.0, 1.0, 1.0));
+        let instance = asset.instantiate(0).unwrap();
+        let world_translation = |node: usize| {
+            let id = instance.node_ids[node].unwrap();
+            assert_eq!(instance.scene.item(id), Some(&node));
+            instance.scene.world_matrix(id).unwrap().w.truncate()
+        };
+        assert_eq!(world_translation(0), Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(world_translation(1), Vector3::new(1.0, 1.0, 0.0));
+        assert_eq!(world_translation(2), Vector3::new(0.0, 0.0, 3.0));
+        assert!(asset.instantiate(1).is_err());
+    }
+
+    #[test]
+    fn samples_animations() {
+        let asset = parse(&embedded()).unwrap();
+        let animation = &asset.animations[0];
+        assert_eq!(animation.duration, 1.0);
+        let channels = &animation.channels;
+        assert_eq!(channels.len(), 4);
+        assert_eq!(channels[0].interpolation, Interpolation::Linear);
+        assert_close(&channels[0].sample(0.25), &[0.5, 1.0, 1.5]);
+        // Keys hold outside their range.
+        assert_close(&channels[0].sample(-1.0), &[0.0, 0.0, 0.0]);
+        assert_close(&channels[0].sample(2.0), &[2.0, 4.0, 6.0]);
+        let quarter_turn = Quaternion::from_angle_z(Rad(std::f32::consts::FRAC_PI_2));
+        assert_close(&channels[1].sample(0.5), &[0.0, 0.0, quarter_turn.v.z, quarter_turn.s]);
+        assert_eq!(channels[2].interpolation, Interpolation::Step);
+        assert_close(&channels[2].sample(0.99), &[1.0, 1.0, 1.0]);
+        assert_close(&channels[2].sample(1.0), &[2.0, 2.0, 2.0]);
+        // Halfway along the Hermite curve from 0 to 1 with an out-tangent of
+        // 1 at the start.
+        assert_eq!(channels[3].interpolation, Interpolation::CubicSpline);
+        assert_close(&channels[3].sample(0.5), &[0.625, 0.0, 0.0]);
+        assert_close(&channels[3].sample(1.0), &[1.0, 0.0, 0.0]);
+        let mut instance = asset.instantiate(0).unwrap();
+        animation.apply(1.0, &mut instance);
+        let transform = instance.scene.transform(instance.node_ids[0].unwrap()).unwrap();
+        assert_eq!(transform.translation, Vector3::new(2.0, 4.0, 6.0));
+        assert_eq!(transform.scale, Vector3::new(2.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn rejects_accessors_beyond_their_data() {
+        let (mut json, data) = document(None);
+        json["buffers"][0]["uri"] = Value::String(format!("data:;base64,{}", encode_base64(&data)));
+        let with_count = |accessor: usize, count: usize| {
+            let mut json = json.clone();
+            json["accessors"][accessor]["count"] = count.into();
+            json.to_string().into_bytes()
+        };
+        assert!(parse_error(&with_count(0, 5)).contains("do not fit"));
+        assert!(parse_error(&with_count(0, usize::MAX / 2)).contains("do not fit"));
+        assert!(parse_error(&with_count(8, usize::MAX / 4)).contains("do not fit"));
+        assert!(parse_error(&with_count(8, 2)).contains("Sparse index 2 is out of range"));
+        let mut json = json.clone();
+        json["bufferViews"][0]["byteLength"] = usize::MAX.into();
+        assert!(parse_error(json.to_string().as_bytes()).contains("Buffer view 0 exceeds buffer 0"));
+    }
+}
@@ -40,8 +40,24 @@ impl Image {
             ImageError {
                 message: format!("Failed to read image {:?}", path),
             }
-        })?.flipv();
-        let (format, width, height, pixels) = match dynamic_img {
+        })?;
+        Ok(Image::from_dynamic_image(dynamic_img))
+    }
+
+    /// Decodes an image file already in memory, in any format the file
+    /// loader understands.
+    pub fn from_memory(bytes: &[u8]) -> Result<Self, ImageError> {
+        let dynamic_img = piston_image::load_from_memory(bytes).map_err(|err| {
+            ImageError {
+                message: format!("Failed to decode image: {}", err),
+            }
+        })?;
+        Ok(Image::from_dynamic_image(dynamic_img))
+    }
+
+    /// Flips the rows so the first is the bottom one, as GL expects.
+    fn from_dynamic_image(dynamic_img: piston_image::DynamicImage) -> Self {
+        let (format, width, height, pixels) = match dynamic_img.flipv() {
             piston_image::DynamicImage::ImageLuma8(img) => {
                 (ImageFormat::R, img.width(), img.height(), img.into_raw())
             },
@@ -55,11 +71,11 @@ impl Image {
                 (ImageFormat::Rgba, img.width(), img.height(), img.into_raw())
             },
         };
-        Ok(Image {
-            format: format,
+        Image {
+            format,
             width: width as _,
             height: height as _,
-            pixels: pixels,
-        })
+            pixels,
+        }
     }
 }
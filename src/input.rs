@@ -0,0 +1,611 @@
+//! Keyboard and mouse state fed from glutin events. glutin does not report
+//! gamepads, so those are not tracked.
+//!
+//! Window events are first translated into `InputEvent`s, which tests can
+//! also construct directly to drive `Input` without a window.
+
+use glutin;
+use std;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+pub use glutin::{ElementState, MouseButton, VirtualKeyCode};
+
+/// Lines scrolled per pixel of touchpad scrolling, so both kinds of wheel
+/// delta can be treated alike.
+const LINES_PER_PIXEL: f32 = 1.0 / 20.0;
+
+/// Keys that can be named in an action map, spelled as their
+/// `VirtualKeyCode` variants.
+const NAMED_KEYS: &[VirtualKeyCode] = &[
+    VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3, VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5, VirtualKeyCode::Key6, VirtualKeyCode::Key7, VirtualKeyCode::Key8,
+    VirtualKeyCode::Key9, VirtualKeyCode::Key0,
+    VirtualKeyCode::A, VirtualKeyCode::B, VirtualKeyCode::C, VirtualKeyCode::D, VirtualKeyCode::E,
+    VirtualKeyCode::F, VirtualKeyCode::G, VirtualKeyCode::H, VirtualKeyCode::I, VirtualKeyCode::J,
+    VirtualKeyCode::K, VirtualKeyCode::L, VirtualKeyCode::M, VirtualKeyCode::N, VirtualKeyCode::O,
+    VirtualKeyCode::P, VirtualKeyCode::Q, VirtualKeyCode::R, VirtualKeyCode::S, VirtualKeyCode::T,
+    VirtualKeyCode::U, VirtualKeyCode::V, VirtualKeyCode::W, VirtualKeyCode::X, VirtualKeyCode::Y,
+    VirtualKeyCode::Z,
+    VirtualKeyCode::F1, VirtualKeyCode::F2, VirtualKeyCode::F3, VirtualKeyCode::F4,
+    VirtualKeyCode::F5, VirtualKeyCode::F6, VirtualKeyCode::F7, VirtualKeyCode::F8,
+    VirtualKeyCode::F9, VirtualKeyCode::F10, VirtualKeyCode::F11, VirtualKeyCode::F12,
+    VirtualKeyCode::Escape, VirtualKeyCode::Tab, VirtualKeyCode::Space, VirtualKeyCode::Return,
+    VirtualKeyCode::Back, VirtualKeyCode::Insert, VirtualKeyCode::Delete, VirtualKeyCode::Home,
+    VirtualKeyCode::End, VirtualKeyCode::PageUp, VirtualKeyCode::PageDown,
+    VirtualKeyCode::Left, VirtualKeyCode::Right, VirtualKeyCode::Up, VirtualKeyCode::Down,
+    VirtualKeyCode::LShift, VirtualKeyCode::RShift, VirtualKeyCode::LControl,
+    VirtualKeyCode::RControl, VirtualKeyCode::LAlt, VirtualKeyCode::RAlt,
+    VirtualKeyCode::Minus, VirtualKeyCode::Equals, VirtualKeyCode::LBracket,
+    VirtualKeyCode::RBracket, VirtualKeyCode::Semicolon, VirtualKeyCode::Apostrophe,
+    VirtualKeyCode::Comma, VirtualKeyCode::Period, VirtualKeyCode::Slash,
+    VirtualKeyCode::Backslash, VirtualKeyCode::Grave,
+    VirtualKeyCode::Numpad0, VirtualKeyCode::Numpad1, VirtualKeyCode::Numpad2,
+    VirtualKeyCode::Numpad3, VirtualKeyCode::Numpad4, VirtualKeyCode::Numpad5,
+    VirtualKeyCode::Numpad6, VirtualKeyCode::Numpad7, VirtualKeyCode::Numpad8,
+    VirtualKeyCode::Numpad9, VirtualKeyCode::Add, VirtualKeyCode::Subtract,
+    VirtualKeyCode::Multiply, VirtualKeyCode::Divide, VirtualKeyCode::NumpadEnter,
+];
+
+/// The subset of window and device events that `Input` consumes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputEvent {
+    Key(VirtualKeyCode, ElementState),
+    MouseButton(MouseButton, ElementState),
+    /// Cursor position in physical pixels from the top-left of the window.
+    CursorMoved(f64, f64),
+    CursorLeft,
+    /// Raw mouse motion, reported even while the cursor is grabbed.
+    MouseMotion(f64, f64),
+    /// Horizontal and vertical scroll in lines.
+    Wheel(f32, f32),
+    Character(char),
+    Focused(bool),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    MouseButton(MouseButton),
+}
+
+/// Named actions, each bound to any number of keys and mouse buttons, so
+/// apps can ask for "jump" rather than a particular key.
+///
+/// The config format has one action per line, with `#` starting a comment:
+///
+/// ```text
+/// move_forward = W, Up
+/// fire = MouseLeft
+/// ```
+///
+/// Keys are spelled as `VirtualKeyCode` variants; mouse buttons as
+/// `MouseLeft`, `MouseRight`, `MouseMiddle` or `Mouse` followed by a button
+/// number.
+#[derive(Clone, Debug, Default)]
+pub struct ActionMap {
+    bindings: HashMap<String, Vec<Binding>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Input {
+    actions: ActionMap,
+    keys_down: HashSet<VirtualKeyCode>,
+    keys_pressed: HashSet<VirtualKeyCode>,
+    keys_released: HashSet<VirtualKeyCode>,
+    buttons_down: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+    cursor_position: Option<(f64, f64)>,
+    mouse_delta: (f64, f64),
+    wheel_delta: (f32, f32),
+    text: String,
+    is_focused: bool,
+    is_cursor_grabbed: bool,
+    cursor_grab_changed: bool,
+}
+
+#[derive(Debug)]
+pub struct InputError {
+    pub message: String,
+    pub io_error: Option<io::Error>,
+}
+
+impl InputEvent {
+    pub fn from_window_event(event: &glutin::WindowEvent) -> Option<InputEvent> {
+        match *event {
+            glutin::WindowEvent::KeyboardInput { input, .. } => {
+                input.virtual_keycode.map(|key| InputEvent::Key(key, input.state))
+            },
+            glutin::WindowEvent::MouseInput { state, button, .. } => {
+                Some(InputEvent::MouseButton(button, state))
+            },
+            glutin::WindowEvent::CursorMoved { position: (x, y), .. } => {
+                Some(InputEvent::CursorMoved(x, y))
+            },
+            glutin::WindowEvent::CursorLeft { .. } => Some(InputEvent::CursorLeft),
+            glutin::WindowEvent::MouseWheel { delta, .. } => {
+                let (x, y) = match delta {
+                    glutin::MouseScrollDelta::LineDelta(x, y) => (x, y),
+                    glutin::MouseScrollDelta::PixelDelta(x, y) => (x * LINES_PER_PIXEL, y * LINES_PER_PIXEL),
+                };
+                Some(InputEvent::Wheel(x, y))
+            },
+            glutin::WindowEvent::ReceivedCharacter(character) => Some(InputEvent::Character(character)),
+            glutin::WindowEvent::Focused(is_focused) => Some(InputEvent::Focused(is_focused)),
+            _ => None,
+        }
+    }
+
+    pub fn from_device_event(event: &glutin::DeviceEvent) -> Option<InputEvent> {
+        match *event {
+            glutin::DeviceEvent::MouseMotion { delta: (x, y) } => Some(InputEvent::MouseMotion(x, y)),
+            _ => None,
+        }
+    }
+}
+
+impl Binding {
+    pub fn parse(name: &str) -> Option<Binding> {
+        match name {
+            "MouseLeft" => return Some(Binding::MouseButton(MouseButton::Left)),
+            "MouseRight" => return Some(Binding::MouseButton(MouseButton::Right)),
+            "MouseMiddle" => return Some(Binding::MouseButton(MouseButton::Middle)),
+            _ => (),
+        }
+        if let Some(number) = name.strip_prefix("Mouse") {
+            return number.parse().ok().map(|number| Binding::MouseButton(MouseButton::Other(number)));
+        }
+        NAMED_KEYS
+            .iter()
+            .find(|key| format!("{:?}", key) == name)
+            .map(|&key| Binding::Key(key))
+    }
+}
+
+impl ActionMap {
+    pub fn new() -> Self {
+        ActionMap::default()
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, InputError> {
+        let mut file = File::open(path).map_err(|err| {
+            InputError {
+                message: format!("Failed to open file {:?}", path),
+                io_error: Some(err),
+            }
+        })?;
+        let mut source = String::new();
+        file.read_to_string(&mut source).map_err(|err| {
+            InputError {
+                message: format!("Failed to read file {:?}", path),
+                io_error: Some(err),
+            }
+        })?;
+        ActionMap::parse(&source).map_err(|err| {
+            InputError {
+                message: format!("{}: {}", path.display(), err.message),
+                io_error: None,
+            }
+        })
+    }
+
+    /// Parses the config format described on `ActionMap`. Repeating an action
+    /// adds to its bindings.
+    pub fn parse(source: &str) -> Result<Self, InputError> {
+        let mut actions = ActionMap::new();
+        for (index, line) in source.lines().enumerate() {
+            let error = |message: String| {
+                InputError {
+                    message: format!("line {}: {}", index + 1, message),
+                    io_error: None,
+                }
+            };
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (action, names) = line
+                .split_once('=')
+                .ok_or_else(|| error(format!("Expected `action = bindings`, got {:?}", line)))?;
+            let action = action.trim();
+            if action.is_empty() {
+                return Err(error("Missing action name".into()));
+            }
+            for name in names.split(',').map(str::trim) {
+                let binding = Binding::parse(name).ok_or_else(|| error(format!("Unknown key {:?}", name)))?;
+                actions.bind(action, binding);
+            }
+        }
+        Ok(actions)
+    }
+
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        let bindings = self.bindings.entry(action.into()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn unbind_all(&mut self, action: &str) {
+        self.bindings.remove(action);
+    }
+
+    /// Empty for unknown actions.
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.bindings.get(action).map_or(&[], |bindings| bindings)
+    }
+}
+
+impl Input {
+    pub fn new(actions: ActionMap) -> Self {
+        Input {
+            actions,
+            keys_down: HashSet::new(),
+            keys_pressed: HashSet::new(),
+            keys_released: HashSet::new(),
+            buttons_down: HashSet::new(),
+            buttons_pressed: HashSet::new(),
+            buttons_released: HashSet::new(),
+            cursor_position: None,
+            mouse_delta: (0.0, 0.0),
+            wheel_delta: (0.0, 0.0),
+            text: String::new(),
+            is_focused: true,
+            is_cursor_grabbed: false,
+            cursor_grab_changed: false,
+        }
+    }
+
+    pub fn actions(&self) -> &ActionMap {
+        &self.actions
+    }
+
+    pub fn actions_mut(&mut self) -> &mut ActionMap {
+        &mut self.actions
+    }
+
+    pub fn handle_event(&mut self, event: InputEvent) {
+        match event {
+            InputEvent::Key(key, ElementState::Pressed) => {
+                // Key repeat sends further presses while held.
+                if self.keys_down.insert(key) {
+                    self.keys_pressed.insert(key);
+                }
+            },
+            InputEvent::Key(key, ElementState::Released) => {
+                if self.keys_down.remove(&key) {
+                    self.keys_released.insert(key);
+                }
+            },
+            InputEvent::MouseButton(button, ElementState::Pressed) => {
+                if self.buttons_down.insert(button) {
+                    self.buttons_pressed.insert(button);
+                }
+            },
+            InputEvent::MouseButton(button, ElementState::Released) => {
+                if self.buttons_down.remove(&button) {
+                    self.buttons_released.insert(button);
+                }
+            },
+            InputEvent::CursorMoved(x, y) => self.cursor_position = Some((x, y)),
+            InputEvent::CursorLeft => self.cursor_position = None,
+            InputEvent::MouseMotion(x, y) => {
+                // Device events arrive regardless of which window has focus.
+                if self.is_focused {
+                    self.mouse_delta.0 += x;
+                    self.mouse_delta.1 += y;
+                }
+            },
+            InputEvent::Wheel(x, y) => {
+                self.wheel_delta.0 += x;
+                self.wheel_delta.1 += y;
+            },
+            InputEvent::Character(character) => {
+                if !character.is_control() {
+                    self.text.push(character);
+                }
+            },
+            InputEvent::Focused(is_focused) => {
+                self.is_focused = is_focused;
+                if !is_focused {
+                    // Releases would go to whichever window gained focus.
+                    self.keys_released.extend(self.keys_down.drain());
+                    self.buttons_released.extend(self.buttons_down.drain());
+                }
+            },
+        }
+    }
+
+    /// Forgets presses, releases, deltas and text seen so far. The runner
+    /// calls this after each `App::update`, so every press is seen by exactly
+    /// one update however many run per frame.
+    pub fn end_update(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.mouse_delta = (0.0, 0.0);
+        self.wheel_delta = (0.0, 0.0);
+        self.text.clear();
+    }
+
+    pub fn is_key_down(&self, key: VirtualKeyCode) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    pub fn was_key_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.keys_pressed.contains(&key)
+    }
+
+    pub fn was_key_released(&self, key: VirtualKeyCode) -> bool {
+        self.keys_released.contains(&key)
+    }
+
+    pub fn is_button_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+
+    pub fn was_button_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    pub fn was_button_released(&self, button: MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+    pub fn is_binding_down(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.is_key_down(key),
+            Binding::MouseButton(button) => self.is_button_down(button),
+        }
+    }
+
+    pub fn was_binding_pressed(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.was_key_pressed(key),
+            Binding::MouseButton(button) => self.was_button_pressed(button),
+        }
+    }
+
+    pub fn was_binding_released(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.was_key_released(key),
+            Binding::MouseButton(button) => self.was_button_released(button),
+        }
+    }
+
+    /// True while any of the action's bindings is held.
+    pub fn is_action_down(&self, action: &str) -> bool {
+        self.actions.bindings(action).iter().any(|&binding| self.is_binding_down(binding))
+    }
+
+    pub fn was_action_pressed(&self, action: &str) -> bool {
+        self.actions.bindings(action).iter().any(|&binding| self.was_binding_pressed(binding))
+    }
+
+    /// True once the last held binding of the action is released.
+    pub fn was_action_released(&self, action: &str) -> bool {
+        let bindings = self.actions.bindings(action);
+        bindings.iter().any(|&binding| self.was_binding_released(binding))
+            && !bindings.iter().any(|&binding| self.is_binding_down(binding))
+    }
+
+    /// `None` while the cursor is outside the window.
+    pub fn cursor_position(&self) -> Option<(f64, f64)> {
+        self.cursor_position
+    }
+
+    /// Raw mouse motion since the last update. Unlike the cursor position it
+    /// keeps changing while the cursor is grabbed, so use it for camera
+    /// control.
+    pub fn mouse_delta(&self) -> (f64, f64) {
+        self.mouse_delta
+    }
+
+    /// Lines scrolled since the last update; positive `y` scrolls up.
+    pub fn wheel_delta(&self) -> (f32, f32) {
+        self.wheel_delta
+    }
+
+    /// Characters typed since the last update, for text fields.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.is_focused
+    }
+
+    pub fn is_cursor_grabbed(&self) -> bool {
+        self.is_cursor_grabbed
+    }
+
+    /// Hides the cursor and confines it to the window. Applied by the runner
+    /// after the current update.
+    pub fn set_cursor_grabbed(&mut self, is_grabbed: bool) {
+        if self.is_cursor_grabbed != is_grabbed {
+            self.is_cursor_grabbed = is_grabbed;
+            self.cursor_grab_changed = true;
+        }
+    }
+
+    pub(crate) fn take_cursor_grab_change(&mut self) -> Option<bool> {
+        if self.cursor_grab_changed {
+            self.cursor_grab_changed = false;
+            Some(self.is_cursor_grabbed)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for InputError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.io_error.as_ref().map(|err| err as _)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(input: &mut Input, key: VirtualKeyCode) {
+        input.handle_event(InputEvent::Key(key, ElementState::Pressed));
+    }
+
+    fn release(input: &mut Input, key: VirtualKeyCode) {
+        input.handle_event(InputEvent::Key(key, ElementState::Released));
+    }
+
+    #[test]
+    fn press_is_seen_by_one_update() {
+        let mut input = Input::new(ActionMap::new());
+        press(&mut input, VirtualKeyCode::A);
+        assert!(input.is_key_down(VirtualKeyCode::A));
+        assert!(input.was_key_pressed(VirtualKeyCode::A));
+        input.end_update();
+        assert!(input.is_key_down(VirtualKeyCode::A));
+        assert!(!input.was_key_pressed(VirtualKeyCode::A));
+        release(&mut input, VirtualKeyCode::A);
+        assert!(!input.is_key_down(VirtualKeyCode::A));
+        assert!(input.was_key_released(VirtualKeyCode::A));
+    }
+
+    #[test]
+    fn key_repeat_does_not_press_again() {
+        let mut input = Input::new(ActionMap::new());
+        press(&mut input, VirtualKeyCode::A);
+        input.end_update();
+        press(&mut input, VirtualKeyCode::A);
+        assert!(!input.was_key_pressed(VirtualKeyCode::A));
+    }
+
+    #[test]
+    fn tap_within_one_update_is_pressed_and_released() {
+        let mut input = Input::new(ActionMap::new());
+        press(&mut input, VirtualKeyCode::Space);
+        release(&mut input, VirtualKeyCode::Space);
+        assert!(!input.is_key_down(VirtualKeyCode::Space));
+        assert!(input.was_key_pressed(VirtualKeyCode::Space));
+        assert!(input.was_key_released(VirtualKeyCode::Space));
+    }
+
+    #[test]
+    fn losing_focus_releases_everything() {
+        let mut input = Input::new(ActionMap::new());
+        press(&mut input, VirtualKeyCode::W);
+        input.handle_event(InputEvent::MouseButton(MouseButton::Left, ElementState::Pressed));
+        input.end_update();
+        input.handle_event(InputEvent::Focused(false));
+        assert!(!input.is_key_down(VirtualKeyCode::W));
+        assert!(input.was_key_released(VirtualKeyCode::W));
+        assert!(input.was_button_released(MouseButton::Left));
+        input.handle_event(InputEvent::MouseMotion(5.0, 5.0));
+        assert_eq!(input.mouse_delta(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn mouse_deltas_accumulate_until_update_ends() {
+        let mut input = Input::new(ActionMap::new());
+        input.handle_event(InputEvent::CursorMoved(10.0, 20.0));
+        input.handle_event(InputEvent::MouseMotion(1.0, 2.0));
+        input.handle_event(InputEvent::MouseMotion(3.0, -1.0));
+        input.handle_event(InputEvent::Wheel(0.0, 1.0));
+        input.handle_event(InputEvent::Wheel(0.0, 2.0));
+        assert_eq!(input.cursor_position(), Some((10.0, 20.0)));
+        assert_eq!(input.mouse_delta(), (4.0, 1.0));
+        assert_eq!(input.wheel_delta(), (0.0, 3.0));
+        input.end_update();
+        assert_eq!(input.mouse_delta(), (0.0, 0.0));
+        assert_eq!(input.wheel_delta(), (0.0, 0.0));
+        assert_eq!(input.cursor_position(), Some((10.0, 20.0)));
+        input.handle_event(InputEvent::CursorLeft);
+        assert_eq!(input.cursor_position(), None);
+    }
+
+    #[test]
+    fn text_skips_control_characters() {
+        let mut input = Input::new(ActionMap::new());
+        for character in "hi\u{8}!\r".chars() {
+            input.handle_event(InputEvent::Character(character));
+        }
+        assert_eq!(input.text(), "hi!");
+        input.end_update();
+        assert_eq!(input.text(), "");
+    }
+
+    #[test]
+    fn cursor_grab_change_is_taken_once() {
+        let mut input = Input::new(ActionMap::new());
+        input.set_cursor_grabbed(false);
+        assert_eq!(input.take_cursor_grab_change(), None);
+        input.set_cursor_grabbed(true);
+        assert_eq!(input.take_cursor_grab_change(), Some(true));
+        assert_eq!(input.take_cursor_grab_change(), None);
+    }
+
+    #[test]
+    fn parses_action_map() {
+        let actions = ActionMap::parse(
+            "# Movement\n\
+             forward = W, Up\n\
+             \n\
+             fire = MouseLeft, Mouse4 # either button\n\
+             forward = Numpad8\n",
+        ).unwrap();
+        assert_eq!(
+            actions.bindings("forward"),
+            &[
+                Binding::Key(VirtualKeyCode::W),
+                Binding::Key(VirtualKeyCode::Up),
+                Binding::Key(VirtualKeyCode::Numpad8),
+            ]
+        );
+        assert_eq!(
+            actions.bindings("fire"),
+            &[Binding::MouseButton(MouseButton::Left), Binding::MouseButton(MouseButton::Other(4))]
+        );
+        assert!(actions.bindings("jump").is_empty());
+    }
+
+    #[test]
+    fn parse_errors_carry_line_numbers() {
+        let err = ActionMap::parse("forward = W\njump = Spacebar\n").unwrap_err();
+        assert_eq!(err.message, "line 2: Unknown key \"Spacebar\"");
+        let err = ActionMap::parse("forward W\n").unwrap_err();
+        assert!(err.message.starts_with("line 1: "));
+        let err = ActionMap::parse(" = W\n").unwrap_err();
+        assert_eq!(err.message, "line 1: Missing action name");
+    }
+
+    #[test]
+    fn action_released_once_all_bindings_are_up() {
+        let mut actions = ActionMap::new();
+        actions.bind("forward", Binding::Key(VirtualKeyCode::W));
+        actions.bind("forward", Binding::Key(VirtualKeyCode::Up));
+        let mut input = Input::new(actions);
+        press(&mut input, VirtualKeyCode::W);
+        press(&mut input, VirtualKeyCode::Up);
+        assert!(input.was_action_pressed("forward"));
+        input.end_update();
+        release(&mut input, VirtualKeyCode::W);
+        assert!(input.is_action_down("forward"));
+        assert!(!input.was_action_released("forward"));
+        input.end_update();
+        release(&mut input, VirtualKeyCode::Up);
+        assert!(!input.is_action_down("forward"));
+        assert!(input.was_action_released("forward"));
+        assert!(!input.is_action_down("unbound"));
+    }
+}
@@ -0,0 +1,33 @@
+extern crate cgmath;
+extern crate gl;
+pub extern crate glutin;
+extern crate image as piston_image;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+pub mod app;
+pub mod buffer;
+pub mod camera;
+pub mod capabilities;
+pub mod context;
+pub mod debug;
+pub mod diagnostic;
+pub mod fence;
+pub mod framebuffer;
+pub mod gltf;
+pub mod image;
+pub mod input;
+pub mod mesh;
+pub mod mesh_builder;
+pub mod obj;
+pub mod preprocessor;
+pub mod program_cache;
+pub mod query;
+pub mod render_state;
+pub mod scene;
+pub mod shader;
+pub mod shader_program;
+pub mod texture;
+pub mod transform_feedback;
+pub mod vertex_array;
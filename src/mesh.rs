@@ -0,0 +1,513 @@
+//! `MeshData` holds geometry on the CPU where it can be built and checked
+//! without a context; `Mesh` uploads it and draws it with one call.
+//!
+//! Vertex streams are bound to fixed attribute locations, so one shader
+//! works with any mesh that has the streams it reads:
+//!
+//! ```glsl
+//! layout(location = 0) in vec3 position;
+//! layout(location = 1) in vec3 normal;
+//! layout(location = 2) in vec2 uv;
+//! layout(location = 3) in vec4 tangent;
+//! layout(location = 4) in vec4 color;
+//! layout(location = 5) in vec4 joints;
+//! layout(location = 6) in vec4 weights;
+//! ```
+
+use cgmath::{InnerSpace, Vector3};
+use std;
+
+use buffer::{BufferUsageHint, IndexBuffer, VertexBuffer};
+use context::Context;
+use vertex_array::{DrawTy, VertexArray, VertexAttrib, VertexAttribTy};
+
+pub const POSITION_LOCATION: u32 = 0;
+pub const NORMAL_LOCATION: u32 = 1;
+pub const UV_LOCATION: u32 = 2;
+pub const TANGENT_LOCATION: u32 = 3;
+pub const COLOR_LOCATION: u32 = 4;
+pub const JOINTS_LOCATION: u32 = 5;
+pub const WEIGHTS_LOCATION: u32 = 6;
+
+/// A range of elements drawn with one material: indices for indexed meshes,
+/// vertices otherwise.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Submesh {
+    pub first: usize,
+    pub count: usize,
+    /// Index into the material list of whatever produced the mesh.
+    pub material: Option<usize>,
+}
+
+/// Vertex streams and indices. Every stream other than `positions` is either
+/// empty or holds one entry per position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeshData {
+    pub topology: DrawTy,
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    /// Tangent in `xyz`, with the bitangent's handedness, 1 or -1, in `w`.
+    pub tangents: Vec<[f32; 4]>,
+    pub colors: Vec<[f32; 4]>,
+    /// Indices of the four joints that move each vertex, as floats so they
+    /// share the float attribute path; cast them in the shader.
+    pub joints: Vec<[f32; 4]>,
+    /// How much each of `joints` moves the vertex, summing to 1.
+    pub weights: Vec<[f32; 4]>,
+    /// `None` draws the vertices in order.
+    pub indices: Option<Vec<u32>>,
+    /// Empty to draw everything as one submesh without a material.
+    pub submeshes: Vec<Submesh>,
+}
+
+pub struct Mesh {
+    vertex_array: VertexArray,
+    vertex_buffer: VertexBuffer,
+    index_buffer: Option<IndexBuffer>,
+    topology: DrawTy,
+    vertex_count: usize,
+    element_count: usize,
+    submeshes: Vec<Submesh>,
+}
+
+#[derive(Debug)]
+pub struct MeshError {
+    pub message: String,
+}
+
+/// Interleaved layout of the streams a `MeshData` has, in floats.
+struct VertexLayout {
+    components: usize,
+    /// Location, component count and offset of each stream.
+    attribs: Vec<(u32, usize, usize)>,
+}
+
+impl MeshData {
+    pub fn new(topology: DrawTy) -> Self {
+        MeshData {
+            topology,
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            tangents: Vec::new(),
+            colors: Vec::new(),
+            joints: Vec::new(),
+            weights: Vec::new(),
+            indices: None,
+            submeshes: Vec::new(),
+        }
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Indices if indexed, vertices otherwise.
+    pub fn element_count(&self) -> usize {
+        match self.indices {
+            Some(ref indices) => indices.len(),
+            None => self.positions.len(),
+        }
+    }
+
+    /// The vertex index of element `i`.
+    pub fn element(&self, i: usize) -> u32 {
+        match self.indices {
+            Some(ref indices) => indices[i],
+            None => i as u32,
+        }
+    }
+
+    /// The vertex indices of each triangle, with strips unrolled so every
+    /// triangle keeps the strip's winding. Empty for points and lines.
+    pub fn triangles(&self) -> Vec<[u32; 3]> {
+        let count = self.element_count();
+        match self.topology {
+            DrawTy::Triangles => {
+                (0..count / 3)
+                    .map(|i| [self.element(3 * i), self.element(3 * i + 1), self.element(3 * i + 2)])
+                    .collect()
+            },
+            DrawTy::TriangleStrip => {
+                (0..count.saturating_sub(2))
+                    .map(|i| {
+                        let (a, b, c) = (self.element(i), self.element(i + 1), self.element(i + 2));
+                        if i % 2 == 0 { [a, b, c] } else { [b, a, c] }
+                    })
+                    .collect()
+            },
+            DrawTy::Points | DrawTy::Lines => Vec::new(),
+        }
+    }
+
+    /// Minimum and maximum corners of the positions, `None` if there are none.
+    pub fn bounds(&self) -> Option<([f32; 3], [f32; 3])> {
+        let first = *self.positions.first()?;
+        Some(self.positions.iter().fold((first, first), |(mut min, mut max), position| {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(position[axis]);
+                max[axis] = max[axis].max(position[axis]);
+            }
+            (min, max)
+        }))
+    }
+
+    /// Per-vertex normals averaged from the adjoining triangles, weighted by
+    /// area and facing the side the triangles wind counter-clockwise on.
+    /// Vertices outside any triangle get a zero normal.
+    pub fn smooth_normals(&self) -> Vec<[f32; 3]> {
+        let mut normals = vec![Vector3::new(0.0f32, 0.0, 0.0); self.positions.len()];
+        for triangle in self.triangles() {
+            let [a, b, c] = triangle.map(|i| Vector3::from(self.positions[i as usize]));
+            // Twice the area, so larger triangles count for more.
+            let normal = (b - a).cross(c - a);
+            for &i in triangle.iter() {
+                normals[i as usize] += normal;
+            }
+        }
+        normals
+            .into_iter()
+            .map(|normal| if normal.magnitude2() > 0.0 { normal.normalize().into() } else { normal.into() })
+            .collect()
+    }
+
+    /// Checks stream lengths, index ranges and that the elements and every
+    /// submesh form whole primitives.
+    pub fn validate(&self) -> Result<(), MeshError> {
+        let vertex_count = self.positions.len();
+        let streams = [
+            ("normals", self.normals.len()),
+            ("uvs", self.uvs.len()),
+            ("tangents", self.tangents.len()),
+            ("colors", self.colors.len()),
+            ("joints", self.joints.len()),
+            ("weights", self.weights.len()),
+        ];
+        for &(name, len) in streams.iter() {
+            if len != 0 && len != vertex_count {
+                return Err(MeshError {
+                    message: format!("{} {} for {} positions", len, name, vertex_count),
+                });
+            }
+        }
+        if vertex_count > u32::MAX as usize {
+            return Err(MeshError {
+                message: format!("{} vertices exceed the range of 32 bit indices", vertex_count),
+            });
+        }
+        if let Some(ref indices) = self.indices {
+            if let Some((i, &index)) = indices.iter().enumerate().find(|&(_, &index)| index as usize >= vertex_count) {
+                return Err(MeshError {
+                    message: format!("Index {} at {} is out of range for {} vertices", index, i, vertex_count),
+                });
+            }
+        }
+        let element_count = self.element_count();
+        let primitive_size = match self.topology {
+            DrawTy::Points | DrawTy::TriangleStrip => 1,
+            DrawTy::Lines => 2,
+            DrawTy::Triangles => 3,
+        };
+        if !element_count.is_multiple_of(primitive_size) {
+            return Err(MeshError {
+                message: format!("{} elements do not form whole {:?}", element_count, self.topology),
+            });
+        }
+        for (i, submesh) in self.submeshes.iter().enumerate() {
+            if submesh.first + submesh.count > element_count {
+                return Err(MeshError {
+                    message: format!(
+                        "Submesh {} covers elements {}..{} of {}",
+                        i,
+                        submesh.first,
+                        submesh.first + submesh.count,
+                        element_count,
+                    ),
+                });
+            }
+            if !submesh.first.is_multiple_of(primitive_size) || !submesh.count.is_multiple_of(primitive_size) {
+                return Err(MeshError {
+                    message: format!("Submesh {} does not cover whole {:?}", i, self.topology),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn vertex_layout(&self) -> VertexLayout {
+        let streams = [
+            (POSITION_LOCATION, 3, true),
+            (NORMAL_LOCATION, 3, !self.normals.is_empty()),
+            (UV_LOCATION, 2, !self.uvs.is_empty()),
+            (TANGENT_LOCATION, 4, !self.tangents.is_empty()),
+            (COLOR_LOCATION, 4, !self.colors.is_empty()),
+            (JOINTS_LOCATION, 4, !self.joints.is_empty()),
+            (WEIGHTS_LOCATION, 4, !self.weights.is_empty()),
+        ];
+        let mut layout = VertexLayout {
+            components: 0,
+            attribs: Vec::new(),
+        };
+        for &(location, components, is_present) in streams.iter() {
+            if is_present {
+                layout.attribs.push((location, components, layout.components));
+                layout.components += components;
+            }
+        }
+        layout
+    }
+
+    /// The streams interleaved in the order of `vertex_layout`. Assumes the
+    /// data is valid.
+    fn interleaved_vertices(&self, layout: &VertexLayout) -> Vec<f32> {
+        let mut vertices = Vec::with_capacity(self.positions.len() * layout.components);
+        for (i, position) in self.positions.iter().enumerate() {
+            vertices.extend_from_slice(position);
+            if let Some(normal) = self.normals.get(i) {
+                vertices.extend_from_slice(normal);
+            }
+            if let Some(uv) = self.uvs.get(i) {
+                vertices.extend_from_slice(uv);
+            }
+            if let Some(tangent) = self.tangents.get(i) {
+                vertices.extend_from_slice(tangent);
+            }
+            if let Some(color) = self.colors.get(i) {
+                vertices.extend_from_slice(color);
+            }
+            if let Some(joints) = self.joints.get(i) {
+                vertices.extend_from_slice(joints);
+            }
+            if let Some(weights) = self.weights.get(i) {
+                vertices.extend_from_slice(weights);
+            }
+        }
+        vertices
+    }
+}
+
+impl Mesh {
+    /// Validates and uploads `data`.
+    pub fn new(context: &Context, data: &MeshData) -> Result<Mesh, MeshError> {
+        data.validate()?;
+        let layout = data.vertex_layout();
+        let mut vertex_array = VertexArray::new(context).map_err(|err| MeshError { message: err.message })?;
+        let mut vertex_buffer = VertexBuffer::new(context).map_err(|err| MeshError { message: err.message })?;
+        vertex_buffer
+            .bind()
+            .upload(&data.interleaved_vertices(&layout), BufferUsageHint::StaticDraw);
+        {
+            let mut bound_vertex_array = vertex_array.bind(vertex_buffer.bind());
+            for &(location, components, offset) in layout.attribs.iter() {
+                let ty = match components {
+                    1 => VertexAttribTy::Float,
+                    2 => VertexAttribTy::FloatVec2,
+                    3 => VertexAttribTy::FloatVec3,
+                    _ => VertexAttribTy::FloatVec4,
+                };
+                let attrib = VertexAttrib {
+                    ty,
+                    offset: offset * std::mem::size_of::<f32>(),
+                    normalized: false,
+                };
+                bound_vertex_array
+                    .set_vertex_attrib(location, layout.components * std::mem::size_of::<f32>(), &attrib)
+                    .map_err(|err| MeshError { message: err.message })?;
+            }
+        }
+        let index_buffer = match data.indices {
+            Some(ref indices) => {
+                let mut index_buffer = IndexBuffer::new(context).map_err(|err| MeshError { message: err.message })?;
+                index_buffer.bind().upload(indices, BufferUsageHint::StaticDraw);
+                Some(index_buffer)
+            },
+            None => None,
+        };
+        Ok(Mesh {
+            vertex_array,
+            vertex_buffer,
+            index_buffer,
+            topology: data.topology,
+            vertex_count: data.vertex_count(),
+            element_count: data.element_count(),
+            submeshes: data.submeshes.clone(),
+        })
+    }
+
+    pub fn set_label(&mut self, label: &str) {
+        self.vertex_array.set_label(label);
+        self.vertex_buffer.set_label(&format!("{} vertices", label));
+        if let Some(ref mut index_buffer) = self.index_buffer {
+            index_buffer.set_label(&format!("{} indices", label));
+        }
+    }
+
+    pub fn topology(&self) -> DrawTy {
+        self.topology
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertex_count
+    }
+
+    /// Indices if indexed, vertices otherwise.
+    pub fn element_count(&self) -> usize {
+        self.element_count
+    }
+
+    pub fn submeshes(&self) -> &[Submesh] {
+        &self.submeshes
+    }
+
+    /// Draws every element with whatever program and textures are bound.
+    pub fn draw(&mut self) {
+        let count = self.element_count;
+        self.draw_range(0, count);
+    }
+
+    pub fn draw_submesh(&mut self, index: usize) -> Result<(), MeshError> {
+        let (first, count) = match self.submeshes.get(index) {
+            Some(submesh) => (submesh.first, submesh.count),
+            None => {
+                return Err(MeshError {
+                    message: format!("Submesh {} of {} does not exist", index, self.submeshes.len()),
+                });
+            },
+        };
+        self.draw_range(first, count);
+        Ok(())
+    }
+
+    /// Draws `instance_count` copies of every element, distinguished by
+    /// `gl_InstanceID`.
+    pub fn draw_instanced(&mut self, instance_count: usize) {
+        let mut bound_vertex_array = self.vertex_array.bind(self.vertex_buffer.bind());
+        match self.index_buffer {
+            Some(ref mut index_buffer) => {
+                bound_vertex_array.draw_elements_instanced(
+                    self.topology,
+                    index_buffer.bind(),
+                    self.element_count,
+                    instance_count,
+                );
+            },
+            None => bound_vertex_array.draw_arrays_instanced(self.topology, 0, self.element_count, instance_count),
+        }
+    }
+
+    fn draw_range(&mut self, first: usize, count: usize) {
+        let mut bound_vertex_array = self.vertex_array.bind(self.vertex_buffer.bind());
+        match self.index_buffer {
+            Some(ref mut index_buffer) => {
+                bound_vertex_array.draw_elements_range(self.topology, index_buffer.bind(), first, count);
+            },
+            None => bound_vertex_array.draw_arrays(self.topology, first, count),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad() -> MeshData {
+        MeshData {
+            positions: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+            uvs: vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+            indices: Some(vec![0, 1, 2, 2, 3, 0]),
+            ..MeshData::new(DrawTy::Triangles)
+        }
+    }
+
+    #[test]
+    fn valid_quad() {
+        let data = quad();
+        data.validate().unwrap();
+        assert_eq!(data.vertex_count(), 4);
+        assert_eq!(data.element_count(), 6);
+        assert_eq!(data.triangles(), vec![[0, 1, 2], [2, 3, 0]]);
+        assert_eq!(data.bounds(), Some(([0.0, 0.0, 0.0], [1.0, 1.0, 0.0])));
+    }
+
+    #[test]
+    fn rejects_mismatched_streams() {
+        let mut data = quad();
+        data.normals = vec![[0.0, 0.0, 1.0]; 3];
+        assert_eq!(data.validate().unwrap_err().message, "3 normals for 4 positions");
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        let mut data = quad();
+        data.indices = Some(vec![0, 1, 4]);
+        assert_eq!(data.validate().unwrap_err().message, "Index 4 at 2 is out of range for 4 vertices");
+    }
+
+    #[test]
+    fn rejects_partial_primitives() {
+        let mut data = quad();
+        data.indices = Some(vec![0, 1, 2, 2]);
+        assert!(data.validate().is_err());
+        data.topology = DrawTy::Lines;
+        data.validate().unwrap();
+        data.topology = DrawTy::TriangleStrip;
+        data.validate().unwrap();
+    }
+
+    #[test]
+    fn rejects_bad_submeshes() {
+        let mut data = quad();
+        data.submeshes = vec![
+            Submesh {
+                first: 0,
+                count: 3,
+                material: Some(0),
+            },
+            Submesh {
+                first: 3,
+                count: 3,
+                material: Some(1),
+            },
+        ];
+        data.validate().unwrap();
+        data.submeshes[1].count = 6;
+        assert_eq!(data.validate().unwrap_err().message, "Submesh 1 covers elements 3..9 of 6");
+        data.submeshes[1].first = 2;
+        data.submeshes[1].count = 3;
+        assert!(data.validate().is_err());
+    }
+
+    #[test]
+    fn strips_keep_winding() {
+        let data = MeshData {
+            positions: vec![[0.0, 1.0, 0.0], [0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [1.0, 0.0, 0.0]],
+            ..MeshData::new(DrawTy::TriangleStrip)
+        };
+        data.validate().unwrap();
+        assert_eq!(data.triangles(), vec![[0, 1, 2], [2, 1, 3]]);
+    }
+
+    #[test]
+    fn interleaves_present_streams() {
+        let mut data = quad();
+        data.colors = vec![[1.0, 0.5, 0.25, 1.0]; 4];
+        let layout = data.vertex_layout();
+        assert_eq!(layout.components, 3 + 2 + 4);
+        assert_eq!(
+            layout.attribs,
+            vec![(POSITION_LOCATION, 3, 0), (UV_LOCATION, 2, 3), (COLOR_LOCATION, 4, 5)]
+        );
+        let vertices = data.interleaved_vertices(&layout);
+        assert_eq!(vertices.len(), 4 * 9);
+        assert_eq!(&vertices[9..18], &[1.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.5, 0.25, 1.0]);
+    }
+
+    #[test]
+    fn empty_mesh_is_valid() {
+        let data = MeshData::new(DrawTy::Triangles);
+        data.validate().unwrap();
+        assert_eq!(data.bounds(), None);
+        assert!(data.triangles().is_empty());
+    }
+}
@@ -0,0 +1,600 @@
+//! Procedural shapes. `MeshBuilder` collects indexed triangles with normals,
+//! tangents and UVs, and its `add_*` generators append common shapes
+//! centered on the origin, wound counter-clockwise when seen from outside.
+//!
+//! UVs follow `Image` in putting `v = 0` at the bottom, and tangents point
+//! along `+u` with the bitangent, `cross(normal, tangent) * w`, along `+v`.
+//! Round shapes start their `u` seam on the +Z side and run around the Y
+//! axis.
+
+use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, SquareMatrix, Vector3};
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use mesh::MeshData;
+use vertex_array::DrawTy;
+
+pub struct MeshBuilder {
+    data: MeshData,
+    transform: Matrix4<f32>,
+    normal_matrix: Matrix3<f32>,
+}
+
+/// A point of the outline that `add_lathe` spins around the Y axis.
+struct ProfilePoint {
+    radius: f32,
+    y: f32,
+    /// Outward normal as its radial and Y parts.
+    normal: [f32; 2],
+    v: f32,
+}
+
+impl MeshBuilder {
+    pub fn new() -> Self {
+        MeshBuilder {
+            data: MeshData {
+                indices: Some(Vec::new()),
+                ..MeshData::new(DrawTy::Triangles)
+            },
+            transform: Matrix4::identity(),
+            normal_matrix: Matrix3::identity(),
+        }
+    }
+
+    /// Moves vertices added from now on, e.g. to put several shapes into one
+    /// mesh. The transform must not mirror, or the winding would flip.
+    pub fn set_transform(&mut self, transform: Matrix4<f32>) -> &mut Self {
+        let linear = Matrix3::from_cols(transform.x.truncate(), transform.y.truncate(), transform.z.truncate());
+        self.transform = transform;
+        self.normal_matrix = linear.invert().map_or(linear, |inverse| inverse.transpose());
+        self
+    }
+
+    /// Adds a vertex, returning its index for `triangle`.
+    pub fn vertex(&mut self, position: [f32; 3], normal: [f32; 3], tangent: [f32; 4], uv: [f32; 2]) -> u32 {
+        let linear = Matrix3::from_cols(
+            self.transform.x.truncate(),
+            self.transform.y.truncate(),
+            self.transform.z.truncate(),
+        );
+        let position = (self.transform * Vector3::from(position).extend(1.0)).truncate();
+        let normal = (self.normal_matrix * Vector3::from(normal)).normalize();
+        let tangent_xyz = (linear * Vector3::new(tangent[0], tangent[1], tangent[2])).normalize();
+        self.data.positions.push(position.into());
+        self.data.normals.push(normal.into());
+        self.data.tangents.push(tangent_xyz.extend(tangent[3]).into());
+        self.data.uvs.push(uv);
+        (self.data.positions.len() - 1) as u32
+    }
+
+    /// Adds a triangle of vertices returned by `vertex`, counter-clockwise
+    /// as seen from its front.
+    pub fn triangle(&mut self, a: u32, b: u32, c: u32) -> &mut Self {
+        self.data.indices.as_mut().unwrap().extend_from_slice(&[a, b, c]);
+        self
+    }
+
+    /// An indexed triangle list of everything added so far.
+    pub fn build(&self) -> MeshData {
+        self.data.clone()
+    }
+
+    /// A `size[0]` by `size[1]` rectangle in the XY plane facing +Z, split
+    /// into a grid of `segments` cells.
+    pub fn add_plane(&mut self, size: [f32; 2], segments: [u32; 2]) -> &mut Self {
+        self.add_grid(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(size[0], 0.0, 0.0),
+            Vector3::new(0.0, size[1], 0.0),
+            segments,
+        )
+    }
+
+    /// A cube with edges `size` long, each face split into `segments` by
+    /// `segments` cells with UVs covering the whole texture.
+    pub fn add_cube(&mut self, size: f32, segments: u32) -> &mut Self {
+        let x = Vector3::new(size, 0.0, 0.0);
+        let y = Vector3::new(0.0, size, 0.0);
+        let z = Vector3::new(0.0, 0.0, size);
+        // Each face's U and V axes, crossing to the face's outward normal.
+        let faces = [(-z, y), (z, y), (x, -z), (x, z), (x, y), (-x, y)];
+        for &(u, v) in faces.iter() {
+            let center = u.cross(v).normalize() * (size / 2.0);
+            self.add_grid(center, u, v, [segments, segments]);
+        }
+        self
+    }
+
+    /// A sphere of `segments` around the Y axis and `rings` from pole to
+    /// pole, with UVs as in an equirectangular map.
+    pub fn add_uv_sphere(&mut self, radius: f32, segments: u32, rings: u32) -> &mut Self {
+        let rings = rings.max(2);
+        let profile: Vec<ProfilePoint> = (0..=rings)
+            .map(|ring| {
+                let angle = PI * (ring as f32 / rings as f32 - 0.5);
+                ProfilePoint {
+                    radius: if ring == 0 || ring == rings { 0.0 } else { radius * angle.cos() },
+                    y: radius * angle.sin(),
+                    normal: [angle.cos(), angle.sin()],
+                    v: ring as f32 / rings as f32,
+                }
+            })
+            .collect();
+        self.add_lathe(&profile, segments)
+    }
+
+    /// A sphere made by splitting each face of an icosahedron into four
+    /// `subdivisions` times, giving evenly sized triangles. UVs are mapped
+    /// like `add_uv_sphere`'s, with `u` going past 1 on triangles that cross
+    /// the seam so the texture repeats across it.
+    pub fn add_icosphere(&mut self, radius: f32, subdivisions: u32) -> &mut Self {
+        let (points, faces) = icosphere_points(subdivisions);
+        // Vertices on either side of the seam, keyed by point and whether
+        // `u` was moved past 1.
+        let mut vertices: HashMap<(usize, bool), u32> = HashMap::new();
+        for face in faces {
+            // Poles have no `u` of their own; they are left as `None`.
+            let mut us = [None; 3];
+            for (u, &point) in us.iter_mut().zip(face.iter()) {
+                let normal = points[point];
+                if normal.x != 0.0 || normal.z != 0.0 {
+                    *u = Some((normal.x.atan2(normal.z) / (2.0 * PI)).rem_euclid(1.0));
+                }
+            }
+            let max_u = us.iter().filter_map(|&u| u).fold(0.0, f32::max);
+            let min_u = us.iter().filter_map(|&u| u).fold(1.0, f32::min);
+            let crosses_seam = max_u - min_u > 0.5;
+            // Each `u` moved past 1 if needed, and whether it was.
+            let unwrapped_us: Vec<Option<(f32, bool)>> = us
+                .iter()
+                .map(|&u| u.map(|u| if crosses_seam && u < 0.5 { (u + 1.0, true) } else { (u, false) }))
+                .collect();
+            let mut indices = [0; 3];
+            for (i, &point) in face.iter().enumerate() {
+                let normal = points[point];
+                let v = (-normal.y).clamp(-1.0, 1.0).acos() / PI;
+                indices[i] = match unwrapped_us[i] {
+                    // Every triangle gets its own pole vertex, under the
+                    // middle of the opposite edge.
+                    None => {
+                        let others: Vec<f32> = unwrapped_us.iter().filter_map(|&u| u.map(|(u, _)| u)).collect();
+                        let u = others.iter().sum::<f32>() / others.len() as f32;
+                        self.sphere_vertex(radius, normal, [u, v])
+                    },
+                    Some((u, wrapped)) => {
+                        match vertices.get(&(point, wrapped)) {
+                            Some(&index) => index,
+                            None => {
+                                let index = self.sphere_vertex(radius, normal, [u, v]);
+                                vertices.insert((point, wrapped), index);
+                                index
+                            },
+                        }
+                    },
+                };
+            }
+            self.triangle(indices[0], indices[1], indices[2]);
+        }
+        self
+    }
+
+    /// A tube of `segments` around the Y axis and `height_segments`
+    /// along it, closed by a disc at each end.
+    pub fn add_cylinder(&mut self, radius: f32, height: f32, segments: u32, height_segments: u32) -> &mut Self {
+        let height_segments = height_segments.max(1);
+        let profile: Vec<ProfilePoint> = (0..=height_segments)
+            .map(|row| {
+                let v = row as f32 / height_segments as f32;
+                ProfilePoint {
+                    radius,
+                    y: height * (v - 0.5),
+                    normal: [1.0, 0.0],
+                    v,
+                }
+            })
+            .collect();
+        self.add_lathe(&profile, segments);
+        self.add_disc(-height / 2.0, radius, segments, false);
+        self.add_disc(height / 2.0, radius, segments, true)
+    }
+
+    /// A cone with its apex up, closed by a disc at the bottom.
+    pub fn add_cone(&mut self, radius: f32, height: f32, segments: u32, height_segments: u32) -> &mut Self {
+        let height_segments = height_segments.max(1);
+        let slant = (height * height + radius * radius).sqrt();
+        let profile: Vec<ProfilePoint> = (0..=height_segments)
+            .map(|row| {
+                let v = row as f32 / height_segments as f32;
+                ProfilePoint {
+                    radius: radius * (1.0 - v),
+                    y: height * (v - 0.5),
+                    normal: [height / slant, radius / slant],
+                    v,
+                }
+            })
+            .collect();
+        self.add_lathe(&profile, segments);
+        self.add_disc(-height / 2.0, radius, segments, false)
+    }
+
+    /// A ring around the Y axis with a tube of `minor_radius` at
+    /// `major_radius` from the center, of `segments` around the ring and
+    /// `tube_segments` around the tube.
+    pub fn add_torus(
+        &mut self,
+        major_radius: f32,
+        minor_radius: f32,
+        segments: u32,
+        tube_segments: u32,
+    ) -> &mut Self {
+        let tube_segments = tube_segments.max(3);
+        let profile: Vec<ProfilePoint> = (0..=tube_segments)
+            .map(|row| {
+                let v = row as f32 / tube_segments as f32;
+                let angle = 2.0 * PI * v;
+                ProfilePoint {
+                    radius: major_radius + minor_radius * angle.cos(),
+                    y: minor_radius * angle.sin(),
+                    normal: [angle.cos(), angle.sin()],
+                    v,
+                }
+            })
+            .collect();
+        self.add_lathe(&profile, segments)
+    }
+
+    /// A cylinder `height` long between the centers of two hemispheres, each
+    /// of `rings` from pole to equator. `v` runs along the surface, so the
+    /// texture is not stretched more on the caps than on the sides.
+    pub fn add_capsule(
+        &mut self,
+        radius: f32,
+        height: f32,
+        segments: u32,
+        rings: u32,
+        height_segments: u32,
+    ) -> &mut Self {
+        let rings = rings.max(1);
+        let height_segments = height_segments.max(1);
+        let quarter_arc = radius * PI / 2.0;
+        let length = 2.0 * quarter_arc + height;
+        let mut profile = Vec::new();
+        let hemisphere_point = |ring: u32, is_top: bool| {
+            let angle = PI / 2.0 * (ring as f32 / rings as f32 - if is_top { 0.0 } else { 1.0 });
+            let is_pole = (ring == 0 && !is_top) || (ring == rings && is_top);
+            let center_y = if is_top { height / 2.0 } else { -height / 2.0 };
+            ProfilePoint {
+                radius: if is_pole { 0.0 } else { radius * angle.cos() },
+                y: center_y + radius * angle.sin(),
+                normal: [angle.cos(), angle.sin()],
+                v: (radius * (angle + PI / 2.0) + if is_top { height } else { 0.0 }) / length,
+            }
+        };
+        profile.extend((0..=rings).map(|ring| hemisphere_point(ring, false)));
+        profile.extend((1..height_segments).map(|row| {
+            let t = row as f32 / height_segments as f32;
+            ProfilePoint {
+                radius,
+                y: height * (t - 0.5),
+                normal: [1.0, 0.0],
+                v: (quarter_arc + height * t) / length,
+            }
+        }));
+        profile.extend((0..=rings).map(|ring| hemisphere_point(ring, true)));
+        self.add_lathe(&profile, segments)
+    }
+
+    /// A parallelogram spanned by `u` and `v` around `center`, facing
+    /// `cross(u, v)`.
+    fn add_grid(
+        &mut self,
+        center: Vector3<f32>,
+        u: Vector3<f32>,
+        v: Vector3<f32>,
+        segments: [u32; 2],
+    ) -> &mut Self {
+        let (columns, rows) = (segments[0].max(1), segments[1].max(1));
+        let normal = u.cross(v).normalize();
+        let tangent = u.normalize().extend(1.0);
+        let first = self.data.positions.len() as u32;
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let uv = [column as f32 / columns as f32, row as f32 / rows as f32];
+                let position = center + u * (uv[0] - 0.5) + v * (uv[1] - 0.5);
+                self.vertex(position.into(), normal.into(), tangent.into(), uv);
+            }
+        }
+        for row in 0..rows {
+            for column in 0..columns {
+                let a = first + row * (columns + 1) + column;
+                let b = a + columns + 1;
+                self.triangle(a, a + 1, b + 1);
+                self.triangle(a, b + 1, b);
+            }
+        }
+        self
+    }
+
+    /// Spins `profile`, listed from bottom to top, around the Y axis in
+    /// `segments` steps. Points on the axis would make zero-area triangles,
+    /// which are left out.
+    fn add_lathe(&mut self, profile: &[ProfilePoint], segments: u32) -> &mut Self {
+        let segments = segments.max(3);
+        let first = self.data.positions.len() as u32;
+        for point in profile.iter() {
+            for segment in 0..=segments {
+                let u = segment as f32 / segments as f32;
+                let (sin, cos) = (2.0 * PI * u).sin_cos();
+                self.vertex(
+                    [point.radius * sin, point.y, point.radius * cos],
+                    [point.normal[0] * sin, point.normal[1], point.normal[0] * cos],
+                    [cos, 0.0, -sin, 1.0],
+                    [u, point.v],
+                );
+            }
+        }
+        for (row, pair) in profile.windows(2).enumerate() {
+            for segment in 0..segments {
+                let a = first + row as u32 * (segments + 1) + segment;
+                let b = a + segments + 1;
+                if pair[0].radius != 0.0 {
+                    self.triangle(a, a + 1, b + 1);
+                }
+                if pair[1].radius != 0.0 {
+                    self.triangle(a, b + 1, b);
+                }
+            }
+        }
+        self
+    }
+
+    /// A disc at height `y` facing up or down, with the texture laid flat
+    /// over it.
+    fn add_disc(&mut self, y: f32, radius: f32, segments: u32, is_facing_up: bool) -> &mut Self {
+        let segments = segments.max(3);
+        let (normal_y, v_sign) = if is_facing_up { (1.0, -1.0) } else { (-1.0, 1.0) };
+        let normal = [0.0, normal_y, 0.0];
+        let tangent = [1.0, 0.0, 0.0, 1.0];
+        let center = self.vertex([0.0, y, 0.0], normal, tangent, [0.5, 0.5]);
+        for segment in 0..segments {
+            let (sin, cos) = (2.0 * PI * segment as f32 / segments as f32).sin_cos();
+            let uv = [0.5 + sin / 2.0, 0.5 + v_sign * cos / 2.0];
+            self.vertex([radius * sin, y, radius * cos], normal, tangent, uv);
+        }
+        for segment in 0..segments {
+            let a = center + 1 + segment;
+            let b = center + 1 + (segment + 1) % segments;
+            if is_facing_up {
+                self.triangle(center, a, b);
+            } else {
+                self.triangle(center, b, a);
+            }
+        }
+        self
+    }
+
+    /// A point on a sphere, with the tangent following `u` around the Y axis.
+    fn sphere_vertex(&mut self, radius: f32, normal: Vector3<f32>, uv: [f32; 2]) -> u32 {
+        let (sin, cos) = (2.0 * PI * uv[0]).sin_cos();
+        self.vertex((normal * radius).into(), normal.into(), [cos, 0.0, -sin, 1.0], uv)
+    }
+}
+
+impl Default for MeshBuilder {
+    fn default() -> Self {
+        MeshBuilder::new()
+    }
+}
+
+/// Unit vectors and outward-facing triangles of a subdivided icosahedron
+/// with a point at each pole.
+fn icosphere_points(subdivisions: u32) -> (Vec<Vector3<f32>>, Vec<[usize; 3]>) {
+    // The top pole, two rings of five offset by a tenth of a turn, then the
+    // bottom pole.
+    let ring_y = 1.0 / 5.0f32.sqrt();
+    let ring_radius = 2.0 * ring_y;
+    let mut points = vec![Vector3::new(0.0, 1.0, 0.0)];
+    for &(y, offset) in [(ring_y, 0.0), (-ring_y, 0.5)].iter() {
+        for i in 0..5 {
+            let angle = 2.0 * PI * (i as f32 + offset) / 5.0;
+            points.push(Vector3::new(ring_radius * angle.sin(), y, ring_radius * angle.cos()));
+        }
+    }
+    points.push(Vector3::new(0.0, -1.0, 0.0));
+    let mut faces = Vec::with_capacity(20 << (2 * subdivisions));
+    for i in 0..5 {
+        let (upper, next_upper) = (1 + i, 1 + (i + 1) % 5);
+        let (lower, next_lower) = (6 + i, 6 + (i + 1) % 5);
+        faces.push([0, upper, next_upper]);
+        faces.push([upper, lower, next_upper]);
+        faces.push([next_upper, lower, next_lower]);
+        faces.push([11, next_lower, lower]);
+    }
+    for _ in 0..subdivisions {
+        let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut next_faces = Vec::with_capacity(faces.len() * 4);
+        for face in faces.iter() {
+            let mut middle = [0; 3];
+            for i in 0..3 {
+                let (a, b) = (face[i], face[(i + 1) % 3]);
+                middle[i] = *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    points.push((points[a] + points[b]).normalize());
+                    points.len() - 1
+                });
+            }
+            next_faces.push([face[0], middle[0], middle[2]]);
+            next_faces.push([face[1], middle[1], middle[0]]);
+            next_faces.push([face[2], middle[2], middle[1]]);
+            next_faces.push(middle);
+        }
+        faces = next_faces;
+    }
+    (points, faces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn vector(array: &[f32]) -> Vector3<f32> {
+        Vector3::new(array[0], array[1], array[2])
+    }
+
+    /// Checks what every generator promises: unit normals with unit tangents
+    /// at right angles to them, triangles wound to face along their vertex
+    /// normals, and tangent frames that follow the UVs.
+    fn check_shape(data: &MeshData) {
+        data.validate().unwrap();
+        assert_eq!(data.normals.len(), data.vertex_count());
+        assert_eq!(data.tangents.len(), data.vertex_count());
+        assert_eq!(data.uvs.len(), data.vertex_count());
+        for (normal, tangent) in data.normals.iter().zip(data.tangents.iter()) {
+            let (normal, tangent_xyz) = (vector(normal), vector(tangent));
+            assert!((normal.magnitude() - 1.0).abs() < EPSILON, "normal {:?}", normal);
+            assert!((tangent_xyz.magnitude() - 1.0).abs() < EPSILON, "tangent {:?}", tangent_xyz);
+            assert!(normal.dot(tangent_xyz).abs() < EPSILON, "{:?} is not across {:?}", tangent_xyz, normal);
+            assert_eq!(tangent[3].abs(), 1.0);
+        }
+        for triangle in data.triangles() {
+            let corners: Vec<usize> = triangle.iter().map(|&i| i as usize).collect();
+            let [a, b, c] = [0, 1, 2].map(|i| vector(&data.positions[corners[i]]));
+            let face_normal = (b - a).cross(c - a);
+            assert!(face_normal.magnitude() > 1e-8, "{:?} has no area", triangle);
+            let mut normal = Vector3::new(0.0, 0.0, 0.0);
+            let mut tangent = Vector3::new(0.0, 0.0, 0.0);
+            let mut bitangent = Vector3::new(0.0, 0.0, 0.0);
+            for &i in corners.iter() {
+                let vertex_normal = vector(&data.normals[i]);
+                let vertex_tangent = vector(&data.tangents[i]);
+                normal += vertex_normal;
+                tangent += vertex_tangent;
+                bitangent += vertex_normal.cross(vertex_tangent) * data.tangents[i][3];
+            }
+            assert!(face_normal.dot(normal) > 0.0, "{:?} is wound the wrong way", triangle);
+            // The directions in which U and V grow across the triangle.
+            let [uv_a, uv_b, uv_c] = [0, 1, 2].map(|i| data.uvs[corners[i]]);
+            let (du1, dv1) = (uv_b[0] - uv_a[0], uv_b[1] - uv_a[1]);
+            let (du2, dv2) = (uv_c[0] - uv_a[0], uv_c[1] - uv_a[1]);
+            let determinant = du1 * dv2 - du2 * dv1;
+            assert!(determinant > 0.0, "{:?} has mirrored or degenerate UVs", triangle);
+            let u_direction = (b - a) * dv2 - (c - a) * dv1;
+            let v_direction = (c - a) * du1 - (b - a) * du2;
+            assert!(u_direction.dot(tangent) > 0.0, "{:?} has tangents against U", triangle);
+            assert!(v_direction.dot(bitangent) > 0.0, "{:?} has bitangents against V", triangle);
+        }
+    }
+
+    fn check_radius(data: &MeshData, radius: f32) {
+        for position in data.positions.iter() {
+            assert!((vector(position).magnitude() - radius).abs() < EPSILON, "{:?}", position);
+        }
+    }
+
+    #[test]
+    fn plane() {
+        let data = MeshBuilder::new().add_plane([2.0, 1.0], [2, 3]).build();
+        check_shape(&data);
+        assert_eq!(data.vertex_count(), 3 * 4);
+        assert_eq!(data.triangles().len(), 2 * 2 * 3);
+        assert_eq!(data.bounds(), Some(([-1.0, -0.5, 0.0], [1.0, 0.5, 0.0])));
+        assert!(data.normals.iter().all(|&normal| normal == [0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn cube() {
+        let data = MeshBuilder::new().add_cube(2.0, 2).build();
+        check_shape(&data);
+        assert_eq!(data.vertex_count(), 6 * 3 * 3);
+        assert_eq!(data.triangles().len(), 6 * 2 * 2 * 2);
+        assert_eq!(data.bounds(), Some(([-1.0, -1.0, -1.0], [1.0, 1.0, 1.0])));
+    }
+
+    #[test]
+    fn uv_sphere() {
+        let data = MeshBuilder::new().add_uv_sphere(2.0, 8, 4).build();
+        check_shape(&data);
+        check_radius(&data, 2.0);
+        assert_eq!(data.vertex_count(), 9 * 5);
+        // The rings next to the poles have one triangle per segment.
+        assert_eq!(data.triangles().len(), 2 * 8 * 4 - 2 * 8);
+    }
+
+    #[test]
+    fn icosphere() {
+        for subdivisions in 0..3 {
+            let data = MeshBuilder::new().add_icosphere(0.5, subdivisions).build();
+            check_shape(&data);
+            check_radius(&data, 0.5);
+            assert_eq!(data.triangles().len(), 20 << (2 * subdivisions));
+            let mut points: Vec<[u32; 3]> = data
+                .positions
+                .iter()
+                .map(|position| [0, 1, 2].map(|i| (position[i] * 1e4).round() as i32 as u32))
+                .collect();
+            points.sort();
+            points.dedup();
+            // Seams and poles split vertices, but not points.
+            assert_eq!(points.len(), (10 << (2 * subdivisions)) + 2);
+        }
+    }
+
+    #[test]
+    fn cylinder() {
+        let data = MeshBuilder::new().add_cylinder(1.0, 3.0, 8, 2).build();
+        check_shape(&data);
+        assert_eq!(data.vertex_count(), 9 * 3 + 2 * (8 + 1));
+        assert_eq!(data.triangles().len(), 2 * 8 * 2 + 2 * 8);
+        let (min, max) = data.bounds().unwrap();
+        assert!((min[1] + 1.5).abs() < EPSILON && (max[1] - 1.5).abs() < EPSILON);
+    }
+
+    #[test]
+    fn cone() {
+        let data = MeshBuilder::new().add_cone(1.0, 2.0, 8, 2).build();
+        check_shape(&data);
+        assert_eq!(data.vertex_count(), 9 * 3 + 8 + 1);
+        // The row at the apex has one triangle per segment.
+        assert_eq!(data.triangles().len(), 2 * 8 * 2 - 8 + 8);
+    }
+
+    #[test]
+    fn torus() {
+        let data = MeshBuilder::new().add_torus(2.0, 0.5, 8, 8).build();
+        check_shape(&data);
+        assert_eq!(data.vertex_count(), 9 * 9);
+        assert_eq!(data.triangles().len(), 2 * 8 * 8);
+        let (min, max) = data.bounds().unwrap();
+        assert!((max[0] - 2.5).abs() < EPSILON && (max[1] - 0.5).abs() < EPSILON);
+        assert!((min[1] + 0.5).abs() < EPSILON);
+    }
+
+    #[test]
+    fn capsule() {
+        let data = MeshBuilder::new().add_capsule(0.5, 1.0, 8, 2, 1).build();
+        check_shape(&data);
+        // Two hemispheres of three rows each; one segment adds no rows.
+        assert_eq!(data.vertex_count(), 9 * 6);
+        assert_eq!(data.triangles().len(), 2 * 8 * 5 - 2 * 8);
+        let (min, max) = data.bounds().unwrap();
+        assert!((min[1] + 1.0).abs() < EPSILON && (max[1] - 1.0).abs() < EPSILON);
+        let data = MeshBuilder::new().add_capsule(0.5, 1.0, 8, 2, 3).build();
+        check_shape(&data);
+        assert_eq!(data.vertex_count(), 9 * 8);
+    }
+
+    #[test]
+    fn transform_applies_to_later_vertices() {
+        let stretch = Matrix4::from_translation(Vector3::new(3.0, 0.0, 0.0))
+            * Matrix4::from_nonuniform_scale(2.0, 1.0, 1.0);
+        let data = MeshBuilder::new()
+            .add_cube(1.0, 1)
+            .set_transform(stretch)
+            .add_uv_sphere(1.0, 8, 4)
+            .build();
+        check_shape(&data);
+        assert_eq!(data.vertex_count(), 6 * 4 + 9 * 5);
+        assert_eq!(data.bounds(), Some(([-0.5, -1.0, -1.0], [5.0, 1.0, 1.0])));
+    }
+}
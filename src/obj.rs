@@ -0,0 +1,516 @@
+//! Wavefront OBJ models with MTL materials.
+//!
+//! Polygons are triangulated as fans and every distinct combination of
+//! position, texture coordinate and normal becomes one indexed vertex. Faces
+//! are grouped into one submesh per material, in the order the materials are
+//! first used. Vertices without a normal get a smooth one computed from the
+//! faces around them.
+//!
+//! Exporters often reference materials without shipping them, so a missing
+//! MTL file is skipped and an unknown material gets `Material::new`'s
+//! defaults.
+
+use std;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::str::SplitWhitespace;
+
+use mesh::{MeshData, Submesh};
+use vertex_array::DrawTy;
+
+/// Material properties from an MTL file. Texture paths are resolved relative
+/// to the MTL file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub name: String,
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub emissive: [f32; 3],
+    /// Specular exponent.
+    pub shininess: f32,
+    /// 1 is opaque.
+    pub opacity: f32,
+    pub diffuse_map: Option<PathBuf>,
+    pub specular_map: Option<PathBuf>,
+    pub normal_map: Option<PathBuf>,
+    pub opacity_map: Option<PathBuf>,
+}
+
+pub struct ObjModel {
+    pub mesh: MeshData,
+    /// Indexed by `Submesh::material`.
+    pub materials: Vec<Material>,
+}
+
+#[derive(Debug)]
+pub struct ObjError {
+    pub message: String,
+    pub io_error: Option<io::Error>,
+}
+
+/// Indices into the position, texture coordinate and normal lists.
+type VertexKey = (usize, Option<usize>, Option<usize>);
+
+struct ObjParser<'a> {
+    path: &'a Path,
+    line_number: usize,
+    positions: Vec<[f32; 3]>,
+    colors: Vec<Option<[f32; 4]>>,
+    uvs: Vec<[f32; 2]>,
+    normals: Vec<[f32; 3]>,
+    vertices: HashMap<VertexKey, u32>,
+    mesh: MeshData,
+    has_normal: Vec<bool>,
+    materials: Vec<Material>,
+    current_material: Option<usize>,
+    /// Triangle indices for each material, in order of first use.
+    groups: Vec<(Option<usize>, Vec<u32>)>,
+}
+
+impl Material {
+    pub fn new(name: &str) -> Self {
+        Material {
+            name: name.into(),
+            ambient: [0.2, 0.2, 0.2],
+            diffuse: [0.8, 0.8, 0.8],
+            specular: [1.0, 1.0, 1.0],
+            emissive: [0.0, 0.0, 0.0],
+            shininess: 0.0,
+            opacity: 1.0,
+            diffuse_map: None,
+            specular_map: None,
+            normal_map: None,
+            opacity_map: None,
+        }
+    }
+}
+
+impl ObjModel {
+    pub fn load(path: &Path) -> Result<ObjModel, ObjError> {
+        let source = read_file(path)?;
+        ObjModel::parse(&source, path)
+    }
+
+    /// Parses OBJ source as if read from `path`, which is used in errors and
+    /// to find `mtllib` files.
+    pub fn parse(source: &str, path: &Path) -> Result<ObjModel, ObjError> {
+        let mut parser = ObjParser {
+            path,
+            line_number: 0,
+            positions: Vec::new(),
+            colors: Vec::new(),
+            uvs: Vec::new(),
+            normals: Vec::new(),
+            vertices: HashMap::new(),
+            mesh: MeshData::new(DrawTy::Triangles),
+            has_normal: Vec::new(),
+            materials: Vec::new(),
+            current_material: None,
+            groups: Vec::new(),
+        };
+        for (i, line) in source.lines().enumerate() {
+            parser.line_number = i + 1;
+            parser.parse_line(line)?;
+        }
+        Ok(parser.finish())
+    }
+}
+
+/// Parses MTL source as if read from `path`, which is used in errors and to
+/// resolve texture paths.
+pub fn parse_mtl(source: &str, path: &Path) -> Result<Vec<Material>, ObjError> {
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut materials: Vec<Material> = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let error = |message: String| error_at(path, i + 1, message);
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) if !keyword.starts_with('#') => keyword,
+            _ => continue,
+        };
+        if keyword == "newmtl" {
+            let name = tokens.next().ok_or_else(|| error("Missing material name".into()))?;
+            materials.push(Material::new(name));
+            continue;
+        }
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => return Err(error(format!("{} before newmtl", keyword))),
+        };
+        match keyword {
+            "Ka" => material.ambient = parse_floats(&mut tokens).map_err(error)?,
+            "Kd" => material.diffuse = parse_floats(&mut tokens).map_err(error)?,
+            "Ks" => material.specular = parse_floats(&mut tokens).map_err(error)?,
+            "Ke" => material.emissive = parse_floats(&mut tokens).map_err(error)?,
+            "Ns" => material.shininess = parse_floats::<1>(&mut tokens).map_err(error)?[0],
+            "d" => material.opacity = parse_floats::<1>(&mut tokens).map_err(error)?[0],
+            "Tr" => material.opacity = 1.0 - parse_floats::<1>(&mut tokens).map_err(error)?[0],
+            "map_Kd" | "map_Ks" | "map_Bump" | "map_bump" | "bump" | "norm" | "map_d" => {
+                // Options such as `-bm 0.5` come before the file name.
+                let file = tokens.last().ok_or_else(|| error(format!("Missing {} file", keyword)))?;
+                let map = Some(directory.join(file));
+                match keyword {
+                    "map_Kd" => material.diffuse_map = map,
+                    "map_Ks" => material.specular_map = map,
+                    "map_d" => material.opacity_map = map,
+                    _ => material.normal_map = map,
+                }
+            },
+            // Illumination models and the rarer maps are not used.
+            _ => (),
+        }
+    }
+    Ok(materials)
+}
+
+impl<'a> ObjParser<'a> {
+    fn error(&self, message: String) -> ObjError {
+        error_at(self.path, self.line_number, message)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), ObjError> {
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                let values: Vec<&str> = tokens.collect();
+                let floats = parse_float_list(&values).map_err(|message| self.error(message))?;
+                match floats.len() {
+                    // An optional w, which only matters for rational curves.
+                    3 | 4 => self.colors.push(None),
+                    // The common extension of vertex colors.
+                    6 => self.colors.push(Some([floats[3], floats[4], floats[5], 1.0])),
+                    count => return Err(self.error(format!("Expected 3 coordinates, got {}", count))),
+                }
+                self.positions.push([floats[0], floats[1], floats[2]]);
+            },
+            Some("vt") => {
+                let values: Vec<&str> = tokens.collect();
+                let floats = parse_float_list(&values).map_err(|message| self.error(message))?;
+                if floats.is_empty() || floats.len() > 3 {
+                    return Err(self.error(format!("Expected 1 to 3 texture coordinates, got {}", floats.len())));
+                }
+                self.uvs.push([floats[0], floats.get(1).cloned().unwrap_or(0.0)]);
+            },
+            Some("vn") => {
+                let normal = parse_floats(&mut tokens).map_err(|message| self.error(message))?;
+                self.normals.push(normal);
+            },
+            Some("f") => {
+                let mut polygon = Vec::new();
+                for token in tokens {
+                    polygon.push(self.face_vertex(token)?);
+                }
+                if polygon.len() < 3 {
+                    return Err(self.error(format!("Face has {} vertices", polygon.len())));
+                }
+                let material = self.current_material;
+                let group = match self.groups.iter().position(|&(group_material, _)| group_material == material) {
+                    Some(group) => group,
+                    None => {
+                        self.groups.push((material, Vec::new()));
+                        self.groups.len() - 1
+                    },
+                };
+                let indices = &mut self.groups[group].1;
+                for i in 1..polygon.len() - 1 {
+                    indices.extend_from_slice(&[polygon[0], polygon[i], polygon[i + 1]]);
+                }
+            },
+            Some("mtllib") => {
+                let directory = self.path.parent().unwrap_or_else(|| Path::new(""));
+                for file in tokens {
+                    let mtl_path = directory.join(file);
+                    let source = match read_file(&mtl_path) {
+                        Ok(source) => source,
+                        Err(ObjError { io_error: Some(ref err), .. }) if err.kind() == io::ErrorKind::NotFound => {
+                            continue;
+                        },
+                        Err(err) => {
+                            return Err(ObjError {
+                                message: format!("{}:{}: {}", self.path.display(), self.line_number, err.message),
+                                io_error: err.io_error,
+                            });
+                        },
+                    };
+                    self.materials.extend(parse_mtl(&source, &mtl_path)?);
+                }
+            },
+            Some("usemtl") => {
+                let name = tokens.next().ok_or_else(|| self.error("Missing material name".into()))?;
+                let material = match self.materials.iter().rposition(|material| material.name == name) {
+                    Some(material) => material,
+                    None => {
+                        self.materials.push(Material::new(name));
+                        self.materials.len() - 1
+                    },
+                };
+                self.current_material = Some(material);
+            },
+            // Objects, groups, smoothing groups, lines, points and comments
+            // do not affect the mesh.
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Parses a `v`, `v/vt`, `v//vn` or `v/vt/vn` reference, adding a vertex
+    /// unless the same combination was seen before.
+    fn face_vertex(&mut self, token: &str) -> Result<u32, ObjError> {
+        let mut parts = token.split('/');
+        let position = self.resolve_index(parts.next(), self.positions.len(), "position")?
+            .ok_or_else(|| self.error(format!("Face vertex {:?} has no position", token)))?;
+        let uv = self.resolve_index(parts.next(), self.uvs.len(), "texture coordinate")?;
+        let normal = self.resolve_index(parts.next(), self.normals.len(), "normal")?;
+        if parts.next().is_some() {
+            return Err(self.error(format!("Malformed face vertex {:?}", token)));
+        }
+        let key = (position, uv, normal);
+        if let Some(&index) = self.vertices.get(&key) {
+            return Ok(index);
+        }
+        let index = self.mesh.positions.len() as u32;
+        self.mesh.positions.push(self.positions[position]);
+        self.mesh.colors.push(self.colors[position].unwrap_or([1.0, 1.0, 1.0, 1.0]));
+        self.mesh.uvs.push(uv.map_or([0.0, 0.0], |uv| self.uvs[uv]));
+        self.mesh.normals.push(normal.map_or([0.0, 0.0, 0.0], |normal| self.normals[normal]));
+        self.has_normal.push(normal.is_some());
+        self.vertices.insert(key, index);
+        Ok(index)
+    }
+
+    /// Resolves a 1-based index, or a negative one counting back from the
+    /// latest element, to a 0-based one. Empty parts are `None`.
+    fn resolve_index(&self, part: Option<&str>, len: usize, name: &str) -> Result<Option<usize>, ObjError> {
+        let part = match part {
+            Some(part) if !part.is_empty() => part,
+            _ => return Ok(None),
+        };
+        let index: i64 = part
+            .parse()
+            .map_err(|_| self.error(format!("Invalid {} index {:?}", name, part)))?;
+        let resolved = if index < 0 { len as i64 + index } else { index - 1 };
+        if resolved < 0 || resolved >= len as i64 {
+            return Err(self.error(format!("{} index {} is out of range for {} defined so far", name, index, len)));
+        }
+        Ok(Some(resolved as usize))
+    }
+
+    fn finish(self) -> ObjModel {
+        let ObjParser {
+            mut mesh,
+            has_normal,
+            colors,
+            vertices,
+            materials,
+            groups,
+            ..
+        } = self;
+        let mut indices = Vec::new();
+        for (material, group_indices) in groups {
+            mesh.submeshes.push(Submesh {
+                first: indices.len(),
+                count: group_indices.len(),
+                material,
+            });
+            indices.extend(group_indices);
+        }
+        mesh.indices = Some(indices);
+        if !has_normal.iter().all(|&has_normal| has_normal) {
+            let smooth_normals = mesh.smooth_normals();
+            for (i, normal) in mesh.normals.iter_mut().enumerate() {
+                if !has_normal[i] {
+                    *normal = smooth_normals[i];
+                }
+            }
+        }
+        if !vertices.keys().any(|&(_, uv, _)| uv.is_some()) {
+            mesh.uvs.clear();
+        }
+        if colors.iter().all(|color| color.is_none()) {
+            mesh.colors.clear();
+        }
+        ObjModel {
+            mesh,
+            materials,
+        }
+    }
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.io_error.as_ref().map(|err| err as _)
+    }
+}
+
+fn error_at(path: &Path, line_number: usize, message: String) -> ObjError {
+    ObjError {
+        message: format!("{}:{}: {}", path.display(), line_number, message),
+        io_error: None,
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, ObjError> {
+    let mut file = File::open(path).map_err(|err| {
+        ObjError {
+            message: format!("Failed to open file {:?}", path),
+            io_error: Some(err),
+        }
+    })?;
+    let mut source = String::new();
+    file.read_to_string(&mut source).map_err(|err| {
+        ObjError {
+            message: format!("Failed to read file {:?}", path),
+            io_error: Some(err),
+        }
+    })?;
+    Ok(source)
+}
+
+fn parse_float_list(values: &[&str]) -> Result<Vec<f32>, String> {
+    values
+        .iter()
+        .map(|value| value.parse().map_err(|_| format!("Invalid number {:?}", value)))
+        .collect()
+}
+
+/// Parses exactly `N` numbers.
+fn parse_floats<const N: usize>(tokens: &mut SplitWhitespace) -> Result<[f32; N], String> {
+    let values: Vec<&str> = tokens.collect();
+    if values.len() != N {
+        return Err(format!("Expected {} numbers, got {}", N, values.len()));
+    }
+    let mut floats = [0.0; N];
+    for (float, value) in floats.iter_mut().zip(values) {
+        *float = value.parse().map_err(|_| format!("Invalid number {:?}", value))?;
+    }
+    Ok(floats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<ObjModel, ObjError> {
+        ObjModel::parse(source, Path::new("models/test.obj"))
+    }
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+
+    #[test]
+    fn quads_become_triangle_fans() {
+        let model = parse(&format!("{}f 1 2 3 4\n", SQUARE)).unwrap();
+        let mesh = &model.mesh;
+        assert_eq!(mesh.positions, vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]]);
+        assert_eq!(mesh.indices, Some(vec![0, 1, 2, 0, 2, 3]));
+        // Without `vn` the normals are smooth, facing the side the face
+        // winds counter-clockwise around.
+        assert!(mesh.normals.iter().all(|&normal| normal == [0.0, 0.0, 1.0]));
+        assert!(mesh.uvs.is_empty());
+        assert!(mesh.colors.is_empty());
+        assert_eq!(mesh.submeshes.len(), 1);
+        assert_eq!(mesh.submeshes[0].material, None);
+        mesh.validate().unwrap();
+    }
+
+    #[test]
+    fn shared_attribute_combinations_share_a_vertex() {
+        let model = parse(&format!(
+            "{}vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\nvn 0 0 -1\n\
+             f 1/1/1 2/2/1 3/3/1\nf 1/1/1 3/3/1 4/4/1\nf 1/1/2 3/3/2 2/2/2\n",
+            SQUARE,
+        ))
+        .unwrap();
+        let mesh = &model.mesh;
+        // The back face differs in normal, so it gets vertices of its own.
+        assert_eq!(mesh.vertex_count(), 4 + 3);
+        assert_eq!(mesh.indices, Some(vec![0, 1, 2, 0, 2, 3, 4, 5, 6]));
+        assert_eq!(mesh.uvs[3], [0.0, 1.0]);
+        assert_eq!(mesh.normals[4], [0.0, 0.0, -1.0]);
+        mesh.validate().unwrap();
+    }
+
+    #[test]
+    fn negative_indices_count_back() {
+        let model = parse(
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nvt 0.5 0.5\nf -3/-1 -2/-1 -1/-1\nv 5 5 5\nf -4/1 -3/-1 -1/1\n",
+        )
+        .unwrap();
+        let mesh = &model.mesh;
+        // `-4` after the fourth position is the same vertex as `-3` before it.
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.indices, Some(vec![0, 1, 2, 0, 1, 3]));
+        assert_eq!(mesh.positions[3], [5.0, 5.0, 5.0]);
+        assert_eq!(mesh.uvs, vec![[0.5, 0.5]; 4]);
+    }
+
+    #[test]
+    fn faces_are_grouped_by_material() {
+        let model = parse(&format!(
+            "{}f 1 2 3\nusemtl red\nf 1 3 4\nusemtl blue\nf 2 3 4\nusemtl red\nf 1 2 4\n",
+            SQUARE,
+        ))
+        .unwrap();
+        let mesh = &model.mesh;
+        assert_eq!(model.materials, vec![Material::new("red"), Material::new("blue")]);
+        let submeshes: Vec<(usize, usize, Option<usize>)> = mesh
+            .submeshes
+            .iter()
+            .map(|submesh| (submesh.first, submesh.count, submesh.material))
+            .collect();
+        assert_eq!(submeshes, vec![(0, 3, None), (3, 6, Some(0)), (9, 3, Some(1))]);
+        assert_eq!(mesh.indices, Some(vec![0, 1, 2, 0, 2, 3, 0, 1, 3, 1, 2, 3]));
+    }
+
+    #[test]
+    fn missing_materials_get_defaults() {
+        let model = parse(&format!("mtllib gl_fun_missing.mtl\n{}usemtl lost\nf 1 2 3\n", SQUARE)).unwrap();
+        assert_eq!(model.materials, vec![Material::new("lost")]);
+        assert_eq!(model.mesh.submeshes[0].material, Some(0));
+    }
+
+    #[test]
+    fn errors_name_file_and_line() {
+        let err = parse(&format!("{}\nf 1 2 5\n", SQUARE)).err().unwrap();
+        assert_eq!(err.message, "models/test.obj:6: position index 5 is out of range for 4 defined so far");
+        let err = parse(&format!("{}f 1/1 2/1 3/1\n", SQUARE)).err().unwrap();
+        assert_eq!(
+            err.message,
+            "models/test.obj:5: texture coordinate index 1 is out of range for 0 defined so far",
+        );
+        let err = parse("v 0 0\n").err().unwrap();
+        assert_eq!(err.message, "models/test.obj:1: Expected 3 coordinates, got 2");
+        let err = parse(&format!("{}f 1 2\n", SQUARE)).err().unwrap();
+        assert_eq!(err.message, "models/test.obj:5: Face has 2 vertices");
+    }
+
+    #[test]
+    fn mtl_materials() {
+        let materials = parse_mtl(
+            "# Exported.\nnewmtl metal\nKd 0.5 0.5 0.5\nNs 250\nmap_Kd textures/metal.png\n\
+             map_Bump -bm 0.5 normal.png\nnewmtl glass\nd 0.25\n",
+            Path::new("models/test.mtl"),
+        )
+        .unwrap();
+        assert_eq!(materials.len(), 2);
+        assert_eq!(materials[0].diffuse, [0.5, 0.5, 0.5]);
+        assert_eq!(materials[0].shininess, 250.0);
+        assert_eq!(materials[0].diffuse_map, Some(PathBuf::from("models/textures/metal.png")));
+        assert_eq!(materials[0].normal_map, Some(PathBuf::from("models/normal.png")));
+        assert_eq!(materials[1].opacity, 0.25);
+        let err = parse_mtl("newmtl metal\nKd 0.5 0.5\n", Path::new("models/test.mtl")).err().unwrap();
+        assert_eq!(err.message, "models/test.mtl:2: Expected 3 numbers, got 2");
+        let err = parse_mtl("Kd 1 1 1\n", Path::new("models/test.mtl")).err().unwrap();
+        assert_eq!(err.message, "models/test.mtl:1: Kd before newmtl");
+    }
+}
@@ -0,0 +1,520 @@
+use std;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use shader::ShaderTy;
+
+#[derive(Debug)]
+pub struct PreprocessorError {
+    pub message: String,
+    pub io_error: Option<io::Error>,
+}
+
+/// Maps each line of preprocessed output back to the file and line it came
+/// from. Output lines are 1-based, matching what drivers report.
+#[derive(Clone, Debug, Default)]
+pub struct LineMap {
+    files: Vec<PathBuf>,
+    lines: Vec<(usize, usize)>,
+}
+
+pub struct PreprocessedSource {
+    pub source: String,
+    pub line_map: LineMap,
+}
+
+/// CPU-side GLSL preprocessor resolving `#include "file"` relative to the
+/// including file and injecting defines right after `#version`.
+#[derive(Default)]
+pub struct ShaderPreprocessor {
+    defines: Vec<(String, String)>,
+}
+
+struct PreprocessState {
+    output: String,
+    line_map: LineMap,
+    once_files: HashSet<PathBuf>,
+    guards: HashSet<String>,
+    include_stack: Vec<PathBuf>,
+    version_seen: bool,
+}
+
+impl PreprocessedSource {
+    /// Splits a single-file program on `#pragma stage <name>` lines. Lines
+    /// before the first stage form a header shared by every stage.
+    pub fn split_stages(&self) -> Result<Vec<(ShaderTy, PreprocessedSource)>, PreprocessorError> {
+        let mut header = Vec::new();
+        let mut stages: Vec<(ShaderTy, Vec<usize>)> = Vec::new();
+        for (i, line) in self.source.lines().enumerate() {
+            let stage_name = match parse_directive(line) {
+                Some(("pragma", args)) if args.starts_with("stage") => Some(args["stage".len()..].trim()),
+                _ => None,
+            };
+            match stage_name {
+                Some(stage_name) => {
+                    let ty = ShaderTy::from_stage_name(stage_name).ok_or_else(|| {
+                        self.error_at(i + 1, &format!("Unknown shader stage {:?}", stage_name))
+                    })?;
+                    if stages.iter().any(|(stage_ty, _)| *stage_ty == ty) {
+                        return Err(self.error_at(i + 1, &format!("Duplicate shader stage {:?}", stage_name)));
+                    }
+                    stages.push((ty, header.clone()));
+                },
+                None => match stages.last_mut() {
+                    Some((_, lines)) => lines.push(i),
+                    None => header.push(i),
+                },
+            }
+        }
+        if stages.is_empty() {
+            return Err(PreprocessorError {
+                message: "No #pragma stage sections found".into(),
+                io_error: None,
+            });
+        }
+        let source_lines = self.source.lines().collect::<Vec<_>>();
+        Ok(stages.into_iter().map(|(ty, lines)| {
+            let mut source = String::new();
+            let mut line_map = LineMap {
+                files: self.line_map.files.clone(),
+                lines: Vec::with_capacity(lines.len()),
+            };
+            for i in lines {
+                source.push_str(source_lines[i]);
+                source.push('\n');
+                line_map.lines.push(self.line_map.lines[i]);
+            }
+            (ty, PreprocessedSource {
+                source,
+                line_map,
+            })
+        }).collect())
+    }
+
+    fn error_at(&self, line: usize, message: &str) -> PreprocessorError {
+        let location = match self.line_map.lookup(line) {
+            Some((path, original_line)) => format!("{}:{}", path.display(), original_line),
+            None => format!("line {}", line),
+        };
+        PreprocessorError {
+            message: format!("{}: {}", location, message),
+            io_error: None,
+        }
+    }
+}
+
+impl fmt::Display for PreprocessorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for PreprocessorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.io_error.as_ref().map(|err| err as _)
+    }
+}
+
+impl LineMap {
+    pub fn lookup(&self, line: usize) -> Option<(&Path, usize)> {
+        if line == 0 {
+            return None;
+        }
+        self.lines.get(line - 1).map(|&(file_index, original_line)| {
+            (self.files[file_index].as_path(), original_line)
+        })
+    }
+
+    pub fn num_lines(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Rewrites `<string>:<line>` and `<string>(<line>)` references in a
+    /// driver info log to `<file>:<line>` of the original source.
+    pub fn rewrite_info_log(&self, info_log: &str) -> String {
+        let mut rewritten = String::with_capacity(info_log.len());
+        for (i, log_line) in info_log.lines().enumerate() {
+            if i > 0 {
+                rewritten.push('\n');
+            }
+            match find_line_reference(log_line) {
+                Some((start, end, line)) => match self.lookup(line) {
+                    Some((path, original_line)) => {
+                        rewritten.push_str(&log_line[..start]);
+                        rewritten.push_str(&format!("{}:{}", path.display(), original_line));
+                        rewritten.push_str(&log_line[end..]);
+                    },
+                    None => rewritten.push_str(log_line),
+                },
+                None => rewritten.push_str(log_line),
+            }
+        }
+        rewritten
+    }
+
+    fn file_index(&mut self, path: &Path) -> usize {
+        match self.files.iter().position(|file| file == path) {
+            Some(index) => index,
+            None => {
+                self.files.push(path.to_path_buf());
+                self.files.len() - 1
+            },
+        }
+    }
+}
+
+/// Finds the first `N:L` (Mesa, AMD, Intel) or `N(L)` (NVIDIA) reference in a
+/// log line, returning its byte range and the referenced line.
+pub(crate) fn find_line_reference(log_line: &str) -> Option<(usize, usize, usize)> {
+    let bytes = log_line.as_bytes();
+    let mut start = 0;
+    while start < bytes.len() {
+        if !bytes[start].is_ascii_digit() || (start > 0 && bytes[start - 1].is_ascii_alphanumeric()) {
+            start += 1;
+            continue;
+        }
+        let mut i = start;
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+        }
+        if i + 1 < bytes.len() && (bytes[i] == b':' || bytes[i] == b'(') {
+            let close = bytes[i];
+            let line_start = i + 1;
+            let mut j = line_start;
+            while j < bytes.len() && bytes[j].is_ascii_digit() {
+                j += 1;
+            }
+            let closed = close == b':' || (j < bytes.len() && bytes[j] == b')');
+            if j > line_start && closed {
+                let end = if close == b'(' { j + 1 } else { j };
+                if let Ok(line) = log_line[line_start..j].parse() {
+                    return Some((start, end, line));
+                }
+            }
+        }
+        start = i;
+    }
+    None
+}
+
+fn read_file(path: &Path) -> Result<String, PreprocessorError> {
+    let mut file = File::open(path).map_err(|err| {
+        PreprocessorError {
+            message: format!("Failed to open file {:?}", path),
+            io_error: Some(err),
+        }
+    })?;
+    let mut source = String::new();
+    file.read_to_string(&mut source).map_err(|err| {
+        PreprocessorError {
+            message: format!("Failed to read file {:?}", path),
+            io_error: Some(err),
+        }
+    })?;
+    Ok(source)
+}
+
+fn parse_directive(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start();
+    if !trimmed.starts_with('#') {
+        return None;
+    }
+    let rest = trimmed[1..].trim_start();
+    let name_len = rest
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(rest.len());
+    Some((&rest[..name_len], rest[name_len..].trim()))
+}
+
+fn parse_include_path(args: &str) -> Option<&str> {
+    if args.len() >= 2 && args.starts_with('"') {
+        args[1..].find('"').map(|end| &args[1..end + 1])
+    } else {
+        None
+    }
+}
+
+/// Returns the macro name of a classic `#ifndef X` / `#define X` guard at the
+/// top of a file, ignoring blank lines and line comments.
+fn find_include_guard(source: &str) -> Option<String> {
+    let mut lines = source
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with("//"));
+    let guard = match lines.next().and_then(parse_directive) {
+        Some(("ifndef", name)) => name,
+        _ => return None,
+    };
+    match lines.next().and_then(parse_directive) {
+        Some(("define", name)) if name == guard => Some(guard.into()),
+        _ => None,
+    }
+}
+
+impl ShaderPreprocessor {
+    pub fn new() -> Self {
+        ShaderPreprocessor {
+            defines: Vec::new(),
+        }
+    }
+
+    pub fn define(mut self, name: &str, value: &str) -> Self {
+        self.defines.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn preprocess_file(&self, path: &Path) -> Result<PreprocessedSource, PreprocessorError> {
+        let source = read_file(path)?;
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.preprocess(&source, &path)
+    }
+
+    /// Preprocesses in-memory source; includes resolve relative to the current
+    /// working directory.
+    pub fn preprocess_source(&self, source: &str) -> Result<PreprocessedSource, PreprocessorError> {
+        self.preprocess(source, Path::new("<source>"))
+    }
+
+    fn preprocess(&self, source: &str, path: &Path) -> Result<PreprocessedSource, PreprocessorError> {
+        let mut state = PreprocessState {
+            output: String::with_capacity(source.len()),
+            line_map: LineMap::default(),
+            once_files: HashSet::new(),
+            guards: HashSet::new(),
+            include_stack: Vec::new(),
+            version_seen: false,
+        };
+        self.process_file(&mut state, source, path)?;
+        if !state.version_seen {
+            self.inject_defines(&mut state, path, 0);
+        }
+        Ok(PreprocessedSource {
+            source: state.output,
+            line_map: state.line_map,
+        })
+    }
+
+    fn process_file(
+        &self,
+        state: &mut PreprocessState,
+        source: &str,
+        path: &Path,
+    ) -> Result<(), PreprocessorError> {
+        if let Some(guard) = find_include_guard(source) {
+            if !state.guards.insert(guard) {
+                return Ok(());
+            }
+        }
+        state.include_stack.push(path.to_path_buf());
+        let file_index = state.line_map.file_index(path);
+        for (i, line) in source.lines().enumerate() {
+            let line_number = i + 1;
+            match parse_directive(line) {
+                Some(("include", args)) => {
+                    let include = parse_include_path(args).ok_or_else(|| {
+                        PreprocessorError {
+                            message: format!("{}:{}: Malformed #include {}", path.display(), line_number, args),
+                            io_error: None,
+                        }
+                    })?;
+                    let include_path = path.parent().unwrap_or_else(|| Path::new("")).join(include);
+                    let canonical_path = include_path.canonicalize().map_err(|err| {
+                        PreprocessorError {
+                            message: format!("{}:{}: Failed to resolve include {:?}", path.display(), line_number, include_path),
+                            io_error: Some(err),
+                        }
+                    })?;
+                    if state.once_files.contains(&canonical_path) {
+                        continue;
+                    }
+                    if state.include_stack.iter().any(|p| p == &canonical_path) {
+                        return Err(PreprocessorError {
+                            message: format!("{}:{}: Recursive include of {:?}", path.display(), line_number, include_path),
+                            io_error: None,
+                        });
+                    }
+                    let include_source = read_file(&include_path)?;
+                    self.process_file(state, &include_source, &canonical_path)?;
+                },
+                Some(("pragma", "once")) => {
+                    state.once_files.insert(path.to_path_buf());
+                },
+                Some(("version", _)) if !state.version_seen => {
+                    state.version_seen = true;
+                    push_line(state, line, file_index, line_number);
+                    self.inject_defines(state, path, line_number);
+                },
+                _ => push_line(state, line, file_index, line_number),
+            }
+        }
+        state.include_stack.pop();
+        Ok(())
+    }
+
+    fn inject_defines(&self, state: &mut PreprocessState, path: &Path, line_number: usize) {
+        let file_index = state.line_map.file_index(path);
+        let mut injected = String::new();
+        for (name, value) in &self.defines {
+            injected.push_str(&format!("#define {} {}\n", name, value));
+            state.line_map.lines.push((file_index, line_number));
+        }
+        if line_number == 0 {
+            state.output.insert_str(0, &injected);
+            state.line_map.lines.rotate_right(self.defines.len());
+        } else {
+            state.output.push_str(&injected);
+        }
+    }
+}
+
+fn push_line(state: &mut PreprocessState, line: &str, file_index: usize, line_number: usize) {
+    state.output.push_str(line);
+    state.output.push('\n');
+    state.line_map.lines.push((file_index, line_number));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// A fresh directory of shader files for one test.
+    fn write_files(test_name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir_name = format!("gl_fun_preprocessor_{}_{}", test_name, std::process::id());
+        let dir = std::env::temp_dir().join(dir_name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (name, source) in files {
+            fs::write(dir.join(name), source).unwrap();
+        }
+        dir
+    }
+
+    fn lookup(source: &PreprocessedSource, line: usize) -> (String, usize) {
+        let (path, original_line) = source.line_map.lookup(line).unwrap();
+        (path.file_name().unwrap().to_string_lossy().into_owned(), original_line)
+    }
+
+    #[test]
+    fn includes_are_inlined_and_mapped_to_their_file() {
+        let dir = write_files("include", &[
+            ("main.glsl", "#version 330\n#include \"common.glsl\"\nvoid main() {}\n"),
+            ("common.glsl", "float a;\nfloat b;\n"),
+        ]);
+        let result = ShaderPreprocessor::new().preprocess_file(&dir.join("main.glsl")).unwrap();
+        assert_eq!(result.source, "#version 330\nfloat a;\nfloat b;\nvoid main() {}\n");
+        assert_eq!(lookup(&result, 1), ("main.glsl".into(), 1));
+        assert_eq!(lookup(&result, 2), ("common.glsl".into(), 1));
+        assert_eq!(lookup(&result, 3), ("common.glsl".into(), 2));
+        assert_eq!(lookup(&result, 4), ("main.glsl".into(), 3));
+        assert!(result.line_map.lookup(5).is_none());
+    }
+
+    #[test]
+    fn pragma_once_and_include_guards_include_a_file_once() {
+        let dir = write_files("once", &[
+            ("main.glsl", "#include \"once.glsl\"\n#include \"guarded.glsl\"\n\
+                           #include \"once.glsl\"\n#include \"guarded.glsl\"\n"),
+            ("once.glsl", "#pragma once\nfloat a;\n"),
+            ("guarded.glsl", "// Guarded.\n#ifndef GUARDED\n#define GUARDED\nfloat b;\n#endif\n"),
+        ]);
+        let result = ShaderPreprocessor::new().preprocess_file(&dir.join("main.glsl")).unwrap();
+        assert_eq!(result.source, "float a;\n// Guarded.\n#ifndef GUARDED\n#define GUARDED\nfloat b;\n#endif\n");
+    }
+
+    #[test]
+    fn recursive_include_is_an_error() {
+        let dir = write_files("recursive", &[
+            ("a.glsl", "#include \"b.glsl\"\n"),
+            ("b.glsl", "float b;\n#include \"a.glsl\"\n"),
+        ]);
+        let err = ShaderPreprocessor::new().preprocess_file(&dir.join("a.glsl")).err().unwrap();
+        assert!(err.message.contains("b.glsl:2: Recursive include"), "{}", err.message);
+    }
+
+    #[test]
+    fn missing_include_names_the_including_line() {
+        let err = ShaderPreprocessor::new()
+            .preprocess_source("float a;\n#include \"gl_fun_missing.glsl\"\n")
+            .err()
+            .unwrap();
+        assert!(err.message.starts_with("<source>:2: Failed to resolve include"), "{}", err.message);
+        assert!(err.io_error.is_some());
+    }
+
+    #[test]
+    fn defines_follow_version() {
+        let result = ShaderPreprocessor::new()
+            .define("COUNT", "4")
+            .define("USE_FOG", "1")
+            .preprocess_source("// Header.\n#version 330\nvoid main() {}\n")
+            .unwrap();
+        assert_eq!(
+            result.source,
+            "// Header.\n#version 330\n#define COUNT 4\n#define USE_FOG 1\nvoid main() {}\n",
+        );
+        assert_eq!(lookup(&result, 3), ("<source>".into(), 2));
+        assert_eq!(lookup(&result, 5), ("<source>".into(), 3));
+    }
+
+    #[test]
+    fn defines_lead_source_without_version() {
+        let result = ShaderPreprocessor::new().define("COUNT", "4").preprocess_source("void main() {}\n").unwrap();
+        assert_eq!(result.source, "#define COUNT 4\nvoid main() {}\n");
+        assert_eq!(lookup(&result, 2), ("<source>".into(), 1));
+    }
+
+    #[test]
+    fn info_log_lines_are_rewritten() {
+        let dir = write_files("info_log", &[
+            ("main.glsl", "#version 330\n#include \"common.glsl\"\nvoid main() {}\n"),
+            ("common.glsl", "float a;\n"),
+        ]);
+        let result = ShaderPreprocessor::new().preprocess_file(&dir.join("main.glsl")).unwrap();
+        let main = dir.join("main.glsl").canonicalize().unwrap();
+        let common = dir.join("common.glsl").canonicalize().unwrap();
+        let log = "0:2(7): error: syntax error\n0(3) : warning C7022: unrecognized profile\nlinker error";
+        assert_eq!(
+            result.line_map.rewrite_info_log(log),
+            format!(
+                "{}:1(7): error: syntax error\n{}:3 : warning C7022: unrecognized profile\nlinker error",
+                common.display(),
+                main.display(),
+            ),
+        );
+    }
+
+    #[test]
+    fn stages_share_the_header() {
+        let source = ShaderPreprocessor::new()
+            .preprocess_source(
+                "#version 330\n#pragma stage vertex\nvoid vs() {}\n#pragma stage fragment\nvoid fs() {}\n",
+            )
+            .unwrap();
+        let stages = source.split_stages().unwrap();
+        assert_eq!(stages.len(), 2);
+        assert_eq!(stages[0].0, ShaderTy::Vertex);
+        assert_eq!(stages[0].1.source, "#version 330\nvoid vs() {}\n");
+        assert_eq!(stages[1].0, ShaderTy::Fragment);
+        assert_eq!(stages[1].1.source, "#version 330\nvoid fs() {}\n");
+        assert_eq!(lookup(&stages[1].1, 2), ("<source>".into(), 5));
+    }
+
+    #[test]
+    fn bad_stages_are_errors() {
+        let preprocessor = ShaderPreprocessor::new();
+        let unknown = preprocessor.preprocess_source("#pragma stage pixel\n").unwrap().split_stages();
+        assert_eq!(unknown.err().unwrap().message, "<source>:1: Unknown shader stage \"pixel\"");
+        let duplicate = preprocessor
+            .preprocess_source("#pragma stage vertex\n#pragma stage vertex\n")
+            .unwrap()
+            .split_stages();
+        assert_eq!(duplicate.err().unwrap().message, "<source>:2: Duplicate shader stage \"vertex\"");
+        let none = preprocessor.preprocess_source("void main() {}\n").unwrap().split_stages();
+        assert_eq!(none.err().unwrap().message, "No #pragma stage sections found");
+    }
+}
@@ -0,0 +1,200 @@
+use std;
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use context::Context;
+use preprocessor::PreprocessedSource;
+use shader::{Shader, ShaderTy};
+use shader_program::{LinkOptions, ProgramBinary, ShaderProgram, ShaderProgramError};
+
+const MAGIC: &[u8; 4] = b"GLPB";
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+#[derive(Debug)]
+pub struct ProgramCacheError {
+    pub message: String,
+    pub io_error: Option<io::Error>,
+}
+
+/// On-disk cache of linked program binaries, keyed by the preprocessed
+/// sources and the driver that produced them. Each entry's header repeats
+/// the driver id and a hash of the sources, so an entry whose file name
+/// collides with another program's is relinked, not loaded.
+pub struct ProgramCache {
+    context: Context,
+    dir: PathBuf,
+    driver_id: String,
+}
+
+impl fmt::Display for ProgramCacheError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ProgramCacheError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.io_error.as_ref().map(|err| err as _)
+    }
+}
+
+impl ProgramCache {
+    /// Requires a current GL context, which is queried for the driver
+    /// vendor, renderer and version.
+    pub fn new(context: &Context, dir: &Path) -> Result<Self, ProgramCacheError> {
+        fs::create_dir_all(dir).map_err(|err| {
+            ProgramCacheError {
+                message: format!("Failed to create cache directory {:?}", dir),
+                io_error: Some(err),
+            }
+        })?;
+        let capabilities = context.capabilities();
+        let driver_id = format!(
+            "{}\n{}\n{}",
+            capabilities.vendor,
+            capabilities.renderer,
+            capabilities.version_string,
+        );
+        Ok(ProgramCache {
+            context: context.clone(),
+            dir: dir.to_path_buf(),
+            driver_id,
+        })
+    }
+
+    /// Loads the program from the cache, or compiles and links it from source
+    /// when there is no entry or the driver rejects the cached binary.
+    pub fn link(&self, sources: &[(ShaderTy, &PreprocessedSource)]) -> Result<ShaderProgram, ShaderProgramError> {
+        let hash = source_hash(sources);
+        let path = self.dir.join(format!("{}.bin", self.file_stem(hash)));
+        if let Some(binary) = read_binary(&path, &self.driver_id, hash) {
+            if let Ok(program) = ShaderProgram::from_binary(&self.context, &binary) {
+                return Ok(program);
+            }
+        }
+        let shaders = sources
+            .iter()
+            .map(|&(ref ty, source)| Shader::from_preprocessed(&self.context, ty.clone(), source))
+            .collect::<Result<Vec<_>, _>>()?;
+        let shader_refs = shaders.iter().collect::<Vec<_>>();
+        let program = ShaderProgram::link_with_options(
+            &self.context,
+            &shader_refs,
+            &LinkOptions {
+                binary_retrievable: true,
+                ..LinkOptions::default()
+            },
+        )?;
+        if let Some(binary) = program.get_binary() {
+            // A failed write only costs a relink next time.
+            let _ = write_binary(&path, &self.driver_id, hash, &binary);
+        }
+        Ok(program)
+    }
+
+    /// A hash of the driver id and the sources' hash, in hex.
+    fn file_stem(&self, source_hash: u64) -> String {
+        let key = fnv1a(add_sized(FNV_OFFSET_BASIS, self.driver_id.as_bytes()), &source_hash.to_le_bytes());
+        format!("{:016x}", key)
+    }
+}
+
+/// A hash of each stage's type and source, in order.
+fn source_hash(sources: &[(ShaderTy, &PreprocessedSource)]) -> u64 {
+    sources.iter().fold(FNV_OFFSET_BASIS, |hash, &(ref ty, source)| {
+        add_sized(fnv1a(hash, &ty.to_gl_shader_ty().to_le_bytes()), source.source.as_bytes())
+    })
+}
+
+/// FNV-1a, for cache keys that must not change between builds the way
+/// `std`'s hashers may.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME))
+}
+
+/// Hashes `bytes` after their length, so that neighbouring fields cannot run
+/// into each other.
+fn add_sized(hash: u64, bytes: &[u8]) -> u64 {
+    fnv1a(fnv1a(hash, &(bytes.len() as u64).to_le_bytes()), bytes)
+}
+
+/// Entries are the magic, the driver id's length and bytes, the source hash,
+/// the binary format and then the binary.
+fn read_binary(path: &Path, driver_id: &str, source_hash: u64) -> Option<ProgramBinary> {
+    let mut bytes = Vec::new();
+    File::open(path).ok()?.read_to_end(&mut bytes).ok()?;
+    if bytes.len() < 8 || &bytes[0..4] != MAGIC {
+        return None;
+    }
+    let mut len_bytes = [0; 4];
+    len_bytes.copy_from_slice(&bytes[4..8]);
+    let driver_id_end = 8 + u32::from_le_bytes(len_bytes) as usize;
+    let hash_end = driver_id_end + 8;
+    let header_len = hash_end + 4;
+    if bytes.len() < header_len
+        || &bytes[8..driver_id_end] != driver_id.as_bytes()
+        || bytes[driver_id_end..hash_end] != source_hash.to_le_bytes()
+    {
+        return None;
+    }
+    let mut format_bytes = [0; 4];
+    format_bytes.copy_from_slice(&bytes[hash_end..header_len]);
+    Some(ProgramBinary {
+        format: u32::from_le_bytes(format_bytes),
+        data: bytes.split_off(header_len),
+    })
+}
+
+fn write_binary(
+    path: &Path,
+    driver_id: &str,
+    source_hash: u64,
+    binary: &ProgramBinary,
+) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(MAGIC)?;
+        file.write_all(&(driver_id.len() as u32).to_le_bytes())?;
+        file.write_all(driver_id.as_bytes())?;
+        file.write_all(&source_hash.to_le_bytes())?;
+        file.write_all(&binary.format.to_le_bytes())?;
+        file.write_all(&binary.data)?;
+    }
+    fs::rename(&tmp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_matches_known_hashes() {
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"foobar"), 0x8594_4171_f739_67e8);
+        assert_eq!(fnv1a(fnv1a(FNV_OFFSET_BASIS, b"foo"), b"bar"), fnv1a(FNV_OFFSET_BASIS, b"foobar"));
+    }
+
+    #[test]
+    fn entries_only_load_for_the_same_driver_and_sources() {
+        let path = std::env::temp_dir().join(format!("gl_fun_program_cache_{}.bin", std::process::id()));
+        let hash = 0x0123_4567_89ab_cdef;
+        let binary = ProgramBinary {
+            format: 0x8741,
+            data: vec![1, 2, 3, 4, 5],
+        };
+        write_binary(&path, "Vendor\nRenderer\n4.6", hash, &binary).unwrap();
+        let loaded = read_binary(&path, "Vendor\nRenderer\n4.6", hash).unwrap();
+        assert_eq!(loaded.format, binary.format);
+        assert_eq!(loaded.data, binary.data);
+        assert!(read_binary(&path, "Vendor\nRenderer\n4.5", hash).is_none());
+        assert!(read_binary(&path, "Vendor\nRenderer\n4.6", hash + 1).is_none());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,257 @@
+use gl;
+use gl::types::*;
+use std;
+use std::fmt;
+
+use context::Context;
+use debug::set_object_label;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryTy {
+    AnySamplesPassed,
+    SamplesPassed,
+    /// GPU time between `begin` and the end of the query, in nanoseconds.
+    TimeElapsed,
+    /// GPU time at which all previous commands finished, in nanoseconds.
+    Timestamp,
+}
+
+impl QueryTy {
+    pub fn to_gl_target(self) -> GLenum {
+        match self {
+            QueryTy::AnySamplesPassed => gl::ANY_SAMPLES_PASSED,
+            QueryTy::SamplesPassed => gl::SAMPLES_PASSED,
+            QueryTy::TimeElapsed => gl::TIME_ELAPSED,
+            QueryTy::Timestamp => gl::TIMESTAMP,
+        }
+    }
+
+    pub fn is_supported(self, context: &Context) -> bool {
+        let capabilities = context.capabilities();
+        match self {
+            QueryTy::AnySamplesPassed => capabilities.supports((3, 3), "GL_ARB_occlusion_query2"),
+            QueryTy::SamplesPassed => true,
+            QueryTy::TimeElapsed | QueryTy::Timestamp => {
+                capabilities.supports((3, 3), "GL_ARB_timer_query")
+            },
+        }
+    }
+}
+
+pub struct Query {
+    context: Context,
+    ty: QueryTy,
+    pub gl_id: GLuint,
+    is_pending: bool,
+}
+
+impl Query {
+    pub fn new(context: &Context, ty: QueryTy) -> Result<Query, QueryError> {
+        if !ty.is_supported(context) {
+            return Err(QueryError {
+                message: format!("{:?} queries are not supported by this driver", ty),
+            });
+        }
+        let gl_id = unsafe {
+            let mut gl_id = 0;
+            gl::GenQueries(1, &mut gl_id);
+            if gl_id == 0 {
+                return Err(QueryError {
+                    message: "Failed to create GPU query".into(),
+                });
+            }
+            gl_id
+        };
+        context.check_errors("Query::new");
+        Ok(Query {
+            context: context.clone(),
+            ty,
+            gl_id,
+            is_pending: false,
+        })
+    }
+
+    pub fn ty(&self) -> QueryTy {
+        self.ty
+    }
+
+    pub fn set_label(&mut self, label: &str) {
+        set_object_label(&self.context, gl::QUERY, self.gl_id, label);
+    }
+
+    /// Starts measuring; the query ends when the returned guard is dropped.
+    /// Only one query of each type can be active at a time.
+    pub fn begin<'a>(&'a mut self) -> Result<ActiveQuery<'a>, QueryError> {
+        if self.ty == QueryTy::Timestamp {
+            return Err(QueryError {
+                message: "Timestamp queries are recorded with record_timestamp".into(),
+            });
+        }
+        unsafe {
+            gl::BeginQuery(self.ty.to_gl_target(), self.gl_id);
+        }
+        self.context.check_errors("Query::begin");
+        Ok(ActiveQuery {
+            query: self,
+        })
+    }
+
+    /// Records the GPU time once all previously issued commands complete.
+    pub fn record_timestamp(&mut self) -> Result<(), QueryError> {
+        if self.ty != QueryTy::Timestamp {
+            return Err(QueryError {
+                message: format!("Cannot record a timestamp with a {:?} query", self.ty),
+            });
+        }
+        unsafe {
+            gl::QueryCounter(self.gl_id, gl::TIMESTAMP);
+        }
+        self.context.check_errors("Query::record_timestamp");
+        self.is_pending = true;
+        Ok(())
+    }
+
+    /// Whether a result has been issued and not yet read.
+    pub fn is_pending(&self) -> bool {
+        self.is_pending
+    }
+
+    /// Returns the result if the GPU has produced it, without waiting.
+    /// Each result is returned once.
+    pub fn try_result(&mut self) -> Option<u64> {
+        if !self.is_pending {
+            return None;
+        }
+        let mut available = 0;
+        unsafe {
+            gl::GetQueryObjectiv(self.gl_id, gl::QUERY_RESULT_AVAILABLE, &mut available);
+        }
+        if available == 0 {
+            return None;
+        }
+        Some(self.read_result())
+    }
+
+    /// Waits for the GPU to produce the result. Stalls the pipeline.
+    pub fn wait_result(&mut self) -> Option<u64> {
+        if !self.is_pending {
+            return None;
+        }
+        Some(self.read_result())
+    }
+
+    fn read_result(&mut self) -> u64 {
+        let mut result = 0;
+        unsafe {
+            if gl::GetQueryObjectui64v::is_loaded() {
+                gl::GetQueryObjectui64v(self.gl_id, gl::QUERY_RESULT, &mut result);
+            } else {
+                let mut result_u32 = 0;
+                gl::GetQueryObjectuiv(self.gl_id, gl::QUERY_RESULT, &mut result_u32);
+                result = result_u32 as u64;
+            }
+        }
+        self.context.check_errors("Query::read_result");
+        self.is_pending = false;
+        result
+    }
+}
+
+impl Drop for Query {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteQueries(1, &self.gl_id);
+        }
+        self.gl_id = 0;
+    }
+}
+
+pub struct ActiveQuery<'a> {
+    query: &'a mut Query,
+}
+
+impl<'a> Drop for ActiveQuery<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::EndQuery(self.query.ty.to_gl_target());
+        }
+        self.query.is_pending = true;
+        self.query.context.check_errors("ActiveQuery::drop");
+    }
+}
+
+/// A few queries of one type used round-robin, so that a measurement can be
+/// issued every frame while results from earlier frames are read back without
+/// waiting on the GPU. With `size` queries, results arrive up to `size - 1`
+/// frames late; a result that is still not available when its query comes up
+/// for reuse is discarded.
+pub struct QueryRing {
+    queries: Vec<Query>,
+    next: usize,
+    latest_result: Option<u64>,
+}
+
+impl QueryRing {
+    pub fn new(context: &Context, ty: QueryTy, size: usize) -> Result<QueryRing, QueryError> {
+        if size == 0 {
+            return Err(QueryError {
+                message: "Query ring needs at least one query".into(),
+            });
+        }
+        let queries = (0..size)
+            .map(|_| Query::new(context, ty))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(QueryRing {
+            queries,
+            next: 0,
+            latest_result: None,
+        })
+    }
+
+    pub fn begin<'a>(&'a mut self) -> Result<ActiveQuery<'a>, QueryError> {
+        self.poll();
+        let index = self.advance();
+        self.queries[index].begin()
+    }
+
+    pub fn record_timestamp(&mut self) -> Result<(), QueryError> {
+        self.poll();
+        let index = self.advance();
+        self.queries[index].record_timestamp()
+    }
+
+    /// Reads every result that is available, oldest first, and returns the
+    /// most recent one seen so far. Never waits on the GPU.
+    pub fn poll(&mut self) -> Option<u64> {
+        let len = self.queries.len();
+        for i in 0..len {
+            if let Some(result) = self.queries[(self.next + i) % len].try_result() {
+                self.latest_result = Some(result);
+            }
+        }
+        self.latest_result
+    }
+
+    pub fn latest_result(&self) -> Option<u64> {
+        self.latest_result
+    }
+
+    fn advance(&mut self) -> usize {
+        let index = self.next;
+        self.next = (self.next + 1) % self.queries.len();
+        index
+    }
+}
+
+#[derive(Debug)]
+pub struct QueryError {
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for QueryError {}
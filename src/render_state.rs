@@ -0,0 +1,471 @@
+use gl;
+use gl::types::*;
+
+use capabilities::Capabilities;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendEquation {
+    Add,
+    Max,
+    Min,
+    ReverseSubtract,
+    Subtract,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendFactor {
+    ConstantAlpha,
+    ConstantColor,
+    DstAlpha,
+    DstColor,
+    One,
+    OneMinusConstantAlpha,
+    OneMinusConstantColor,
+    OneMinusDstAlpha,
+    OneMinusDstColor,
+    OneMinusSrcAlpha,
+    OneMinusSrcColor,
+    SrcAlpha,
+    SrcAlphaSaturate,
+    SrcColor,
+    Zero,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompareFunc {
+    Always,
+    Equal,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Never,
+    NotEqual,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StencilOp {
+    Decr,
+    DecrWrap,
+    Incr,
+    IncrWrap,
+    Invert,
+    Keep,
+    Replace,
+    Zero,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CullFace {
+    Back,
+    Front,
+    FrontAndBack,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrontFace {
+    Ccw,
+    Cw,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PolygonMode {
+    Fill,
+    Line,
+    Point,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlendState {
+    pub color_equation: BlendEquation,
+    pub alpha_equation: BlendEquation,
+    pub src_color: BlendFactor,
+    pub dst_color: BlendFactor,
+    pub src_alpha: BlendFactor,
+    pub dst_alpha: BlendFactor,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StencilFaceState {
+    pub func: CompareFunc,
+    pub reference: i32,
+    pub read_mask: u32,
+    pub write_mask: u32,
+    pub stencil_fail: StencilOp,
+    pub depth_fail: StencilOp,
+    pub pass: StencilOp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StencilState {
+    pub front: StencilFaceState,
+    pub back: StencilFaceState,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Fixed-function state for a draw. `None` disables the corresponding test,
+/// except for `viewport` where it leaves the current viewport untouched.
+///
+/// `blend` holds one entry per draw buffer. A single entry applies to every
+/// draw buffer; more than one needs GL 4.0 or ARB_draw_buffers_blend, and
+/// without it only the first entry is applied, to every draw buffer. Draw
+/// buffers past the last entry have blending disabled.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderState {
+    pub blend: Vec<Option<BlendState>>,
+    pub blend_color: [f32; 4],
+    pub depth_test: Option<CompareFunc>,
+    pub depth_write: bool,
+    pub stencil: Option<StencilState>,
+    pub cull_face: Option<CullFace>,
+    pub front_face: FrontFace,
+    pub polygon_mode: PolygonMode,
+    pub color_mask: [bool; 4],
+    pub scissor: Option<Rect>,
+    pub viewport: Option<Rect>,
+}
+
+/// Tracks the state last applied to the context so that `apply` only issues
+/// GL calls for what changed.
+pub struct RenderStateCache {
+    current: RenderState,
+    is_valid: bool,
+}
+
+impl BlendEquation {
+    pub fn to_gl_blend_equation(self) -> GLenum {
+        match self {
+            BlendEquation::Add => gl::FUNC_ADD,
+            BlendEquation::Max => gl::MAX,
+            BlendEquation::Min => gl::MIN,
+            BlendEquation::ReverseSubtract => gl::FUNC_REVERSE_SUBTRACT,
+            BlendEquation::Subtract => gl::FUNC_SUBTRACT,
+        }
+    }
+}
+
+impl BlendFactor {
+    pub fn to_gl_blend_factor(self) -> GLenum {
+        match self {
+            BlendFactor::ConstantAlpha => gl::CONSTANT_ALPHA,
+            BlendFactor::ConstantColor => gl::CONSTANT_COLOR,
+            BlendFactor::DstAlpha => gl::DST_ALPHA,
+            BlendFactor::DstColor => gl::DST_COLOR,
+            BlendFactor::One => gl::ONE,
+            BlendFactor::OneMinusConstantAlpha => gl::ONE_MINUS_CONSTANT_ALPHA,
+            BlendFactor::OneMinusConstantColor => gl::ONE_MINUS_CONSTANT_COLOR,
+            BlendFactor::OneMinusDstAlpha => gl::ONE_MINUS_DST_ALPHA,
+            BlendFactor::OneMinusDstColor => gl::ONE_MINUS_DST_COLOR,
+            BlendFactor::OneMinusSrcAlpha => gl::ONE_MINUS_SRC_ALPHA,
+            BlendFactor::OneMinusSrcColor => gl::ONE_MINUS_SRC_COLOR,
+            BlendFactor::SrcAlpha => gl::SRC_ALPHA,
+            BlendFactor::SrcAlphaSaturate => gl::SRC_ALPHA_SATURATE,
+            BlendFactor::SrcColor => gl::SRC_COLOR,
+            BlendFactor::Zero => gl::ZERO,
+        }
+    }
+}
+
+impl CompareFunc {
+    pub fn to_gl_compare_func(self) -> GLenum {
+        match self {
+            CompareFunc::Always => gl::ALWAYS,
+            CompareFunc::Equal => gl::EQUAL,
+            CompareFunc::Greater => gl::GREATER,
+            CompareFunc::GreaterEqual => gl::GEQUAL,
+            CompareFunc::Less => gl::LESS,
+            CompareFunc::LessEqual => gl::LEQUAL,
+            CompareFunc::Never => gl::NEVER,
+            CompareFunc::NotEqual => gl::NOTEQUAL,
+        }
+    }
+}
+
+impl StencilOp {
+    pub fn to_gl_stencil_op(self) -> GLenum {
+        match self {
+            StencilOp::Decr => gl::DECR,
+            StencilOp::DecrWrap => gl::DECR_WRAP,
+            StencilOp::Incr => gl::INCR,
+            StencilOp::IncrWrap => gl::INCR_WRAP,
+            StencilOp::Invert => gl::INVERT,
+            StencilOp::Keep => gl::KEEP,
+            StencilOp::Replace => gl::REPLACE,
+            StencilOp::Zero => gl::ZERO,
+        }
+    }
+}
+
+impl CullFace {
+    pub fn to_gl_cull_face(self) -> GLenum {
+        match self {
+            CullFace::Back => gl::BACK,
+            CullFace::Front => gl::FRONT,
+            CullFace::FrontAndBack => gl::FRONT_AND_BACK,
+        }
+    }
+}
+
+impl FrontFace {
+    pub fn to_gl_front_face(self) -> GLenum {
+        match self {
+            FrontFace::Ccw => gl::CCW,
+            FrontFace::Cw => gl::CW,
+        }
+    }
+}
+
+impl PolygonMode {
+    pub fn to_gl_polygon_mode(self) -> GLenum {
+        match self {
+            PolygonMode::Fill => gl::FILL,
+            PolygonMode::Line => gl::LINE,
+            PolygonMode::Point => gl::POINT,
+        }
+    }
+}
+
+impl BlendState {
+    pub fn alpha_blending() -> Self {
+        BlendState {
+            color_equation: BlendEquation::Add,
+            alpha_equation: BlendEquation::Add,
+            src_color: BlendFactor::SrcAlpha,
+            dst_color: BlendFactor::OneMinusSrcAlpha,
+            src_alpha: BlendFactor::One,
+            dst_alpha: BlendFactor::OneMinusSrcAlpha,
+        }
+    }
+
+    pub fn additive() -> Self {
+        BlendState {
+            color_equation: BlendEquation::Add,
+            alpha_equation: BlendEquation::Add,
+            src_color: BlendFactor::One,
+            dst_color: BlendFactor::One,
+            src_alpha: BlendFactor::One,
+            dst_alpha: BlendFactor::One,
+        }
+    }
+}
+
+impl Default for StencilFaceState {
+    fn default() -> Self {
+        StencilFaceState {
+            func: CompareFunc::Always,
+            reference: 0,
+            read_mask: !0,
+            write_mask: !0,
+            stencil_fail: StencilOp::Keep,
+            depth_fail: StencilOp::Keep,
+            pass: StencilOp::Keep,
+        }
+    }
+}
+
+/// Matches the initial state of a fresh GL context.
+impl Default for RenderState {
+    fn default() -> Self {
+        RenderState {
+            blend: vec![None],
+            blend_color: [0.0; 4],
+            depth_test: None,
+            depth_write: true,
+            stencil: None,
+            cull_face: None,
+            front_face: FrontFace::Ccw,
+            polygon_mode: PolygonMode::Fill,
+            color_mask: [true; 4],
+            scissor: None,
+            viewport: None,
+        }
+    }
+}
+
+impl Default for RenderStateCache {
+    fn default() -> Self {
+        RenderStateCache::new()
+    }
+}
+
+impl RenderStateCache {
+    pub fn new() -> Self {
+        RenderStateCache {
+            current: RenderState::default(),
+            is_valid: false,
+        }
+    }
+
+    pub fn current(&self) -> &RenderState {
+        &self.current
+    }
+
+    /// Forces the next `apply` to set every piece of state, e.g. after code
+    /// outside this cache changed GL state directly.
+    pub fn invalidate(&mut self) {
+        self.is_valid = false;
+    }
+
+    /// Sets the viewport outside of a full `apply`, e.g. after a resize.
+    pub fn set_viewport(&mut self, rect: Rect) {
+        if !self.is_valid || self.current.viewport != Some(rect) {
+            unsafe {
+                gl::Viewport(rect.x, rect.y, rect.width, rect.height);
+            }
+            self.current.viewport = Some(rect);
+        }
+    }
+
+    pub fn apply(&mut self, state: &RenderState, capabilities: &Capabilities) {
+        let force = !self.is_valid;
+        let current = &self.current;
+        unsafe {
+            if force || state.blend != current.blend {
+                // After a reset any draw buffer may still be blending.
+                let previous_len = if force {
+                    capabilities.max_draw_buffers as usize
+                } else {
+                    current.blend.len()
+                };
+                apply_blend(&state.blend, previous_len, capabilities);
+            }
+            if force || state.blend_color != current.blend_color {
+                let [r, g, b, a] = state.blend_color;
+                gl::BlendColor(r, g, b, a);
+            }
+            if force || state.depth_test != current.depth_test {
+                match state.depth_test {
+                    Some(func) => {
+                        gl::Enable(gl::DEPTH_TEST);
+                        gl::DepthFunc(func.to_gl_compare_func());
+                    },
+                    None => gl::Disable(gl::DEPTH_TEST),
+                }
+            }
+            if force || state.depth_write != current.depth_write {
+                gl::DepthMask(state.depth_write as _);
+            }
+            if force || state.stencil != current.stencil {
+                match state.stencil {
+                    Some(ref stencil) => {
+                        gl::Enable(gl::STENCIL_TEST);
+                        apply_stencil_face(gl::FRONT, &stencil.front);
+                        apply_stencil_face(gl::BACK, &stencil.back);
+                    },
+                    None => gl::Disable(gl::STENCIL_TEST),
+                }
+            }
+            if force || state.cull_face != current.cull_face {
+                match state.cull_face {
+                    Some(cull_face) => {
+                        gl::Enable(gl::CULL_FACE);
+                        gl::CullFace(cull_face.to_gl_cull_face());
+                    },
+                    None => gl::Disable(gl::CULL_FACE),
+                }
+            }
+            if force || state.front_face != current.front_face {
+                gl::FrontFace(state.front_face.to_gl_front_face());
+            }
+            if force || state.polygon_mode != current.polygon_mode {
+                gl::PolygonMode(gl::FRONT_AND_BACK, state.polygon_mode.to_gl_polygon_mode());
+            }
+            if force || state.color_mask != current.color_mask {
+                let [r, g, b, a] = state.color_mask;
+                gl::ColorMask(r as _, g as _, b as _, a as _);
+            }
+            if force || state.scissor != current.scissor {
+                match state.scissor {
+                    Some(rect) => {
+                        gl::Enable(gl::SCISSOR_TEST);
+                        gl::Scissor(rect.x, rect.y, rect.width, rect.height);
+                    },
+                    None => gl::Disable(gl::SCISSOR_TEST),
+                }
+            }
+            if let Some(rect) = state.viewport {
+                if force || state.viewport != current.viewport {
+                    gl::Viewport(rect.x, rect.y, rect.width, rect.height);
+                }
+            }
+        }
+        let viewport = state.viewport.or(self.current.viewport);
+        self.current.clone_from(state);
+        self.current.viewport = viewport;
+        self.is_valid = true;
+    }
+}
+
+/// Non-indexed calls set every draw buffer at once, so they also clear what
+/// an earlier indexed state left behind. Indexed ones disable the draw
+/// buffers from the end of `blend` up to the `previous_len` that may still
+/// be blending.
+unsafe fn apply_blend(blend: &[Option<BlendState>], previous_len: usize, capabilities: &Capabilities) {
+    let is_indexed = blend.len() > 1 && capabilities.supports((4, 0), "GL_ARB_draw_buffers_blend");
+    if !is_indexed {
+        match blend.first().and_then(|blend| *blend) {
+            Some(blend) => {
+                gl::Enable(gl::BLEND);
+                gl::BlendEquationSeparate(
+                    blend.color_equation.to_gl_blend_equation(),
+                    blend.alpha_equation.to_gl_blend_equation(),
+                );
+                gl::BlendFuncSeparate(
+                    blend.src_color.to_gl_blend_factor(),
+                    blend.dst_color.to_gl_blend_factor(),
+                    blend.src_alpha.to_gl_blend_factor(),
+                    blend.dst_alpha.to_gl_blend_factor(),
+                );
+            },
+            None => gl::Disable(gl::BLEND),
+        }
+        return;
+    }
+    for (i, attachment_blend) in blend.iter().enumerate() {
+        let i = i as GLuint;
+        match *attachment_blend {
+            Some(blend) => {
+                gl::Enablei(gl::BLEND, i);
+                gl::BlendEquationSeparatei(
+                    i,
+                    blend.color_equation.to_gl_blend_equation(),
+                    blend.alpha_equation.to_gl_blend_equation(),
+                );
+                gl::BlendFuncSeparatei(
+                    i,
+                    blend.src_color.to_gl_blend_factor(),
+                    blend.dst_color.to_gl_blend_factor(),
+                    blend.src_alpha.to_gl_blend_factor(),
+                    blend.dst_alpha.to_gl_blend_factor(),
+                );
+            },
+            None => gl::Disablei(gl::BLEND, i),
+        }
+    }
+    for i in blend.len()..previous_len {
+        gl::Disablei(gl::BLEND, i as GLuint);
+    }
+}
+
+unsafe fn apply_stencil_face(face: GLenum, stencil: &StencilFaceState) {
+    gl::StencilFuncSeparate(
+        face,
+        stencil.func.to_gl_compare_func(),
+        stencil.reference,
+        stencil.read_mask,
+    );
+    gl::StencilOpSeparate(
+        face,
+        stencil.stencil_fail.to_gl_stencil_op(),
+        stencil.depth_fail.to_gl_stencil_op(),
+        stencil.pass.to_gl_stencil_op(),
+    );
+    gl::StencilMaskSeparate(face, stencil.write_mask);
+}
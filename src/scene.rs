@@ -0,0 +1,419 @@
+//! A hierarchy of transformed nodes. Each node may carry an item, typically
+//! a mesh and material, which `Scene::render` hands to a `SceneRenderer`
+//! along with the node's world matrix.
+
+use cgmath::{Matrix4, One, Quaternion, Vector3, Zero};
+use std;
+use std::cell::Cell;
+use std::fmt;
+
+/// Translation, rotation and scale relative to the parent node, applied in
+/// reverse order: scale first, then rotation, then translation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+/// Refers to a node of one `Scene`. Stays invalid once the node is removed,
+/// even if its slot is reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    index: usize,
+    generation: u32,
+}
+
+/// Receives each item of a scene with its node's world matrix. Implemented
+/// for closures.
+pub trait SceneRenderer<Item> {
+    fn submit(&mut self, node: NodeId, world: &Matrix4<f32>, item: &Item);
+}
+
+pub struct Scene<Item> {
+    slots: Vec<Slot<Item>>,
+    free_slots: Vec<usize>,
+    roots: Vec<NodeId>,
+}
+
+#[derive(Debug)]
+pub struct SceneError {
+    pub message: String,
+}
+
+struct Slot<Item> {
+    generation: u32,
+    node: Option<Node<Item>>,
+}
+
+struct Node<Item> {
+    transform: Transform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    item: Option<Item>,
+    world: Cell<Matrix4<f32>>,
+    /// Set on the node and all its descendants whenever a transform above
+    /// them changes, so a clean node always has clean ancestors.
+    is_world_dirty: Cell<bool>,
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Transform {
+            translation: Vector3::zero(),
+            rotation: Quaternion::one(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn from_translation(translation: Vector3<f32>) -> Self {
+        Transform {
+            translation,
+            ..Transform::identity()
+        }
+    }
+
+    pub fn from_rotation(rotation: Quaternion<f32>) -> Self {
+        Transform {
+            rotation,
+            ..Transform::identity()
+        }
+    }
+
+    pub fn from_scale(scale: f32) -> Self {
+        Transform {
+            scale: Vector3::new(scale, scale, scale),
+            ..Transform::identity()
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+impl<Item, F: FnMut(NodeId, &Matrix4<f32>, &Item)> SceneRenderer<Item> for F {
+    fn submit(&mut self, node: NodeId, world: &Matrix4<f32>, item: &Item) {
+        self(node, world, item)
+    }
+}
+
+impl<Item> Scene<Item> {
+    pub fn new() -> Self {
+        Scene {
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            roots: Vec::new(),
+        }
+    }
+
+    /// Adds a node under `parent`, or as a root if `parent` is `None`.
+    pub fn add(
+        &mut self,
+        parent: Option<NodeId>,
+        transform: Transform,
+        item: Option<Item>,
+    ) -> Result<NodeId, SceneError> {
+        if let Some(parent) = parent {
+            self.node(parent).ok_or_else(|| invalid_node(parent))?;
+        }
+        let node = Node {
+            transform,
+            parent,
+            children: Vec::new(),
+            item,
+            world: Cell::new(Matrix4::one()),
+            is_world_dirty: Cell::new(true),
+        };
+        let id = match self.free_slots.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index];
+                slot.node = Some(node);
+                NodeId {
+                    index,
+                    generation: slot.generation,
+                }
+            },
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    node: Some(node),
+                });
+                NodeId {
+                    index: self.slots.len() - 1,
+                    generation: 0,
+                }
+            },
+        };
+        self.children_of_mut(parent).push(id);
+        Ok(id)
+    }
+
+    /// Removes `id` and all its descendants.
+    pub fn remove(&mut self, id: NodeId) -> Result<(), SceneError> {
+        let parent = self.node(id).ok_or_else(|| invalid_node(id))?.parent;
+        self.children_of_mut(parent).retain(|&child| child != id);
+        let mut pending = vec![id];
+        while let Some(id) = pending.pop() {
+            let slot = &mut self.slots[id.index];
+            let node = slot.node.take().unwrap();
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free_slots.push(id.index);
+            pending.extend(node.children);
+        }
+        Ok(())
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.node(id).is_some()
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).and_then(|node| node.parent)
+    }
+
+    /// Empty for removed nodes.
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        self.node(id).map_or(&[], |node| &node.children)
+    }
+
+    /// Moves `id` under `parent`, or to the roots, keeping its local
+    /// transform.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> Result<(), SceneError> {
+        let old_parent = self.node(id).ok_or_else(|| invalid_node(id))?.parent;
+        if let Some(parent) = parent {
+            self.node(parent).ok_or_else(|| invalid_node(parent))?;
+            let mut ancestor = Some(parent);
+            while let Some(current) = ancestor {
+                if current == id {
+                    return Err(SceneError {
+                        message: format!("Cannot move {:?} under its own descendant {:?}", id, parent),
+                    });
+                }
+                ancestor = self.parent(current);
+            }
+        }
+        self.children_of_mut(old_parent).retain(|&child| child != id);
+        self.children_of_mut(parent).push(id);
+        self.node_mut(id).unwrap().parent = parent;
+        self.mark_world_dirty(id);
+        Ok(())
+    }
+
+    pub fn transform(&self, id: NodeId) -> Option<&Transform> {
+        self.node(id).map(|node| &node.transform)
+    }
+
+    pub fn set_transform(&mut self, id: NodeId, transform: Transform) -> Result<(), SceneError> {
+        self.node_mut(id).ok_or_else(|| invalid_node(id))?.transform = transform;
+        self.mark_world_dirty(id);
+        Ok(())
+    }
+
+    pub fn item(&self, id: NodeId) -> Option<&Item> {
+        self.node(id).and_then(|node| node.item.as_ref())
+    }
+
+    pub fn item_mut(&mut self, id: NodeId) -> Option<&mut Item> {
+        self.node_mut(id).and_then(|node| node.item.as_mut())
+    }
+
+    /// Returns the previous item.
+    pub fn set_item(&mut self, id: NodeId, item: Option<Item>) -> Result<Option<Item>, SceneError> {
+        let node = self.node_mut(id).ok_or_else(|| invalid_node(id))?;
+        Ok(std::mem::replace(&mut node.item, item))
+    }
+
+    /// Recomputes the matrix only if a transform on the path from the root
+    /// changed since it was last computed.
+    pub fn world_matrix(&self, id: NodeId) -> Option<Matrix4<f32>> {
+        let node = self.node(id)?;
+        if node.is_world_dirty.get() {
+            let parent_world = match node.parent {
+                Some(parent) => self.world_matrix(parent).unwrap(),
+                None => Matrix4::one(),
+            };
+            node.world.set(parent_world * node.transform.matrix());
+            node.is_world_dirty.set(false);
+        }
+        Some(node.world.get())
+    }
+
+    /// Submits every node with an item, parents before children, updating
+    /// stale world matrices on the way down.
+    pub fn render<R: SceneRenderer<Item>>(&self, renderer: &mut R) {
+        let mut pending: Vec<(NodeId, Matrix4<f32>)> =
+            self.roots.iter().rev().map(|&root| (root, Matrix4::one())).collect();
+        while let Some((id, parent_world)) = pending.pop() {
+            let node = self.node(id).unwrap();
+            if node.is_world_dirty.get() {
+                node.world.set(parent_world * node.transform.matrix());
+                node.is_world_dirty.set(false);
+            }
+            let world = node.world.get();
+            if let Some(ref item) = node.item {
+                renderer.submit(id, &world, item);
+            }
+            pending.extend(node.children.iter().rev().map(|&child| (child, world)));
+        }
+    }
+
+    fn node(&self, id: NodeId) -> Option<&Node<Item>> {
+        self.slots
+            .get(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_ref())
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> Option<&mut Node<Item>> {
+        self.slots
+            .get_mut(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_mut())
+    }
+
+    /// The child list of `parent`, or the roots. `parent` must be valid.
+    fn children_of_mut(&mut self, parent: Option<NodeId>) -> &mut Vec<NodeId> {
+        match parent {
+            Some(parent) => &mut self.node_mut(parent).unwrap().children,
+            None => &mut self.roots,
+        }
+    }
+
+    fn mark_world_dirty(&self, id: NodeId) {
+        let node = self.node(id).unwrap();
+        node.is_world_dirty.set(true);
+        let mut pending = node.children.clone();
+        while let Some(id) = pending.pop() {
+            let node = self.node(id).unwrap();
+            // The descendants of a node that was already dirty are too.
+            if !node.is_world_dirty.replace(true) {
+                pending.extend(node.children.iter().cloned());
+            }
+        }
+    }
+}
+
+impl<Item> Default for Scene<Item> {
+    fn default() -> Self {
+        Scene::new()
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+fn invalid_node(id: NodeId) -> SceneError {
+    SceneError {
+        message: format!("{:?} is not in the scene", id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translation(x: f32, y: f32, z: f32) -> Transform {
+        Transform::from_translation(Vector3::new(x, y, z))
+    }
+
+    fn world_translation(scene: &Scene<()>, id: NodeId) -> Vector3<f32> {
+        scene.world_matrix(id).unwrap().w.truncate()
+    }
+
+    #[test]
+    fn world_matrices_follow_changes_above() {
+        let mut scene = Scene::new();
+        let a = scene.add(None, translation(1.0, 0.0, 0.0), None).unwrap();
+        let b = scene.add(None, Transform::from_scale(2.0), None).unwrap();
+        let child = scene.add(Some(a), translation(0.0, 1.0, 0.0), None).unwrap();
+        let grandchild = scene.add(Some(child), translation(0.0, 0.0, 1.0), None).unwrap();
+        assert_eq!(world_translation(&scene, grandchild), Vector3::new(1.0, 1.0, 1.0));
+        scene.set_transform(a, translation(5.0, 0.0, 0.0)).unwrap();
+        assert_eq!(world_translation(&scene, grandchild), Vector3::new(5.0, 1.0, 1.0));
+        // Reparenting the subtree moves it under the new parent's transform.
+        scene.set_parent(child, Some(b)).unwrap();
+        assert_eq!(scene.parent(child), Some(b));
+        assert_eq!(scene.children(a), &[]);
+        assert_eq!(scene.children(b), &[child]);
+        assert_eq!(world_translation(&scene, child), Vector3::new(0.0, 2.0, 0.0));
+        assert_eq!(world_translation(&scene, grandchild), Vector3::new(0.0, 2.0, 2.0));
+        scene.set_parent(child, None).unwrap();
+        assert_eq!(scene.roots(), &[a, b, child]);
+        assert_eq!(world_translation(&scene, grandchild), Vector3::new(0.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn render_matches_world_matrix() {
+        let mut scene = Scene::new();
+        let root = scene.add(None, translation(1.0, 0.0, 0.0), Some(1)).unwrap();
+        let child = scene.add(Some(root), translation(0.0, 1.0, 0.0), Some(2)).unwrap();
+        scene.set_transform(root, translation(3.0, 0.0, 0.0)).unwrap();
+        let mut submitted = Vec::new();
+        scene.render(&mut |id, world: &Matrix4<f32>, item: &u32| submitted.push((id, *world, *item)));
+        assert_eq!(submitted.len(), 2);
+        assert_eq!((submitted[0].0, submitted[0].2), (root, 1));
+        assert_eq!((submitted[1].0, submitted[1].2), (child, 2));
+        assert_eq!(submitted[1].1.w.truncate(), Vector3::new(3.0, 1.0, 0.0));
+        assert_eq!(scene.world_matrix(child), Some(submitted[1].1));
+    }
+
+    #[test]
+    fn cannot_move_a_node_under_itself() {
+        let mut scene: Scene<()> = Scene::new();
+        let a = scene.add(None, Transform::identity(), None).unwrap();
+        let child = scene.add(Some(a), Transform::identity(), None).unwrap();
+        let grandchild = scene.add(Some(child), Transform::identity(), None).unwrap();
+        assert!(scene.set_parent(a, Some(grandchild)).is_err());
+        assert!(scene.set_parent(a, Some(a)).is_err());
+        assert_eq!(scene.parent(a), None);
+        assert_eq!(scene.children(grandchild), &[]);
+        assert_eq!(scene.roots(), &[a]);
+    }
+
+    #[test]
+    fn removed_ids_stay_invalid_when_slots_are_reused() {
+        let mut scene = Scene::new();
+        let a = scene.add(None, Transform::identity(), Some(1)).unwrap();
+        let child = scene.add(Some(a), Transform::identity(), Some(2)).unwrap();
+        scene.remove(a).unwrap();
+        assert!(!scene.contains(a));
+        assert!(!scene.contains(child));
+        assert!(scene.roots().is_empty());
+        let b = scene.add(None, Transform::identity(), Some(3)).unwrap();
+        let c = scene.add(None, Transform::identity(), Some(4)).unwrap();
+        // Both freed slots are reused, so the old ids share an index with
+        // live nodes.
+        assert!(b.index == a.index || c.index == a.index);
+        assert!(b.index == child.index || c.index == child.index);
+        for &stale in &[a, child] {
+            assert!(!scene.contains(stale));
+            assert_eq!(scene.item(stale), None);
+            assert_eq!(scene.world_matrix(stale), None);
+            assert!(scene.set_transform(stale, Transform::identity()).is_err());
+            assert!(scene.set_parent(stale, None).is_err());
+            assert!(scene.set_parent(b, Some(stale)).is_err());
+            assert!(scene.add(Some(stale), Transform::identity(), None).is_err());
+            assert!(scene.remove(stale).is_err());
+        }
+        assert_eq!(scene.item(b), Some(&3));
+        assert_eq!(scene.item(c), Some(&4));
+    }
+}
@@ -1,13 +1,23 @@
 use gl;
 use gl::types::*;
 use std;
-use std::fs::File;
-use std::io::prelude::*;
+use std::fmt;
+use std::io;
 use std::path::Path;
 
+use capabilities::Capabilities;
+use context::Context;
+use debug::set_object_label;
+use diagnostic::{parse_info_log, Diagnostic};
+use preprocessor::{PreprocessedSource, PreprocessorError, ShaderPreprocessor};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ShaderTy {
+    Compute,
     Fragment,
     Geometry,
+    TessControl,
+    TessEvaluation,
     Vertex,
 }
 
@@ -15,76 +25,165 @@ pub enum ShaderTy {
 pub struct ShaderError {
     pub message: String,
     pub info_log: Option<String>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub io_error: Option<io::Error>,
 }
 
 pub struct Shader {
+    context: Context,
     pub ty: ShaderTy,
     pub gl_id: GLuint,
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl ShaderTy {
+    pub fn from_stage_name(name: &str) -> Option<Self> {
+        match name {
+            "compute" => Some(ShaderTy::Compute),
+            "fragment" => Some(ShaderTy::Fragment),
+            "geometry" => Some(ShaderTy::Geometry),
+            "tess_control" => Some(ShaderTy::TessControl),
+            "tess_evaluation" => Some(ShaderTy::TessEvaluation),
+            "vertex" => Some(ShaderTy::Vertex),
+            _ => None,
+        }
+    }
+
+    /// Tessellation needs GL 4.0 and compute needs GL 4.3 unless the driver
+    /// exposes the matching ARB extension.
+    pub fn is_supported(&self, capabilities: &Capabilities) -> bool {
+        match *self {
+            ShaderTy::Compute => capabilities.supports((4, 3), "GL_ARB_compute_shader"),
+            ShaderTy::TessControl | ShaderTy::TessEvaluation => {
+                capabilities.supports((4, 0), "GL_ARB_tessellation_shader")
+            },
+            _ => true,
+        }
+    }
+
     pub fn to_gl_shader_ty(&self) -> GLenum {
         match *self {
+            ShaderTy::Compute => gl::COMPUTE_SHADER,
             ShaderTy::Fragment => gl::FRAGMENT_SHADER,
             ShaderTy::Geometry => gl::GEOMETRY_SHADER,
+            ShaderTy::TessControl => gl::TESS_CONTROL_SHADER,
+            ShaderTy::TessEvaluation => gl::TESS_EVALUATION_SHADER,
             ShaderTy::Vertex => gl::VERTEX_SHADER,
         }
     }
 }
 
+impl From<PreprocessorError> for ShaderError {
+    fn from(err: PreprocessorError) -> Self {
+        ShaderError {
+            message: err.message,
+            info_log: None,
+            diagnostics: Vec::new(),
+            io_error: err.io_error,
+        }
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        for diagnostic in &self.diagnostics {
+            write!(f, "\n{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ShaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.io_error.as_ref().map(|err| err as _)
+    }
+}
+
 impl Shader {
-    pub fn from_file(ty: ShaderTy, path: &Path) -> Result<Self, ShaderError> {
-        let mut file = File::open(path).map_err(|_| {
-            ShaderError {
-                message: format!("Failed to open file {:?}", path),
-                info_log: None,
-            }
-        })?;
-        let mut source = String::new();
-        file.read_to_string(&mut source).map_err(|_| {
-            ShaderError {
-                message: format!("Failed to read file {:?}", path),
-                info_log: None,
-            }
-        })?;
-        Shader::from_source(ty, &source)
+    pub fn from_file(context: &Context, ty: ShaderTy, path: &Path) -> Result<Self, ShaderError> {
+        let preprocessed = ShaderPreprocessor::new().preprocess_file(path)?;
+        Shader::from_preprocessed(context, ty, &preprocessed)
+    }
+
+    pub fn from_source(context: &Context, ty: ShaderTy, source: &str) -> Result<Self, ShaderError> {
+        let preprocessed = ShaderPreprocessor::new().preprocess_source(source)?;
+        Shader::from_preprocessed(context, ty, &preprocessed)
     }
 
-    pub fn from_source(ty: ShaderTy, source: &str) -> Result<Self, ShaderError> {
-        let gl_id = unsafe {
+    pub fn from_preprocessed(
+        context: &Context,
+        ty: ShaderTy,
+        preprocessed: &PreprocessedSource,
+    ) -> Result<Self, ShaderError> {
+        if !ty.is_supported(context.capabilities()) {
+            return Err(ShaderError {
+                message: format!("{:?} shaders are not supported by this context", ty),
+                info_log: None,
+                diagnostics: Vec::new(),
+                io_error: None,
+            });
+        }
+        let source = &preprocessed.source;
+        let (gl_id, diagnostics) = unsafe {
             let gl_id = gl::CreateShader(ty.to_gl_shader_ty());
             if gl_id == 0 {
                 return Err(ShaderError {
                     message: "Failed to create GPU shader".into(),
                     info_log: None,
+                    diagnostics: Vec::new(),
+                    io_error: None,
                 });
             }
             gl::ShaderSource(gl_id, 1, &(source.as_ptr() as *const _), &(source.len() as GLint));
             gl::CompileShader(gl_id);
             let mut is_compiled = 0;
             gl::GetShaderiv(gl_id, gl::COMPILE_STATUS, &mut is_compiled);
+            let info_log = get_shader_info_log(gl_id);
+            let diagnostics = info_log
+                .as_ref()
+                .map(|log| parse_info_log(log, Some(preprocessed)))
+                .unwrap_or_default();
             if is_compiled == 0 {
-                let mut log_len = 0;
-                gl::GetShaderiv(gl_id, gl::INFO_LOG_LENGTH, &mut log_len);
-                let mut log_buf = vec![0; log_len as usize];
-                gl::GetShaderInfoLog(
-                    gl_id,
-                    log_len,
-                    std::ptr::null_mut(),
-                    log_buf.as_mut_ptr() as *mut _,
-                );
+                gl::DeleteShader(gl_id);
                 return Err(ShaderError {
                     message: "Failed to compile shader".into(),
-                    info_log: String::from_utf8(log_buf).ok(),
+                    info_log: info_log.map(|log| preprocessed.line_map.rewrite_info_log(&log)),
+                    diagnostics,
+                    io_error: None,
                 });
             }
-            gl_id
+            (gl_id, diagnostics)
         };
+        context.check_errors("Shader::from_preprocessed");
         Ok(Shader {
-            ty: ty,
-            gl_id: gl_id,
+            context: context.clone(),
+            ty,
+            gl_id,
+            diagnostics,
         })
     }
+
+    pub fn set_label(&mut self, label: &str) {
+        set_object_label(&self.context, gl::SHADER, self.gl_id, label);
+    }
+}
+
+unsafe fn get_shader_info_log(gl_id: GLuint) -> Option<String> {
+    let mut log_len = 0;
+    gl::GetShaderiv(gl_id, gl::INFO_LOG_LENGTH, &mut log_len);
+    if log_len <= 1 {
+        return None;
+    }
+    let mut log_buf = vec![0; log_len as usize];
+    gl::GetShaderInfoLog(
+        gl_id,
+        log_len,
+        std::ptr::null_mut(),
+        log_buf.as_mut_ptr() as *mut _,
+    );
+    log_buf.pop();
+    String::from_utf8(log_buf).ok()
 }
 
 impl Drop for Shader {
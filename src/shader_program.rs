@@ -2,17 +2,56 @@ use gl;
 use gl::types::*;
 use std;
 use std::ffi::CString;
+use std::fmt;
+use std::path::Path;
 
-use shader::Shader;
+use buffer::VertexBuffer;
+use context::Context;
+use debug::set_object_label;
+use diagnostic::{parse_info_log, Diagnostic};
+use preprocessor::{PreprocessorError, ShaderPreprocessor};
+use shader::{Shader, ShaderError};
+use transform_feedback::{TransformFeedback, TransformFeedbackMode, TransformFeedbackPrimitive};
 
 #[derive(Debug)]
 pub struct ShaderProgramError {
     pub message: String,
     pub info_log: Option<String>,
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 pub struct ShaderProgram {
+    context: Context,
     pub gl_id: GLuint,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+pub enum MemoryBarrier {
+    All,
+    BufferUpdate,
+    Command,
+    ElementArray,
+    ShaderImageAccess,
+    ShaderStorage,
+    TextureFetch,
+    TextureUpdate,
+    Uniform,
+    VertexAttribArray,
+}
+
+#[derive(Default)]
+pub struct LinkOptions {
+    pub binary_retrievable: bool,
+    pub transform_feedback_varyings: Vec<String>,
+    pub transform_feedback_mode: TransformFeedbackMode,
+    /// Fragment shader outputs and the draw buffer each one writes to, for
+    /// shaders that do not use `layout(location = N)`.
+    pub frag_data_locations: Vec<(String, u32)>,
+}
+
+pub struct ProgramBinary {
+    pub format: GLenum,
+    pub data: Vec<u8>,
 }
 
 pub type UniformLocation = Option<u32>;
@@ -21,15 +60,152 @@ pub struct ActivatedShaderProgram<'a> {
     program: &'a mut ShaderProgram,
 }
 
+impl MemoryBarrier {
+    pub fn to_gl_barrier_bits(&self) -> GLbitfield {
+        match *self {
+            MemoryBarrier::All => gl::ALL_BARRIER_BITS,
+            MemoryBarrier::BufferUpdate => gl::BUFFER_UPDATE_BARRIER_BIT,
+            MemoryBarrier::Command => gl::COMMAND_BARRIER_BIT,
+            MemoryBarrier::ElementArray => gl::ELEMENT_ARRAY_BARRIER_BIT,
+            MemoryBarrier::ShaderImageAccess => gl::SHADER_IMAGE_ACCESS_BARRIER_BIT,
+            MemoryBarrier::ShaderStorage => gl::SHADER_STORAGE_BARRIER_BIT,
+            MemoryBarrier::TextureFetch => gl::TEXTURE_FETCH_BARRIER_BIT,
+            MemoryBarrier::TextureUpdate => gl::TEXTURE_UPDATE_BARRIER_BIT,
+            MemoryBarrier::Uniform => gl::UNIFORM_BARRIER_BIT,
+            MemoryBarrier::VertexAttribArray => gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT,
+        }
+    }
+}
+
+/// Orders incoherent writes from compute or image stores before the listed
+/// kinds of reads. Does nothing without GL 4.2 or ARB_shader_image_load_store.
+pub fn memory_barrier(context: &Context, barriers: &[MemoryBarrier]) {
+    if !context.capabilities().supports((4, 2), "GL_ARB_shader_image_load_store") {
+        return;
+    }
+    let bits = barriers
+        .iter()
+        .fold(0, |bits, barrier| bits | barrier.to_gl_barrier_bits());
+    unsafe {
+        gl::MemoryBarrier(bits);
+    }
+    context.check_errors("memory_barrier");
+}
+
+impl fmt::Display for ShaderProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        for diagnostic in &self.diagnostics {
+            write!(f, "\n{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ShaderProgramError {}
+
+impl From<ShaderError> for ShaderProgramError {
+    fn from(err: ShaderError) -> Self {
+        ShaderProgramError {
+            message: err.message,
+            info_log: err.info_log,
+            diagnostics: err.diagnostics,
+        }
+    }
+}
+
+impl From<PreprocessorError> for ShaderProgramError {
+    fn from(err: PreprocessorError) -> Self {
+        ShaderProgramError {
+            message: err.message,
+            info_log: None,
+            diagnostics: Vec::new(),
+        }
+    }
+}
+
 impl ShaderProgram {
-    pub fn link(shaders: &[&Shader]) -> Result<Self, ShaderProgramError> {
-        let gl_id = unsafe {
-            let gl_id = gl::CreateProgram();
-            if gl_id == 0 {
-                return Err(ShaderProgramError {
-                    message: "Failed to create GPU shader program".into(),
-                    info_log: None,
-                });
+    /// Builds a program from a single `.glsl` file split into stages with
+    /// `#pragma stage <name>`, using the names from `ShaderTy::from_stage_name`.
+    pub fn from_file(context: &Context, path: &Path) -> Result<Self, ShaderProgramError> {
+        ShaderProgram::from_file_with_preprocessor(context, &ShaderPreprocessor::new(), path)
+    }
+
+    pub fn from_file_with_preprocessor(
+        context: &Context,
+        preprocessor: &ShaderPreprocessor,
+        path: &Path,
+    ) -> Result<Self, ShaderProgramError> {
+        let stages = preprocessor.preprocess_file(path)?.split_stages()?;
+        let shaders = stages
+            .iter()
+            .map(|(ty, source)| Shader::from_preprocessed(context, ty.clone(), source))
+            .collect::<Result<Vec<_>, _>>()?;
+        ShaderProgram::link(context, &shaders.iter().collect::<Vec<_>>())
+    }
+
+    pub fn link(context: &Context, shaders: &[&Shader]) -> Result<Self, ShaderProgramError> {
+        ShaderProgram::link_with_options(context, shaders, &LinkOptions::default())
+    }
+
+    pub fn link_with_options(
+        context: &Context,
+        shaders: &[&Shader],
+        options: &LinkOptions,
+    ) -> Result<Self, ShaderProgramError> {
+        let max_draw_buffers = context.capabilities().max_draw_buffers;
+        if let Some((output, location)) = options.frag_data_locations
+            .iter()
+            .find(|(_, location)| *location >= max_draw_buffers)
+        {
+            return Err(ShaderProgramError {
+                message: format!(
+                    "Fragment output {:?} location {} exceeds the {} draw buffers supported",
+                    output,
+                    location,
+                    max_draw_buffers,
+                ),
+                info_log: None,
+                diagnostics: Vec::new(),
+            });
+        }
+        let is_retrievable = options.binary_retrievable && supports_program_binaries(context);
+        let gl_id = create_program()?;
+        let diagnostics = unsafe {
+            if is_retrievable {
+                gl::ProgramParameteri(gl_id, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as _);
+            }
+            if !options.transform_feedback_varyings.is_empty() {
+                let c_varyings = options.transform_feedback_varyings
+                    .iter()
+                    .map(|varying| CString::new(varying.as_str()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| {
+                        gl::DeleteProgram(gl_id);
+                        ShaderProgramError {
+                            message: "Transform feedback varying contains a nul byte".into(),
+                            info_log: None,
+                            diagnostics: Vec::new(),
+                        }
+                    })?;
+                let varying_ptrs = c_varyings.iter().map(|c_varying| c_varying.as_ptr()).collect::<Vec<_>>();
+                gl::TransformFeedbackVaryings(
+                    gl_id,
+                    varying_ptrs.len() as _,
+                    varying_ptrs.as_ptr(),
+                    options.transform_feedback_mode.to_gl_buffer_mode(),
+                );
+            }
+            for (output, location) in &options.frag_data_locations {
+                let c_output = CString::new(output.as_str()).map_err(|_| {
+                    gl::DeleteProgram(gl_id);
+                    ShaderProgramError {
+                        message: "Fragment output name contains a nul byte".into(),
+                        info_log: None,
+                        diagnostics: Vec::new(),
+                    }
+                })?;
+                gl::BindFragDataLocation(gl_id, *location, c_output.as_ptr());
             }
             for shader in shaders {
                 gl::AttachShader(gl_id, shader.gl_id);
@@ -38,30 +214,83 @@ impl ShaderProgram {
             for shader in shaders {
                 gl::DetachShader(gl_id, shader.gl_id);
             }
-            let mut is_linked = 0;
-            gl::GetProgramiv(gl_id, gl::LINK_STATUS, &mut is_linked);
-            if is_linked == 0 {
-                let mut log_len = 0;
-                gl::GetProgramiv(gl_id, gl::INFO_LOG_LENGTH, &mut log_len);
-                let mut log_buf = vec![0; log_len as usize];
-                gl::GetProgramInfoLog(
-                    gl_id,
-                    log_len,
-                    std::ptr::null_mut(),
-                    log_buf.as_mut_ptr() as *mut _,
-                    );
-                return Err(ShaderProgramError {
-                    message: "Failed to link shader program".into(),
-                    info_log: String::from_utf8(log_buf).ok(),
-                });
-            }
-            gl_id
+            check_link_status(gl_id, "Failed to link shader program")?
+        };
+        check_uniform_block_sizes(context, gl_id)?;
+        context.check_errors("ShaderProgram::link");
+        Ok(ShaderProgram {
+            context: context.clone(),
+            gl_id,
+            diagnostics,
+        })
+    }
+
+    /// Loads a program previously retrieved with `get_binary`. Drivers reject
+    /// binaries from other GPUs or driver versions, in which case the caller
+    /// should relink from source.
+    pub fn from_binary(context: &Context, binary: &ProgramBinary) -> Result<Self, ShaderProgramError> {
+        if !supports_program_binaries(context) {
+            return Err(ShaderProgramError {
+                message: "Program binaries are not supported".into(),
+                info_log: None,
+                diagnostics: Vec::new(),
+            });
+        }
+        let gl_id = create_program()?;
+        let link_status = unsafe {
+            gl::ProgramBinary(
+                gl_id,
+                binary.format,
+                binary.data.as_ptr() as *const _,
+                binary.data.len() as _,
+            );
+            check_link_status(gl_id, "Failed to load shader program binary")
         };
+        // A rejected binary can also raise a GL error, which belongs to this
+        // call rather than to whatever the caller does next.
+        context.check_errors("ShaderProgram::from_binary");
+        let diagnostics = link_status?;
         Ok(ShaderProgram {
-            gl_id: gl_id,
+            context: context.clone(),
+            gl_id,
+            diagnostics,
         })
     }
 
+    /// Returns `None` when the driver does not support program binaries or the
+    /// program was not linked with `LinkOptions::binary_retrievable`.
+    pub fn get_binary(&self) -> Option<ProgramBinary> {
+        if !supports_program_binaries(&self.context) {
+            return None;
+        }
+        unsafe {
+            let mut binary_len = 0;
+            gl::GetProgramiv(self.gl_id, gl::PROGRAM_BINARY_LENGTH, &mut binary_len);
+            if binary_len <= 0 {
+                return None;
+            }
+            let mut data = vec![0u8; binary_len as usize];
+            let mut written = 0;
+            let mut format = 0;
+            gl::GetProgramBinary(
+                self.gl_id,
+                binary_len,
+                &mut written,
+                &mut format,
+                data.as_mut_ptr() as *mut _,
+            );
+            if written <= 0 {
+                return None;
+            }
+            data.truncate(written as usize);
+            self.context.check_errors("ShaderProgram::get_binary");
+            Some(ProgramBinary {
+                format,
+                data,
+            })
+        }
+    }
+
     pub fn get_uniform_location(&self, uniform: &str) -> UniformLocation {
         unsafe {
             if let Ok(c_name) = CString::new(uniform) {
@@ -77,13 +306,104 @@ impl ShaderProgram {
         }
     }
 
+    pub fn set_label(&mut self, label: &str) {
+        set_object_label(&self.context, gl::PROGRAM, self.gl_id, label);
+    }
+
     pub fn activate<'a>(&'a mut self) -> ActivatedShaderProgram<'a> {
         ActivatedShaderProgram::new(self)
     }
 }
 
+fn create_program() -> Result<GLuint, ShaderProgramError> {
+    let gl_id = unsafe { gl::CreateProgram() };
+    if gl_id == 0 {
+        return Err(ShaderProgramError {
+            message: "Failed to create GPU shader program".into(),
+            info_log: None,
+            diagnostics: Vec::new(),
+        });
+    }
+    Ok(gl_id)
+}
+
+fn supports_program_binaries(context: &Context) -> bool {
+    context.capabilities().supports((4, 1), "GL_ARB_get_program_binary")
+}
+
+/// Deletes the program and returns an error if linking failed, otherwise
+/// returns any warnings the driver reported.
+unsafe fn check_link_status(gl_id: GLuint, message: &str) -> Result<Vec<Diagnostic>, ShaderProgramError> {
+    let mut is_linked = 0;
+    gl::GetProgramiv(gl_id, gl::LINK_STATUS, &mut is_linked);
+    let info_log = get_program_info_log(gl_id);
+    let diagnostics = info_log
+        .as_ref()
+        .map(|log| parse_info_log(log, None))
+        .unwrap_or_default();
+    if is_linked == 0 {
+        gl::DeleteProgram(gl_id);
+        return Err(ShaderProgramError {
+            message: message.into(),
+            info_log,
+            diagnostics,
+        });
+    }
+    Ok(diagnostics)
+}
+
+/// Rejects programs whose uniform blocks are larger than the driver allows,
+/// which some drivers otherwise only report when drawing.
+fn check_uniform_block_sizes(context: &Context, gl_id: GLuint) -> Result<(), ShaderProgramError> {
+    let max_uniform_block_size = context.capabilities().max_uniform_block_size;
+    let mut num_blocks = 0;
+    unsafe {
+        gl::GetProgramiv(gl_id, gl::ACTIVE_UNIFORM_BLOCKS, &mut num_blocks);
+    }
+    for i in 0..num_blocks.max(0) as GLuint {
+        let mut block_size = 0;
+        unsafe {
+            gl::GetActiveUniformBlockiv(gl_id, i, gl::UNIFORM_BLOCK_DATA_SIZE, &mut block_size);
+        }
+        if block_size as u32 > max_uniform_block_size {
+            unsafe {
+                gl::DeleteProgram(gl_id);
+            }
+            return Err(ShaderProgramError {
+                message: format!(
+                    "Uniform block {} is {} bytes, exceeding the maximum of {}",
+                    i,
+                    block_size,
+                    max_uniform_block_size,
+                ),
+                info_log: None,
+                diagnostics: Vec::new(),
+            });
+        }
+    }
+    Ok(())
+}
+
+unsafe fn get_program_info_log(gl_id: GLuint) -> Option<String> {
+    let mut log_len = 0;
+    gl::GetProgramiv(gl_id, gl::INFO_LOG_LENGTH, &mut log_len);
+    if log_len <= 1 {
+        return None;
+    }
+    let mut log_buf = vec![0; log_len as usize];
+    gl::GetProgramInfoLog(
+        gl_id,
+        log_len,
+        std::ptr::null_mut(),
+        log_buf.as_mut_ptr() as *mut _,
+    );
+    log_buf.pop();
+    String::from_utf8(log_buf).ok()
+}
+
 impl Drop for ShaderProgram {
     fn drop(&mut self) {
+        self.context.forget_program(self.gl_id);
         unsafe {
             gl::DeleteProgram(self.gl_id);
         }
@@ -93,83 +413,142 @@ impl Drop for ShaderProgram {
 
 impl<'a> ActivatedShaderProgram<'a> {
     fn new(program: &'a mut ShaderProgram) -> Self {
-        unsafe {
-            gl::UseProgram(program.gl_id);
-        }
+        program.context.use_program(program.gl_id);
+        program.context.check_errors("ShaderProgram::activate");
         ActivatedShaderProgram {
-            program: program,
+            program,
+        }
+    }
+
+    /// Makes the program current again if another program was activated since
+    /// this guard was created.
+    pub fn ensure_active(&self) {
+        self.program.context.use_program(self.program.gl_id);
+    }
+
+    /// Runs the active compute program over a grid of work groups. Returns an
+    /// error when compute shaders are unsupported by the context.
+    pub fn dispatch_compute(&mut self, x: u32, y: u32, z: u32) -> Result<(), ShaderProgramError> {
+        if !self.program.context.capabilities().supports((4, 3), "GL_ARB_compute_shader") {
+            return Err(ShaderProgramError {
+                message: "Compute shaders are not supported by this context".into(),
+                info_log: None,
+                diagnostics: Vec::new(),
+            });
+        }
+        self.ensure_active();
+        unsafe {
+            gl::DispatchCompute(x, y, z);
         }
+        self.program.context.check_errors("ActivatedShaderProgram::dispatch_compute");
+        Ok(())
+    }
+
+    /// Captures vertex or geometry output into `buffers` until the returned
+    /// session is ended or dropped. The varyings must have been declared in
+    /// `LinkOptions` when linking.
+    pub fn begin_transform_feedback<'b>(
+        &'b mut self,
+        primitive: TransformFeedbackPrimitive,
+        buffers: &[&'b VertexBuffer],
+    ) -> TransformFeedback<'b> {
+        self.ensure_active();
+        TransformFeedback::begin(&self.program.context, primitive, buffers)
     }
 
     pub fn uniform_integer(&mut self, location: UniformLocation, value: i32) {
+        self.ensure_active();
         if let Some(gl_location) = location {
             unsafe { gl::Uniform1i(gl_location as _, value); }
         }
+        self.program.context.check_errors("ActivatedShaderProgram::uniform_integer");
     }
 
     pub fn uniform_float(&mut self, location: UniformLocation, value: f32) {
+        self.ensure_active();
         if let Some(gl_location) = location {
             unsafe { gl::Uniform1f(gl_location as _, value); }
         }
+        self.program.context.check_errors("ActivatedShaderProgram::uniform_float");
     }
 
     pub fn uniform_float_vec2(&mut self, location: UniformLocation, value: &[f32; 2]) {
+        self.ensure_active();
         if let Some(gl_location) = location {
             unsafe { gl::Uniform2fv(gl_location as _, 1, value.as_ptr() as *const _); }
         }
+        self.program.context.check_errors("ActivatedShaderProgram::uniform_float_vec2");
     }
 
     pub fn uniform_float_vec3(&mut self, location: UniformLocation, value: &[f32; 3]) {
+        self.ensure_active();
         if let Some(gl_location) = location {
             unsafe { gl::Uniform3fv(gl_location as _, 1, value.as_ptr() as *const _); }
         }
+        self.program.context.check_errors("ActivatedShaderProgram::uniform_float_vec3");
     }
 
     pub fn uniform_float_vec4(&mut self, location: UniformLocation, value: &[f32; 4]) {
+        self.ensure_active();
         if let Some(gl_location) = location {
             unsafe { gl::Uniform4fv(gl_location as _, 1, value.as_ptr() as *const _); }
         }
+        self.program.context.check_errors("ActivatedShaderProgram::uniform_float_vec4");
     }
 
     pub fn uniform_float_array(&mut self, location: UniformLocation, value: &[f32]) {
+        self.ensure_active();
         if let Some(gl_location) = location {
             unsafe { gl::Uniform1fv(gl_location as _, value.len() as _, value.as_ptr() as *const _); }
         }
+        self.program.context.check_errors("ActivatedShaderProgram::uniform_float_array");
     }
 
     pub fn uniform_float_vec2_array(&mut self, location: UniformLocation, value: &[[f32; 2]]) {
+        self.ensure_active();
         if let Some(gl_location) = location {
             unsafe { gl::Uniform2fv(gl_location as _, value.len() as _, value.as_ptr() as *const _); }
         }
+        self.program.context.check_errors("ActivatedShaderProgram::uniform_float_vec2_array");
     }
 
     pub fn uniform_float_vec3_array(&mut self, location: UniformLocation, value: &[[f32; 3]]) {
+        self.ensure_active();
         if let Some(gl_location) = location {
             unsafe { gl::Uniform3fv(gl_location as _, value.len() as _, value.as_ptr() as *const _); }
         }
+        self.program.context.check_errors("ActivatedShaderProgram::uniform_float_vec3_array");
     }
 
     pub fn uniform_float_vec4_array(&mut self, location: UniformLocation, value: &[[f32; 4]]) {
+        self.ensure_active();
         if let Some(gl_location) = location {
             unsafe { gl::Uniform4fv(gl_location as _, value.len() as _, value.as_ptr() as *const _); }
         }
+        self.program.context.check_errors("ActivatedShaderProgram::uniform_float_vec4_array");
     }
 
     pub fn uniform_float_mat2(&mut self, location: UniformLocation, value: &[f32; 4]) {
+        self.ensure_active();
         if let Some(gl_location) = location {
             unsafe { gl::UniformMatrix2fv(gl_location as _, 1, gl::FALSE, value.as_ptr() as *const _); }
         }
+        self.program.context.check_errors("ActivatedShaderProgram::uniform_float_mat2");
     }
 
     pub fn uniform_float_mat3(&mut self, location: UniformLocation, value: &[f32; 9]) {
+        self.ensure_active();
         if let Some(gl_location) = location {
             unsafe { gl::UniformMatrix3fv(gl_location as _, 1, gl::FALSE, value.as_ptr() as *const _); }
         }
+        self.program.context.check_errors("ActivatedShaderProgram::uniform_float_mat3");
     }
 
     pub fn uniform_float_mat4(&mut self, location: UniformLocation, value: &[f32; 16]) {
+        self.ensure_active();
         if let Some(gl_location) = location {
             unsafe { gl::UniformMatrix4fv(gl_location as _, 1, gl::FALSE, value.as_ptr() as *const _); }
         }
+        self.program.context.check_errors("ActivatedShaderProgram::uniform_float_mat4");
     }
 }
@@ -1,5 +1,9 @@
 use gl;
 use gl::types::*;
+use std;
+
+use context::Context;
+use debug::set_object_label;
 
 pub enum TextureFormat {
     R,
@@ -7,9 +11,41 @@ pub enum TextureFormat {
     Rgba,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TextureFilter {
     Linear,
     Nearest,
+    /// The mipmap filters are only valid for minification and need mipmaps,
+    /// e.g. from `BoundTexture::generate_mipmaps`.
+    NearestMipmapNearest,
+    LinearMipmapNearest,
+    NearestMipmapLinear,
+    LinearMipmapLinear,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureWrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+}
+
+pub enum ImageAccess {
+    ReadOnly,
+    ReadWrite,
+    WriteOnly,
+}
+
+/// Sized formats usable both as texture storage and as `image2D` formats for
+/// shader image load/store.
+pub enum ImageUnitFormat {
+    R32F,
+    R32I,
+    R32Ui,
+    Rg32F,
+    Rgba16F,
+    Rgba32F,
+    Rgba8,
 }
 
 #[derive(Debug)]
@@ -18,7 +54,16 @@ pub struct TextureError {
 }
 
 pub struct Texture {
+    context: Context,
+    pub gl_id: GLuint,
+}
+
+/// A `TEXTURE_2D_MULTISAMPLE` texture, rendered to through a framebuffer and
+/// read as a `sampler2DMS` or resolved with `Framebuffer::resolve_into`.
+pub struct MultisampleTexture {
+    context: Context,
     pub gl_id: GLuint,
+    pub samples: u32,
 }
 
 pub struct BoundTexture<'a> {
@@ -26,6 +71,47 @@ pub struct BoundTexture<'a> {
     texture: &'a mut Texture,
 }
 
+pub struct BoundImage<'a> {
+    pub unit_index: u32,
+    texture: &'a mut Texture,
+}
+
+impl ImageAccess {
+    pub fn to_gl_access(&self) -> GLenum {
+        match *self {
+            ImageAccess::ReadOnly => gl::READ_ONLY,
+            ImageAccess::ReadWrite => gl::READ_WRITE,
+            ImageAccess::WriteOnly => gl::WRITE_ONLY,
+        }
+    }
+}
+
+impl ImageUnitFormat {
+    pub fn to_gl_internal_format(&self) -> GLenum {
+        match *self {
+            ImageUnitFormat::R32F => gl::R32F,
+            ImageUnitFormat::R32I => gl::R32I,
+            ImageUnitFormat::R32Ui => gl::R32UI,
+            ImageUnitFormat::Rg32F => gl::RG32F,
+            ImageUnitFormat::Rgba16F => gl::RGBA16F,
+            ImageUnitFormat::Rgba32F => gl::RGBA32F,
+            ImageUnitFormat::Rgba8 => gl::RGBA8,
+        }
+    }
+
+    fn to_gl_format_and_ty(&self) -> (GLenum, GLenum) {
+        match *self {
+            ImageUnitFormat::R32F => (gl::RED, gl::FLOAT),
+            ImageUnitFormat::R32I => (gl::RED_INTEGER, gl::INT),
+            ImageUnitFormat::R32Ui => (gl::RED_INTEGER, gl::UNSIGNED_INT),
+            ImageUnitFormat::Rg32F => (gl::RG, gl::FLOAT),
+            ImageUnitFormat::Rgba16F => (gl::RGBA, gl::FLOAT),
+            ImageUnitFormat::Rgba32F => (gl::RGBA, gl::FLOAT),
+            ImageUnitFormat::Rgba8 => (gl::RGBA, gl::UNSIGNED_BYTE),
+        }
+    }
+}
+
 impl TextureFormat {
     pub fn to_gl_format(&self) -> GLenum {
         match *self {
@@ -34,6 +120,14 @@ impl TextureFormat {
             TextureFormat::Rgba => gl::RGBA,
         }
     }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        match *self {
+            TextureFormat::R => 1,
+            TextureFormat::Rgb => 3,
+            TextureFormat::Rgba => 4,
+        }
+    }
 }
 
 impl TextureFilter {
@@ -41,12 +135,30 @@ impl TextureFilter {
         match *self {
             TextureFilter::Linear => gl::LINEAR,
             TextureFilter::Nearest => gl::NEAREST,
+            TextureFilter::NearestMipmapNearest => gl::NEAREST_MIPMAP_NEAREST,
+            TextureFilter::LinearMipmapNearest => gl::LINEAR_MIPMAP_NEAREST,
+            TextureFilter::NearestMipmapLinear => gl::NEAREST_MIPMAP_LINEAR,
+            TextureFilter::LinearMipmapLinear => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+
+    pub fn uses_mipmaps(&self) -> bool {
+        !matches!(*self, TextureFilter::Linear | TextureFilter::Nearest)
+    }
+}
+
+impl TextureWrap {
+    pub fn to_gl_wrap(&self) -> GLenum {
+        match *self {
+            TextureWrap::Repeat => gl::REPEAT,
+            TextureWrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+            TextureWrap::ClampToEdge => gl::CLAMP_TO_EDGE,
         }
     }
 }
 
 impl Texture {
-    pub fn new() -> Result<Self, TextureError> {
+    pub fn new(context: &Context) -> Result<Self, TextureError> {
         let gl_id = unsafe {
             let mut gl_id = 0;
             gl::GenTextures(1, &mut gl_id);
@@ -57,31 +169,196 @@ impl Texture {
             }
             gl_id
         };
-        let mut texture = Texture { gl_id: gl_id };
-        texture.bind(0).set_filters(TextureFilter::Nearest, TextureFilter::Nearest);
+        let mut texture = Texture {
+            context: context.clone(),
+            gl_id,
+        };
+        texture.bind(0)?.set_filters(TextureFilter::Nearest, TextureFilter::Nearest);
         Ok(texture)
     }
 
-    pub fn bind<'a>(&'a mut self, unit_index: u32) -> BoundTexture<'a> {
-        BoundTexture::new(unit_index, self)
+    pub fn set_label(&mut self, label: &str) {
+        set_object_label(&self.context, gl::TEXTURE, self.gl_id, label);
+    }
+
+    pub fn bind<'a>(&'a mut self, unit_index: u32) -> Result<BoundTexture<'a>, TextureError> {
+        let max_texture_units = self.context.capabilities().max_texture_units;
+        if unit_index >= max_texture_units {
+            return Err(TextureError {
+                message: format!(
+                    "Texture unit {} exceeds the {} units supported",
+                    unit_index,
+                    max_texture_units,
+                ),
+            });
+        }
+        Ok(BoundTexture::new(unit_index, self))
+    }
+
+    /// Binds a mip level to an image unit for `imageLoad`/`imageStore`. Needs
+    /// GL 4.2 or ARB_shader_image_load_store.
+    pub fn bind_image<'a>(
+        &'a mut self,
+        unit_index: u32,
+        level: u32,
+        access: ImageAccess,
+        format: ImageUnitFormat,
+    ) -> Result<BoundImage<'a>, TextureError> {
+        if !self.context.capabilities().supports((4, 2), "GL_ARB_shader_image_load_store") {
+            return Err(TextureError {
+                message: "Image load/store is not supported by this context".into(),
+            });
+        }
+        Ok(BoundImage::new(unit_index, level, access, format, self))
     }
 }
 
-impl<'a> BoundTexture<'a> {
-    pub fn new(unit_index: u32, texture: &'a mut Texture) -> Self {
+impl MultisampleTexture {
+    /// Needs GL 3.2 or ARB_texture_multisample.
+    pub fn new(
+        context: &Context,
+        format: ImageUnitFormat,
+        width: usize,
+        height: usize,
+        samples: u32,
+    ) -> Result<Self, TextureError> {
+        let capabilities = context.capabilities();
+        if !capabilities.supports((3, 2), "GL_ARB_texture_multisample") {
+            return Err(TextureError {
+                message: "Multisample textures are not supported by this context".into(),
+            });
+        }
+        if samples == 0 || samples > capabilities.max_samples {
+            return Err(TextureError {
+                message: format!(
+                    "{} samples is outside the 1 to {} supported",
+                    samples,
+                    capabilities.max_samples,
+                ),
+            });
+        }
+        let max_texture_size = capabilities.max_texture_size as usize;
+        if width > max_texture_size || height > max_texture_size {
+            return Err(TextureError {
+                message: format!(
+                    "Texture size {}x{} exceeds the maximum of {}",
+                    width,
+                    height,
+                    max_texture_size,
+                ),
+            });
+        }
+        let gl_id = unsafe {
+            let mut gl_id = 0;
+            gl::GenTextures(1, &mut gl_id);
+            if gl_id == 0 {
+                return Err(TextureError {
+                    message: "Failed to create GPU texture".into(),
+                });
+            }
+            gl_id
+        };
+        context.bind_texture(0, gl::TEXTURE_2D_MULTISAMPLE, gl_id);
         unsafe {
-            gl::ActiveTexture(gl::TEXTURE0 + unit_index);
-            gl::BindTexture(gl::TEXTURE_2D, texture.gl_id);
+            gl::TexImage2DMultisample(
+                gl::TEXTURE_2D_MULTISAMPLE,
+                samples as _,
+                format.to_gl_internal_format(),
+                width as _,
+                height as _,
+                gl::TRUE,
+            );
+        }
+        context.check_errors("MultisampleTexture::new");
+        Ok(MultisampleTexture {
+            context: context.clone(),
+            gl_id,
+            samples,
+        })
+    }
+
+    pub fn set_label(&mut self, label: &str) {
+        set_object_label(&self.context, gl::TEXTURE, self.gl_id, label);
+    }
+
+    /// Binds the texture to `unit_index` for sampling with `texelFetch`.
+    pub fn bind(&mut self, unit_index: u32) -> Result<(), TextureError> {
+        let max_texture_units = self.context.capabilities().max_texture_units;
+        if unit_index >= max_texture_units {
+            return Err(TextureError {
+                message: format!(
+                    "Texture unit {} exceeds the {} units supported",
+                    unit_index,
+                    max_texture_units,
+                ),
+            });
+        }
+        self.context.bind_texture(unit_index, gl::TEXTURE_2D_MULTISAMPLE, self.gl_id);
+        self.context.check_errors("MultisampleTexture::bind");
+        Ok(())
+    }
+}
+
+impl Drop for MultisampleTexture {
+    fn drop(&mut self) {
+        self.context.forget_texture(self.gl_id);
+        unsafe {
+            gl::DeleteTextures(1, &self.gl_id);
         }
+        self.gl_id = 0;
+    }
+}
+
+impl<'a> BoundImage<'a> {
+    fn new(
+        unit_index: u32,
+        level: u32,
+        access: ImageAccess,
+        format: ImageUnitFormat,
+        texture: &'a mut Texture,
+    ) -> Self {
+        unsafe {
+            gl::BindImageTexture(
+                unit_index,
+                texture.gl_id,
+                level as _,
+                gl::FALSE,
+                0,
+                access.to_gl_access(),
+                format.to_gl_internal_format(),
+            );
+        }
+        texture.context.check_errors("Texture::bind_image");
+        BoundImage {
+            unit_index,
+            texture,
+        }
+    }
+
+    pub fn texture(&self) -> &Texture {
+        self.texture
+    }
+}
+
+impl<'a> BoundTexture<'a> {
+    pub fn new(unit_index: u32, texture: &'a mut Texture) -> Self {
+        texture.context.bind_texture(unit_index, gl::TEXTURE_2D, texture.gl_id);
+        texture.context.check_errors("Texture::bind");
         BoundTexture {
-            unit_index: unit_index,
-            texture: texture,
+            unit_index,
+            texture,
         }
     }
 
+    /// Rebinds the texture and makes its unit active if another bind happened
+    /// since this guard was created.
+    pub fn ensure_bound(&self) {
+        self.texture.context.bind_texture(self.unit_index, gl::TEXTURE_2D, self.texture.gl_id);
+    }
+
     pub fn set_filters(&mut self, min: TextureFilter, mag: TextureFilter) {
+        self.ensure_bound();
         unsafe {
-            gl::ActiveTexture(gl::TEXTURE0 + self.unit_index);
             gl::TexParameteri(
                 gl::TEXTURE_2D,
                 gl::TEXTURE_MIN_FILTER,
@@ -89,17 +366,71 @@ impl<'a> BoundTexture<'a> {
             );
             gl::TexParameteri(
                 gl::TEXTURE_2D,
-                gl::TEXTURE_MIN_FILTER,
+                gl::TEXTURE_MAG_FILTER,
                 mag.to_gl_filter() as _,
             );
         }
+        self.texture.context.check_errors("BoundTexture::set_filters");
+    }
+
+    /// Sets how coordinates outside 0 to 1 are handled along S and T.
+    pub fn set_wrap(&mut self, s: TextureWrap, t: TextureWrap) {
+        self.ensure_bound();
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, s.to_gl_wrap() as _);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, t.to_gl_wrap() as _);
+        }
+        self.texture.context.check_errors("BoundTexture::set_wrap");
+    }
+
+    /// Builds every mip level from level 0.
+    pub fn generate_mipmaps(&mut self) {
+        self.ensure_bound();
+        unsafe {
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+        }
+        self.texture.context.check_errors("BoundTexture::generate_mipmaps");
+    }
+
+    fn check_size(&self, width: usize, height: usize) -> Result<(), TextureError> {
+        let max_texture_size = self.texture.context.capabilities().max_texture_size as usize;
+        if width > max_texture_size || height > max_texture_size {
+            return Err(TextureError {
+                message: format!(
+                    "Texture size {}x{} exceeds the maximum of {}",
+                    width,
+                    height,
+                    max_texture_size,
+                ),
+            });
+        }
+        Ok(())
     }
 
-    pub fn upload_image_2d(&mut self, format: TextureFormat, width: usize, height: usize, pixels: &[u8]) {
+    pub fn upload_image_2d(
+        &mut self,
+        format: TextureFormat,
+        width: usize,
+        height: usize,
+        pixels: &[u8],
+    ) -> Result<(), TextureError> {
+        self.check_size(width, height)?;
+        let (size, alignment) = unpack_layout(&format, width, height);
+        if pixels.len() < size {
+            return Err(TextureError {
+                message: format!(
+                    "{} bytes of pixels for a {}x{} texture that needs {}",
+                    pixels.len(),
+                    width,
+                    height,
+                    size,
+                ),
+            });
+        }
+        self.ensure_bound();
         unsafe {
-            gl::ActiveTexture(gl::TEXTURE0 + self.unit_index);
             let gl_format = format.to_gl_format();
-            gl::ActiveTexture(gl::TEXTURE0 + self.unit_index);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, alignment);
             gl::TexImage2D(
                 gl::TEXTURE_2D,
                 0,
@@ -112,14 +443,73 @@ impl<'a> BoundTexture<'a> {
                 pixels.as_ptr() as *const _,
             );
         }
+        self.texture.context.check_errors("BoundTexture::upload_image_2d");
+        Ok(())
     }
+
+    /// Allocates uninitialized storage in a sized format, e.g. as the target of
+    /// compute shader image stores.
+    pub fn allocate_image_2d(
+        &mut self,
+        format: ImageUnitFormat,
+        width: usize,
+        height: usize,
+    ) -> Result<(), TextureError> {
+        self.check_size(width, height)?;
+        let (gl_format, gl_ty) = format.to_gl_format_and_ty();
+        self.ensure_bound();
+        unsafe {
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                format.to_gl_internal_format() as _,
+                width as _,
+                height as _,
+                0,
+                gl_format,
+                gl_ty,
+                std::ptr::null(),
+            );
+        }
+        self.texture.context.check_errors("BoundTexture::allocate_image_2d");
+        Ok(())
+    }
+}
+
+/// The size of tightly packed pixels and the largest unpack alignment their
+/// rows keep to, since GL pads each row to a multiple of the alignment.
+fn unpack_layout(format: &TextureFormat, width: usize, height: usize) -> (usize, GLint) {
+    let row_size = width * format.bytes_per_pixel();
+    let alignment = match row_size % 4 {
+        0 => 4,
+        2 => 2,
+        _ => 1,
+    };
+    (row_size * height, alignment)
 }
 
 impl Drop for Texture {
     fn drop(&mut self) {
+        self.context.forget_texture(self.gl_id);
         unsafe {
             gl::DeleteTextures(1, &self.gl_id);
         }
         self.gl_id = 0;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unpack_alignment_follows_the_row_size() {
+        // 9 byte rows would be padded to 12 with the default alignment.
+        assert_eq!(unpack_layout(&TextureFormat::Rgb, 3, 5), (45, 1));
+        assert_eq!(unpack_layout(&TextureFormat::Rgb, 4, 5), (60, 4));
+        assert_eq!(unpack_layout(&TextureFormat::Rgb, 2, 5), (30, 2));
+        assert_eq!(unpack_layout(&TextureFormat::R, 3, 2), (6, 1));
+        assert_eq!(unpack_layout(&TextureFormat::R, 6, 2), (12, 2));
+        assert_eq!(unpack_layout(&TextureFormat::Rgba, 3, 2), (24, 4));
+    }
+}
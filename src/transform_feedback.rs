@@ -0,0 +1,148 @@
+use gl;
+use gl::types::*;
+use std::marker::PhantomData;
+
+use buffer::VertexBuffer;
+use context::Context;
+
+#[derive(Default)]
+pub enum TransformFeedbackMode {
+    #[default]
+    Interleaved,
+    Separate,
+}
+
+pub enum TransformFeedbackPrimitive {
+    Lines,
+    Points,
+    Triangles,
+}
+
+/// An active capture session. Ends the capture when dropped; use `end` to
+/// also read back how many primitives were written.
+pub struct TransformFeedback<'a> {
+    context: Context,
+    query_gl_id: GLuint,
+    buffer_count: usize,
+    rasterizer_discard: bool,
+    ended: bool,
+    session: PhantomData<&'a mut ()>,
+}
+
+impl TransformFeedbackMode {
+    pub fn to_gl_buffer_mode(&self) -> GLenum {
+        match *self {
+            TransformFeedbackMode::Interleaved => gl::INTERLEAVED_ATTRIBS,
+            TransformFeedbackMode::Separate => gl::SEPARATE_ATTRIBS,
+        }
+    }
+}
+
+impl TransformFeedbackPrimitive {
+    pub fn to_gl_primitive(&self) -> GLenum {
+        match *self {
+            TransformFeedbackPrimitive::Lines => gl::LINES,
+            TransformFeedbackPrimitive::Points => gl::POINTS,
+            TransformFeedbackPrimitive::Triangles => gl::TRIANGLES,
+        }
+    }
+}
+
+impl<'a> TransformFeedback<'a> {
+    /// Binds `buffers` to consecutive transform feedback binding points. In
+    /// interleaved mode only the first buffer is written.
+    pub fn begin(
+        context: &Context,
+        primitive: TransformFeedbackPrimitive,
+        buffers: &[&'a VertexBuffer],
+    ) -> Self {
+        let mut query_gl_id = 0;
+        unsafe {
+            for (i, buffer) in buffers.iter().enumerate() {
+                gl::BindBufferBase(gl::TRANSFORM_FEEDBACK_BUFFER, i as _, buffer.gl_id);
+            }
+            gl::GenQueries(1, &mut query_gl_id);
+            if query_gl_id != 0 {
+                gl::BeginQuery(gl::TRANSFORM_FEEDBACK_PRIMITIVES_WRITTEN, query_gl_id);
+            }
+            gl::BeginTransformFeedback(primitive.to_gl_primitive());
+        }
+        context.check_errors("TransformFeedback::begin");
+        TransformFeedback {
+            context: context.clone(),
+            query_gl_id,
+            buffer_count: buffers.len(),
+            rasterizer_discard: false,
+            ended: false,
+            session: PhantomData,
+        }
+    }
+
+    /// Skips rasterization while capturing, for passes that only update
+    /// vertex data.
+    pub fn set_rasterizer_discard(&mut self, discard: bool) {
+        unsafe {
+            if discard {
+                gl::Enable(gl::RASTERIZER_DISCARD);
+            } else {
+                gl::Disable(gl::RASTERIZER_DISCARD);
+            }
+        }
+        self.rasterizer_discard = discard;
+    }
+
+    pub fn pause(&mut self) {
+        unsafe {
+            gl::PauseTransformFeedback();
+        }
+    }
+
+    pub fn resume(&mut self) {
+        unsafe {
+            gl::ResumeTransformFeedback();
+        }
+    }
+
+    /// Ends the capture and returns the number of primitives written. Waits
+    /// for the GPU to finish the captured draws.
+    pub fn end(mut self) -> u32 {
+        self.finish();
+        let mut primitives_written = 0;
+        if self.query_gl_id != 0 {
+            unsafe {
+                gl::GetQueryObjectuiv(self.query_gl_id, gl::QUERY_RESULT, &mut primitives_written);
+            }
+        }
+        self.context.check_errors("TransformFeedback::end");
+        primitives_written
+    }
+
+    fn finish(&mut self) {
+        if self.ended {
+            return;
+        }
+        self.ended = true;
+        unsafe {
+            gl::EndTransformFeedback();
+            if self.query_gl_id != 0 {
+                gl::EndQuery(gl::TRANSFORM_FEEDBACK_PRIMITIVES_WRITTEN);
+            }
+            if self.rasterizer_discard {
+                gl::Disable(gl::RASTERIZER_DISCARD);
+            }
+            for i in 0..self.buffer_count {
+                gl::BindBufferBase(gl::TRANSFORM_FEEDBACK_BUFFER, i as _, 0);
+            }
+        }
+    }
+}
+
+impl<'a> Drop for TransformFeedback<'a> {
+    fn drop(&mut self) {
+        self.finish();
+        unsafe {
+            gl::DeleteQueries(1, &self.query_gl_id);
+        }
+        self.query_gl_id = 0;
+    }
+}
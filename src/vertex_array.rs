@@ -1,7 +1,11 @@
 use gl;
 use gl::types::*;
+use std;
+use std::fmt;
 
 use buffer::{BoundVertexBuffer, BoundIndexBuffer};
+use context::Context;
+use debug::set_object_label;
 
 pub enum VertexAttribTy {
     Float,
@@ -16,6 +20,7 @@ pub struct VertexAttrib {
     pub normalized: bool,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DrawTy {
     Points,
     Lines,
@@ -24,8 +29,8 @@ pub enum DrawTy {
 }
 
 impl DrawTy {
-    pub fn to_gl_draw_ty(&self) -> GLenum {
-        match *self {
+    pub fn to_gl_draw_ty(self) -> GLenum {
+        match self {
             DrawTy::Points => gl::POINTS,
             DrawTy::Lines => gl::LINES,
             DrawTy::Triangles => gl::TRIANGLES,
@@ -35,11 +40,12 @@ impl DrawTy {
 }
 
 pub struct VertexArray {
+    context: Context,
     pub gl_id: GLuint,
 }
 
 impl VertexArray {
-    pub fn new() -> Result<VertexArray, VertexArrayError> {
+    pub fn new(context: &Context) -> Result<VertexArray, VertexArrayError> {
         let gl_id = unsafe {
             let mut gl_id = 0;
             gl::GenVertexArrays(1, &mut gl_id);
@@ -50,12 +56,17 @@ impl VertexArray {
             }
             gl_id
         };
-
+        context.check_errors("VertexArray::new");
         Ok(VertexArray {
-            gl_id: gl_id,
+            context: context.clone(),
+            gl_id,
         })
     }
 
+    pub fn set_label(&mut self, label: &str) {
+        set_object_label(&self.context, gl::VERTEX_ARRAY, self.gl_id, label);
+    }
+
     pub fn bind<'a>(&'a mut self, vertex_buffer: BoundVertexBuffer<'a>) -> BoundVertexArray<'a> {
         BoundVertexArray::new(self, vertex_buffer)
     }
@@ -63,6 +74,7 @@ impl VertexArray {
 
 impl Drop for VertexArray {
     fn drop(&mut self) {
+        self.context.forget_vertex_array(self.gl_id);
         unsafe {
             gl::DeleteVertexArrays(1, &self.gl_id);
         }
@@ -77,41 +89,78 @@ pub struct BoundVertexArray<'a> {
 
 impl<'a> BoundVertexArray<'a> {
     pub fn new(vertex_array: &'a mut VertexArray, vertex_buffer: BoundVertexBuffer<'a>) -> Self {
-        unsafe {
-            gl::BindVertexArray(vertex_array.gl_id);
-        }
+        vertex_array.context.bind_vertex_array(vertex_array.gl_id);
+        vertex_array.context.check_errors("VertexArray::bind");
         BoundVertexArray {
-            vertex_array: vertex_array,
-            vertex_buffer: vertex_buffer,
+            vertex_array,
+            vertex_buffer,
         }
     }
 
-    pub fn set_vertex_attribs(&mut self, stride: usize, attribs: &[VertexAttrib]) {
+    pub fn ensure_bound(&self) {
+        self.vertex_array.context.bind_vertex_array(self.vertex_array.gl_id);
+    }
+
+    pub fn set_vertex_attribs(&mut self, stride: usize, attribs: &[VertexAttrib]) -> Result<(), VertexArrayError> {
+        let max_vertex_attribs = self.vertex_array.context.capabilities().max_vertex_attribs as usize;
+        if attribs.len() > max_vertex_attribs {
+            return Err(VertexArrayError {
+                message: format!(
+                    "{} vertex attributes exceed the {} supported",
+                    attribs.len(),
+                    max_vertex_attribs,
+                ),
+            });
+        }
+        self.ensure_bound();
+        self.vertex_buffer.ensure_bound();
         for (i, attrib) in attribs.iter().enumerate() {
-            let (size, gl_ty) = match attrib.ty {
-                VertexAttribTy::Float => (1, gl::FLOAT),
-                VertexAttribTy::FloatVec2 => (2, gl::FLOAT),
-                VertexAttribTy::FloatVec3 => (3, gl::FLOAT),
-                VertexAttribTy::FloatVec4 => (4, gl::FLOAT),
-            };
-            unsafe {
-                gl::EnableVertexAttribArray(i as _);
-                gl::VertexAttribPointer(
-                    i as _,
-                    size,
-                    gl_ty,
-                    attrib.normalized as _,
-                    stride as _,
-                    attrib.offset as *const _,
-                );
-            }
+            enable_vertex_attrib(i as _, stride, attrib);
+        }
+        self.vertex_array.context.check_errors("BoundVertexArray::set_vertex_attribs");
+        Ok(())
+    }
+
+    /// Like `set_vertex_attribs` for a single attribute at an explicit
+    /// location, for layouts where some locations are left unused.
+    pub fn set_vertex_attrib(
+        &mut self,
+        location: u32,
+        stride: usize,
+        attrib: &VertexAttrib,
+    ) -> Result<(), VertexArrayError> {
+        let max_vertex_attribs = self.vertex_array.context.capabilities().max_vertex_attribs;
+        if location >= max_vertex_attribs {
+            return Err(VertexArrayError {
+                message: format!(
+                    "Vertex attribute location {} exceeds the {} supported",
+                    location,
+                    max_vertex_attribs,
+                ),
+            });
         }
+        self.ensure_bound();
+        self.vertex_buffer.ensure_bound();
+        enable_vertex_attrib(location, stride, attrib);
+        self.vertex_array.context.check_errors("BoundVertexArray::set_vertex_attrib");
+        Ok(())
     }
 
     pub fn draw_arrays(&mut self, draw_ty: DrawTy, first: usize, count: usize) {
+        self.ensure_bound();
         unsafe {
             gl::DrawArrays(draw_ty.to_gl_draw_ty(), first as _, count as _);
         }
+        self.vertex_array.context.check_errors("BoundVertexArray::draw_arrays");
+    }
+
+    /// Draws `instance_count` copies, distinguished by `gl_InstanceID`.
+    pub fn draw_arrays_instanced(&mut self, draw_ty: DrawTy, first: usize, count: usize, instance_count: usize) {
+        self.ensure_bound();
+        unsafe {
+            gl::DrawArraysInstanced(draw_ty.to_gl_draw_ty(), first as _, count as _, instance_count as _);
+        }
+        self.vertex_array.context.check_errors("BoundVertexArray::draw_arrays_instanced");
     }
 
     pub fn draw_elements<'b>(
@@ -120,14 +169,59 @@ impl<'a> BoundVertexArray<'a> {
         index_buffer: BoundIndexBuffer<'b>,
         count: usize
     ) {
+        self.ensure_bound();
+        index_buffer.ensure_bound();
         unsafe {
             gl::DrawElements(
                 draw_ty.to_gl_draw_ty(),
                 count as _,
                 gl::UNSIGNED_INT,
-                0 as *const _,
+                std::ptr::null(),
             );
         }
+        self.vertex_array.context.check_errors("BoundVertexArray::draw_elements");
+    }
+
+    /// Draws `count` indices starting at index `first`.
+    pub fn draw_elements_range<'b>(
+        &mut self,
+        draw_ty: DrawTy,
+        index_buffer: BoundIndexBuffer<'b>,
+        first: usize,
+        count: usize,
+    ) {
+        self.ensure_bound();
+        index_buffer.ensure_bound();
+        unsafe {
+            gl::DrawElements(
+                draw_ty.to_gl_draw_ty(),
+                count as _,
+                gl::UNSIGNED_INT,
+                (first * std::mem::size_of::<u32>()) as *const _,
+            );
+        }
+        self.vertex_array.context.check_errors("BoundVertexArray::draw_elements_range");
+    }
+
+    pub fn draw_elements_instanced<'b>(
+        &mut self,
+        draw_ty: DrawTy,
+        index_buffer: BoundIndexBuffer<'b>,
+        count: usize,
+        instance_count: usize,
+    ) {
+        self.ensure_bound();
+        index_buffer.ensure_bound();
+        unsafe {
+            gl::DrawElementsInstanced(
+                draw_ty.to_gl_draw_ty(),
+                count as _,
+                gl::UNSIGNED_INT,
+                std::ptr::null(),
+                instance_count as _,
+            );
+        }
+        self.vertex_array.context.check_errors("BoundVertexArray::draw_elements_instanced");
     }
 }
 
@@ -136,3 +230,31 @@ pub struct VertexArrayError {
     pub message: String,
 }
 
+impl fmt::Display for VertexArrayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for VertexArrayError {}
+
+fn enable_vertex_attrib(location: u32, stride: usize, attrib: &VertexAttrib) {
+    let (size, gl_ty) = match attrib.ty {
+        VertexAttribTy::Float => (1, gl::FLOAT),
+        VertexAttribTy::FloatVec2 => (2, gl::FLOAT),
+        VertexAttribTy::FloatVec3 => (3, gl::FLOAT),
+        VertexAttribTy::FloatVec4 => (4, gl::FLOAT),
+    };
+    unsafe {
+        gl::EnableVertexAttribArray(location);
+        gl::VertexAttribPointer(
+            location,
+            size,
+            gl_ty,
+            attrib.normalized as _,
+            stride as _,
+            attrib.offset as *const _,
+        );
+    }
+}
+